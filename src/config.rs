@@ -0,0 +1,164 @@
+//! Server configuration file
+
+use crate::{
+    error::Error,
+    onnx::{Registry, RouteTable},
+    serve::{
+        BreakerConfig, CacheConfig, Experiment, FallbackConfig, FallbackOrder, FeedbackConfig,
+        MemoryConfig, SampleConfig, SpendConfig,
+    },
+    Result,
+};
+use serde::Deserialize;
+use std::{collections::HashMap, path::Path};
+
+/// Configuration loaded from the `--config` JSON file.
+///
+/// ```json
+/// {
+///   "api_key": "...",
+///   "limit": 3,
+///   "models": {
+///     "new_game": {
+///       "model": "new_game.onnx",
+///       "kind": "pair_classifier",
+///       "input_shape": [52, 52],
+///       "grayscale": false
+///     }
+///   },
+///   "routes": [
+///     {
+///       "game_variant": "3d_rollball_objects",
+///       "pattern": "face in the direction of the hand",
+///       "model": "3d_rollball_objects_hand.onnx"
+///     }
+///   ],
+///   "experiments": {
+///     "3d_rollball_objects": {
+///       "mode": "shadow",
+///       "model": "3d_rollball_objects_v2.onnx",
+///       "save_dir": "/var/lib/fs/shadow"
+///     }
+///   },
+///   "samples": {
+///     "dir": "/var/lib/fs/samples",
+///     "min_confidence": 0.5,
+///     "max_bytes": 1073741824
+///   },
+///   "feedback": {
+///     "window": 100,
+///     "min_accuracy": 0.6
+///   },
+///   "breaker": {
+///     "failures": 5,
+///     "cooldown": 30
+///   },
+///   "fallbacks": [
+///     { "provider": "capsolver", "key": "...", "limit": 3, "timeout": 30, "cost": 0.8 },
+///     { "provider": "yescaptcha", "key": "...", "variants": ["3d_rollball_objects"], "retries": 3, "concurrency": 8 }
+///   ],
+///   "fallback_order": "cheapest",
+///   "fallback_retry_invalid": true,
+///   "spend": { "daily_budget": 50.0, "balance_interval": 300 },
+///   "cache": { "capacity": 10000, "ttl": 3600, "path": "cache.json" },
+///   "memory": { "threshold": 4, "min_confidence": 0.95, "path": "memory.json" }
+/// }
+/// ```
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct Config {
+    /// API key, overrides `--api-key`
+    pub api_key: Option<String>,
+    /// Multiple image submission limit, overrides `--limit`
+    pub limit: Option<usize>,
+    /// Model registry, extends or replaces the compiled-in model specs
+    pub models: Registry,
+    /// Instruction-aware routes, matched in order
+    pub routes: RouteTable,
+    /// Candidate model experiments, keyed by game variant
+    pub experiments: HashMap<String, Experiment>,
+    /// Sample store, disabled if unset
+    pub samples: Option<SampleConfig>,
+    /// Answer feedback
+    pub feedback: FeedbackConfig,
+    /// Circuit breakers of the local models and fallback providers
+    pub breaker: BreakerConfig,
+    /// Fallback providers, tried after the command line provider
+    pub fallbacks: Vec<FallbackConfig>,
+    /// The order the fallback providers are tried in
+    pub fallback_order: FallbackOrder,
+    /// Whether an invalid fallback response moves on to the next provider
+    pub fallback_retry_invalid: bool,
+    /// Fallback daily budget and balance polling
+    pub spend: SpendConfig,
+    /// Result cache, disabled if unset
+    pub cache: Option<CacheConfig>,
+    /// Tile memory, disabled if unset
+    pub memory: Option<MemoryConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            api_key: None,
+            limit: None,
+            models: Registry::default(),
+            routes: RouteTable::default(),
+            experiments: HashMap::new(),
+            samples: None,
+            feedback: FeedbackConfig::default(),
+            breaker: BreakerConfig::default(),
+            fallbacks: vec![],
+            fallback_order: FallbackOrder::default(),
+            fallback_retry_invalid: true,
+            spend: SpendConfig::default(),
+            cache: None,
+            memory: None,
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration from a JSON file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let data = std::fs::read(path)?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    /// Check the settings that can't be checked while parsing.
+    pub fn validate(&self) -> Result<()> {
+        if self.limit == Some(0) {
+            return Err(Error::InvalidConfig("limit must be at least 1".to_owned()));
+        }
+        if self.api_key.as_deref() == Some("") {
+            return Err(Error::InvalidConfig("api_key must not be empty".to_owned()));
+        }
+        for fallback in &self.fallbacks {
+            let name = fallback
+                .name
+                .as_deref()
+                .unwrap_or(fallback.provider.as_str());
+            if fallback.key.is_empty() {
+                return Err(Error::InvalidConfig(format!("fallback {name} has no key")));
+            }
+            if fallback.limit == 0 {
+                return Err(Error::InvalidConfig(format!(
+                    "fallback {name} limit must be at least 1"
+                )));
+            }
+        }
+        let variants = self
+            .routes
+            .iter()
+            .map(|route| &route.game_variant)
+            .chain(self.experiments.keys());
+        for game_variant in variants {
+            if self.models.get(game_variant).is_err() {
+                return Err(Error::InvalidConfig(format!(
+                    "unknown game variant {game_variant}"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
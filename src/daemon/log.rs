@@ -0,0 +1,153 @@
+use crate::Result;
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufRead, BufReader},
+    os::unix::fs::MetadataExt,
+    path::PathBuf,
+};
+use tracing::Level;
+
+/// Returns the level of a log line written by the text or JSON formatter,
+/// `None` for lines without one, e.g. the continuation of a multi-line entry.
+pub fn level(line: &str) -> Option<Level> {
+    if line.trim_start().starts_with('{') {
+        let value: serde_json::Value = serde_json::from_str(line).ok()?;
+        return value.get("level")?.as_str()?.parse().ok();
+    }
+
+    // The level follows the timestamp, possibly wrapped in color codes
+    strip_ansi(line)
+        .split_whitespace()
+        .take(3)
+        .find(|token| ["TRACE", "DEBUG", "INFO", "WARN", "ERROR"].contains(token))?
+        .parse()
+        .ok()
+}
+
+/// Remove the ANSI escape sequences of a colored line.
+fn strip_ansi(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // Skip up to and including the final byte of the sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// Keeps the lines of at least the minimum level, lines without a level
+/// belong to the entry before them.
+pub struct LevelFilter {
+    min: Option<Level>,
+    keep: bool,
+}
+
+impl LevelFilter {
+    pub fn new(min: Option<Level>) -> LevelFilter {
+        LevelFilter { min, keep: true }
+    }
+
+    pub fn keep(&mut self, line: &str) -> bool {
+        if let (Some(min), Some(level)) = (self.min, level(line)) {
+            // More severe levels compare lower
+            self.keep = level <= min;
+        }
+        self.min.is_none() || self.keep
+    }
+}
+
+/// Returns the last `n` lines, all of them if `n` is unset.
+pub fn tail(lines: Vec<String>, n: Option<usize>) -> Vec<String> {
+    match n {
+        Some(n) => {
+            let mut last = VecDeque::with_capacity(n);
+            for line in lines {
+                if last.len() == n {
+                    last.pop_front();
+                }
+                if n > 0 {
+                    last.push_back(line);
+                }
+            }
+            last.into()
+        }
+        None => lines,
+    }
+}
+
+/// Reads the lines appended to a log file, following it across rotations.
+pub struct Follower {
+    path: PathBuf,
+    reader: Option<BufReader<File>>,
+    inode: u64,
+    position: u64,
+    partial: Vec<u8>,
+}
+
+impl Follower {
+    /// Follow the file from its start.
+    pub fn new(path: PathBuf) -> Follower {
+        Follower {
+            path,
+            reader: None,
+            inode: 0,
+            position: 0,
+            partial: vec![],
+        }
+    }
+
+    /// Returns the complete lines written since the last poll. The file is
+    /// read again from its start if it was rotated or truncated.
+    pub fn poll(&mut self) -> Result<Vec<String>> {
+        let metadata = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut lines = vec![];
+        if metadata.ino() != self.inode || metadata.len() < self.position {
+            // Drain what the rotated file got before the switch
+            if metadata.ino() != self.inode {
+                lines.extend(self.read()?);
+            }
+            self.reader = Some(BufReader::new(File::open(&self.path)?));
+            self.inode = metadata.ino();
+            self.position = 0;
+            self.partial.clear();
+        }
+
+        lines.extend(self.read()?);
+        Ok(lines)
+    }
+
+    fn read(&mut self) -> Result<Vec<String>> {
+        let Some(reader) = self.reader.as_mut() else {
+            return Ok(vec![]);
+        };
+
+        let mut lines = vec![];
+        loop {
+            let read = reader.read_until(b'\n', &mut self.partial)?;
+            if read == 0 {
+                break;
+            }
+            self.position += read as u64;
+            if self.partial.ends_with(b"\n") {
+                let line = std::mem::take(&mut self.partial);
+                let line = String::from_utf8_lossy(&line);
+                lines.push(line.trim_end_matches(['\n', '\r']).to_owned());
+            }
+        }
+        Ok(lines)
+    }
+}
@@ -0,0 +1,388 @@
+pub mod log;
+pub mod paths;
+pub mod rotate;
+pub mod service;
+
+use crate::{
+    error::Error, serve, BootArgs, DaemonPaths, LogArgs, Result, ServiceCommand, StartArgs,
+    StatusArgs,
+};
+use daemonize::Daemonize;
+use log::{Follower, LevelFilter};
+use paths::running_pid;
+use rotate::{LogFile, RotatePolicy};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// How often `fs log --follow` checks the logs for new lines.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Run the server
+pub fn run(args: BootArgs) -> Result<()> {
+    serve::run(args)
+}
+
+/// Start the daemon
+#[cfg(target_family = "unix")]
+pub fn start(mut args: StartArgs) -> Result<()> {
+    use crate::homedir::setting_dir;
+
+    let pid_file = args.paths.pid_file();
+    if let Some(pid) = running_pid(&pid_file)? {
+        println!("fs is already running with pid: {}", pid);
+        return Ok(());
+    }
+
+    // The logs are appended to, the rotated files keep the previous runs
+    let stdout_log = args.paths.stdout_log();
+    let stderr_log = args.paths.stderr_log();
+    // The log directories created now, or the runtime directory, are the daemon's own
+    let log_dirs = [&stdout_log, &stderr_log]
+        .into_iter()
+        .filter_map(|log| log.parent())
+        .filter(|dir| !dir.exists() || *dir == paths::runtime_dir())
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    args.paths.create_dirs()?;
+    let stdout = rotate::open(&stdout_log)?;
+    let stderr = rotate::open(&stderr_log)?;
+
+    // `fs ps` queries the daemon on the admin socket next to the PID file
+    let admin_socket = args
+        .boot
+        .admin_socket
+        .get_or_insert_with(|| args.paths.admin_socket())
+        .clone();
+
+    // The user the daemon runs as, if started with sudo
+    let real_user = std::env::var("SUDO_USER")
+        .ok()
+        .and_then(|user| nix::unistd::User::from_name(&user).ok().flatten());
+
+    // The logs are rotated after the privileges are dropped, hand them over
+    // along with the daemon's own directories
+    if let Some(ref user) = real_user {
+        let logs = [stdout_log.as_path(), stderr_log.as_path()];
+        rotate::chown(
+            logs.into_iter()
+                .chain(log_dirs.iter().map(PathBuf::as_path)),
+            user,
+        )?;
+        for dir in logs.into_iter().filter_map(Path::parent) {
+            if !log_dirs.iter().any(|own| own == dir) {
+                eprintln!(
+                    "The logs in {} can only be rotated if {} can write to it",
+                    dir.display(),
+                    user.name
+                );
+            }
+        }
+    }
+
+    // The admin socket is bound before the privileges are dropped, the runtime
+    // directory may be writable by root only
+    let admin = match serve::admin::bind(&admin_socket) {
+        Ok(listener) => {
+            if let Some(ref user) = real_user {
+                std::os::unix::fs::chown(
+                    &admin_socket,
+                    Some(user.uid.as_raw()),
+                    Some(user.gid.as_raw()),
+                )?;
+            }
+            Some(listener)
+        }
+        Err(err) => {
+            eprintln!("Running without the admin socket: {}", err);
+            args.boot.admin_socket = None;
+            None
+        }
+    };
+
+    let mut daemonize = Daemonize::new()
+        .pid_file(&pid_file) // Every method except `new` and `start`
+        .chown_pid_file(true) // is optional, see `Daemonize` documentation
+        .umask(0o027) // New files aren't accessible to other users.
+        .stdout(stdout) // Redirect stdout to the stdout log.
+        .stderr(stderr) // Redirect stderr to the stderr log.
+        .privileged_action(|| "Executed before drop privileges");
+
+    if let Some(real_user) = real_user {
+        #[cfg(not(target_os = "windows"))]
+        setting_dir(real_user.dir);
+        daemonize = daemonize
+            .user(real_user.name.as_str())
+            .group(real_user.gid.as_raw());
+    }
+
+    daemonize
+        .start()
+        .map_err(|err| Error::DaemonizeError(err.to_string()))?;
+
+    // Rotate the logs the standard streams are redirected to
+    rotate::spawn(
+        vec![LogFile::new(stdout_log, 1), LogFile::new(stderr_log, 2)],
+        RotatePolicy::from(&args),
+    );
+
+    match admin {
+        Some(admin) => serve::run_with_admin(args.boot, admin),
+        None => run(args.boot),
+    }
+}
+
+/// Stop the daemon
+#[cfg(target_family = "unix")]
+pub fn stop(paths: &DaemonPaths) -> Result<()> {
+    use nix::{errno::Errno, sys::signal, unistd::Pid};
+
+    let pid_file = paths.pid_file();
+    if let Some(pid) = running_pid(&pid_file)? {
+        for _ in 0..360 {
+            match signal::kill(Pid::from_raw(pid), signal::SIGINT) {
+                Ok(()) => std::thread::sleep(std::time::Duration::from_secs(1)),
+                Err(Errno::ESRCH) => break,
+                Err(err) => return Err(std::io::Error::from(err).into()),
+            }
+        }
+        let _ = std::fs::remove_file(pid_file);
+    }
+
+    Ok(())
+}
+
+/// Restart the daemon
+#[cfg(target_family = "unix")]
+pub fn restart(args: StartArgs) -> Result<()> {
+    stop(&args.paths)?;
+    start(args)
+}
+
+/// Show the status of the daemon, queried on its admin socket if reachable
+#[cfg(target_family = "unix")]
+pub fn status(args: StatusArgs) -> Result<()> {
+    use serve::admin::{self, AdminStatus};
+    use sysinfo::{Pid, System};
+
+    let socket = args
+        .admin_socket
+        .unwrap_or_else(|| args.paths.admin_socket());
+    let admin = match admin::status(&socket) {
+        Ok(status) => Some(status),
+        Err(Error::IoError(_)) => None,
+        Err(err) => {
+            eprintln!("Failed to query {}: {}", socket.display(), err);
+            None
+        }
+    };
+
+    // The server may run without a PID file, e.g. under systemd
+    let pid = match admin {
+        Some(ref status) => status.pid as i32,
+        None => match running_pid(&args.paths.pid_file())? {
+            Some(pid) => pid,
+            None => {
+                println!("fs is not running");
+                return Ok(());
+            }
+        },
+    };
+
+    let mut sys = System::new();
+    sys.refresh_process(Pid::from_u32(pid as u32));
+    let process = sys
+        .process(Pid::from_u32(pid as u32))
+        .ok_or_else(|| Error::NotRunning)?;
+
+    println!("{:<6} {:<6}  {:<6}", "PID", "CPU(%)", "MEM(MB)");
+    println!(
+        "{:<6}   {:<6.1}  {:<6.1}",
+        pid,
+        process.cpu_usage(),
+        (process.memory() as f64) / 1024.0 / 1024.0
+    );
+
+    let Some(AdminStatus {
+        version,
+        uptime,
+        bind,
+        in_flight,
+        models,
+        requests,
+        fallbacks,
+        ..
+    }) = admin
+    else {
+        return Ok(());
+    };
+
+    println!();
+    println!("Version:   {}", version);
+    println!("Uptime:    {}", format_uptime(uptime));
+    println!("Bind:      {}", bind.as_deref().unwrap_or("-"));
+    println!("In flight: {}", in_flight);
+
+    if !models.is_empty() {
+        println!();
+        println!(
+            "{:<32} {:<40} {:<12} {:<6}",
+            "VARIANT", "MODEL", "VERSION", "ACTIVE"
+        );
+        for model in models {
+            println!(
+                "{:<32} {:<40} {:<12} {}",
+                model.game_variant,
+                model.model,
+                model.version.as_deref().unwrap_or("-"),
+                model.active
+            );
+        }
+    }
+
+    if !requests.is_empty() {
+        println!();
+        println!("{:<32} {:<8} {:<8}", "VARIANT", "OK", "ERROR");
+        for variant in requests {
+            println!(
+                "{:<32} {:<8} {}",
+                variant.game_variant, variant.ok, variant.error
+            );
+        }
+    }
+
+    if !fallbacks.is_empty() {
+        println!();
+        println!(
+            "{:<16} {:<8} {:<8} {:<8}",
+            "FALLBACK", "TASKS", "IMAGES", "SPEND"
+        );
+        for fallback in fallbacks {
+            println!(
+                "{:<16} {:<8} {:<8} {:.2}",
+                fallback.provider, fallback.tasks, fallback.images, fallback.spend
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Format seconds as e.g. `1d 2h 3m 4s`.
+pub fn format_uptime(secs: u64) -> String {
+    let units = [(86400, "d"), (3600, "h"), (60, "m")];
+    let mut rest = secs;
+    let mut parts = vec![];
+    for (unit, suffix) in units {
+        if rest >= unit || !parts.is_empty() {
+            parts.push(format!("{}{}", rest / unit, suffix));
+            rest %= unit;
+        }
+    }
+    parts.push(format!("{rest}s"));
+    parts.join(" ")
+}
+
+/// Reload the configuration of the daemon, through its admin socket if
+/// reachable, otherwise with SIGHUP
+#[cfg(target_family = "unix")]
+pub fn reload(args: StatusArgs) -> Result<()> {
+    use nix::{sys::signal, unistd::Pid};
+
+    let socket = args
+        .admin_socket
+        .unwrap_or_else(|| args.paths.admin_socket());
+    match serve::admin::query(&socket, "reload") {
+        Ok(_) => println!("Reloaded the configuration"),
+        Err(Error::IoError(_)) => match running_pid(&args.paths.pid_file())? {
+            Some(pid) => {
+                signal::kill(Pid::from_raw(pid), signal::SIGHUP).map_err(std::io::Error::from)?;
+                println!("Sent SIGHUP to {pid}, the outcome is logged");
+            }
+            None => println!("fs is not running"),
+        },
+        Err(err) => return Err(err),
+    }
+    Ok(())
+}
+
+/// Manage the systemd service
+#[cfg(target_family = "unix")]
+pub fn service(command: ServiceCommand) -> Result<()> {
+    match command {
+        ServiceCommand::Install(args) => service::install(args),
+    }
+}
+
+/// Show the log of the daemon
+#[cfg(target_family = "unix")]
+pub fn log(args: LogArgs) -> Result<()> {
+    /// A log being printed, the placeholder is printed before its first line
+    /// and whenever the output switches to it.
+    struct Source {
+        placeholder: &'static str,
+        follower: Follower,
+        filter: LevelFilter,
+    }
+
+    let mut paths: Vec<(&'static str, PathBuf)> = vec![];
+    if !args.stderr {
+        paths.push(("STDOUT>", args.paths.stdout_log()));
+    }
+    paths.push(("STDERR>", args.paths.stderr_log()));
+
+    let mut sources = paths
+        .into_iter()
+        .map(|(placeholder, path)| Source {
+            placeholder,
+            follower: Follower::new(path),
+            filter: LevelFilter::new(args.level),
+        })
+        .collect::<Vec<_>>();
+
+    impl Source {
+        /// Returns the new lines of at least the level filtered.
+        fn poll(&mut self) -> Result<Vec<String>> {
+            let lines = self.follower.poll()?;
+            Ok(lines
+                .into_iter()
+                .filter(|line| self.filter.keep(line))
+                .collect())
+        }
+    }
+
+    let mut current = None;
+    let mut print = |index: usize, source: &Source, lines: Vec<String>| {
+        if lines.is_empty() {
+            return;
+        }
+        if current != Some(index) {
+            current = Some(index);
+            println!("{}", source.placeholder);
+        }
+        for line in lines {
+            println!("{line}");
+        }
+    };
+
+    // Print the existing lines of each log
+    for (index, source) in sources.iter_mut().enumerate() {
+        let lines = log::tail(source.poll()?, args.tail);
+        print(index, source, lines);
+    }
+
+    if !args.follow {
+        return Ok(());
+    }
+
+    // Print the lines as they are written
+    loop {
+        std::thread::sleep(FOLLOW_INTERVAL);
+        for (index, source) in sources.iter_mut().enumerate() {
+            match source.poll() {
+                Ok(lines) => print(index, source, lines),
+                Err(err) => eprintln!("Error reading log: {}", err),
+            }
+        }
+    }
+}
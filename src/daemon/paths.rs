@@ -0,0 +1,128 @@
+use crate::{error::Error, DaemonPaths, Result};
+use std::{
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+};
+use sysinfo::{Pid, System};
+
+/// Length the kernel truncates process names to on Linux.
+const COMM_LEN: usize = 15;
+
+/// Returns the directory of the daemon's PID and log files: `/var/run/fs` for
+/// root, `$XDG_RUNTIME_DIR/fs` for other users, or `/tmp/fs-<uid>` if unset.
+/// The directory is the daemon's own, it is handed to the user the daemon runs
+/// as when started with sudo.
+pub fn runtime_dir() -> PathBuf {
+    let uid = nix::unistd::Uid::effective();
+    if uid.is_root() {
+        return PathBuf::from("/var/run/fs");
+    }
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir).join("fs"),
+        _ => std::env::temp_dir().join(format!("fs-{uid}")),
+    }
+}
+
+impl DaemonPaths {
+    /// The PID file, `fs.pid` in the runtime directory by default.
+    pub fn pid_file(&self) -> PathBuf {
+        self.pid_file
+            .clone()
+            .unwrap_or_else(|| runtime_dir().join("fs.pid"))
+    }
+
+    /// The stdout log, `fs.out` in the runtime directory by default.
+    pub fn stdout_log(&self) -> PathBuf {
+        self.stdout_log
+            .clone()
+            .unwrap_or_else(|| runtime_dir().join("fs.out"))
+    }
+
+    /// The stderr log, `fs.err` in the runtime directory by default.
+    pub fn stderr_log(&self) -> PathBuf {
+        self.stderr_log
+            .clone()
+            .unwrap_or_else(|| runtime_dir().join("fs.err"))
+    }
+
+    /// The admin socket of the server, next to the PID file, e.g. `fs.sock`
+    /// for `fs.pid`.
+    pub fn admin_socket(&self) -> PathBuf {
+        self.pid_file().with_extension("sock")
+    }
+
+    /// Create the directories of the PID and log files, accessible to the
+    /// current user only if created.
+    pub fn create_dirs(&self) -> Result<()> {
+        for path in [self.pid_file(), self.stdout_log(), self.stderr_log()] {
+            if let Some(dir) = path.parent().filter(|dir| !dir.exists()) {
+                std::fs::DirBuilder::new()
+                    .recursive(true)
+                    .mode(0o700)
+                    .create(dir)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read the PID file, `None` if it doesn't exist.
+pub fn read_pid(path: &Path) -> Result<Option<i32>> {
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    match data.trim().parse() {
+        Ok(pid) if pid > 0 => Ok(Some(pid)),
+        _ => Err(Error::InvalidPidFile(
+            path.to_owned(),
+            data.trim().to_owned(),
+        )),
+    }
+}
+
+/// Whether the process is alive and runs fs.
+pub fn is_fs(pid: i32) -> bool {
+    let pid = Pid::from_u32(pid as u32);
+    let mut sys = System::new();
+    if !sys.refresh_process(pid) {
+        return false;
+    }
+    let Some(process) = sys.process(pid) else {
+        return false;
+    };
+
+    // The name is the executable's, truncated to 15 bytes on Linux
+    let name = process.name();
+    let named =
+        |expected: &str| expected == name || (name.len() == COMM_LEN && expected.starts_with(name));
+    named(env!("CARGO_PKG_NAME"))
+        || std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.file_name()?.to_str().map(named))
+            .unwrap_or(false)
+}
+
+/// Returns the PID of the running daemon, `None` if it isn't running. A stale
+/// PID file, left by a daemon that is gone or whose PID was reused by another
+/// program, is removed.
+pub fn running_pid(path: &Path) -> Result<Option<i32>> {
+    let Some(pid) = read_pid(path)? else {
+        return Ok(None);
+    };
+    if is_fs(pid) {
+        return Ok(Some(pid));
+    }
+
+    eprintln!(
+        "Removing stale PID file {} of process {}",
+        path.display(),
+        pid
+    );
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(None),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
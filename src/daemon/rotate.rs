@@ -0,0 +1,143 @@
+use crate::{Result, StartArgs};
+use std::{
+    fs::{File, OpenOptions, Permissions},
+    os::unix::{
+        fs::PermissionsExt,
+        io::{AsRawFd, RawFd},
+    },
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// How often the log files are checked for rotation.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Log rotation policy.
+#[derive(Clone, Debug)]
+pub struct RotatePolicy {
+    /// Rotate a log file once it reaches this size in bytes, never if 0
+    pub max_size: u64,
+    /// Rotate a log file after this long, never if unset
+    pub interval: Option<Duration>,
+    /// Number of rotated files kept per log, `<log>.1` being the most recent
+    pub retention: usize,
+}
+
+impl From<&StartArgs> for RotatePolicy {
+    fn from(args: &StartArgs) -> Self {
+        Self {
+            max_size: args.log_max_size * 1024 * 1024,
+            interval: (args.log_rotate_interval > 0)
+                .then(|| Duration::from_secs(args.log_rotate_interval)),
+            retention: args.log_retention,
+        }
+    }
+}
+
+/// Returns the path of the `index`th rotated file of the log.
+pub fn rotated(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{index}"));
+    PathBuf::from(name)
+}
+
+/// Rotate the log file: `<log>` becomes `<log>.1`, `<log>.1` becomes
+/// `<log>.2` and so on, the files beyond the retention are removed.
+pub fn rotate(path: &Path, retention: usize) -> Result<()> {
+    // Remove the oldest files, including any left over by a larger retention
+    let mut index = retention.max(1);
+    while rotated(path, index).exists() {
+        std::fs::remove_file(rotated(path, index))?;
+        index += 1;
+    }
+
+    if retention == 0 {
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        return Ok(());
+    }
+
+    for index in (1..retention).rev() {
+        let from = rotated(path, index);
+        if from.exists() {
+            std::fs::rename(from, rotated(path, index + 1))?;
+        }
+    }
+    if path.exists() {
+        std::fs::rename(path, rotated(path, 1))?;
+    }
+    Ok(())
+}
+
+/// Hand the log files and the directories they are rotated in to the user, so
+/// that a daemon dropping its privileges can still rotate them.
+pub fn chown<'a>(
+    paths: impl IntoIterator<Item = &'a Path>,
+    user: &nix::unistd::User,
+) -> Result<()> {
+    for path in paths {
+        nix::unistd::chown(path, Some(user.uid), Some(user.gid)).map_err(std::io::Error::from)?;
+    }
+    Ok(())
+}
+
+/// Create or append to a log file.
+pub fn open(path: &Path) -> Result<File> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.set_permissions(Permissions::from_mode(0o644))?;
+    Ok(file)
+}
+
+/// A log file a file descriptor of the process is redirected to.
+pub struct LogFile {
+    path: PathBuf,
+    fd: RawFd,
+    opened: Instant,
+}
+
+impl LogFile {
+    pub fn new(path: PathBuf, fd: RawFd) -> LogFile {
+        LogFile {
+            path,
+            fd,
+            opened: Instant::now(),
+        }
+    }
+
+    /// Whether the file reached the size or the age to be rotated. Empty files
+    /// are never rotated.
+    pub fn due(&self, policy: &RotatePolicy) -> bool {
+        let size = std::fs::metadata(&self.path).map_or(0, |metadata| metadata.len());
+        size > 0
+            && ((policy.max_size > 0 && size >= policy.max_size)
+                || policy
+                    .interval
+                    .is_some_and(|interval| self.opened.elapsed() >= interval))
+    }
+
+    /// Rotate the file and redirect the descriptor to a new one.
+    pub fn rotate(&mut self, policy: &RotatePolicy) -> Result<()> {
+        rotate(&self.path, policy.retention)?;
+        let file = open(&self.path)?;
+        nix::unistd::dup2(file.as_raw_fd(), self.fd).map_err(std::io::Error::from)?;
+        self.opened = Instant::now();
+        Ok(())
+    }
+}
+
+/// Spawn a thread rotating the log files when they are due.
+pub fn spawn(mut files: Vec<LogFile>, policy: RotatePolicy) {
+    if policy.max_size == 0 && policy.interval.is_none() {
+        return;
+    }
+
+    std::thread::spawn(move || loop {
+        std::thread::sleep(CHECK_INTERVAL);
+        for file in files.iter_mut().filter(|file| file.due(&policy)) {
+            if let Err(err) = file.rotate(&policy) {
+                eprintln!("Failed to rotate {}: {}", file.path.display(), err);
+            }
+        }
+    });
+}
@@ -0,0 +1,270 @@
+use crate::{onnx, BootArgs, Result, ServiceArgs};
+use clap::ValueEnum;
+use std::{
+    fs::Permissions,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+/// Whether the service is a system service, a user service otherwise.
+fn system() -> bool {
+    nix::unistd::Uid::effective().is_root()
+}
+
+/// Returns the directory the units are written to.
+pub fn unit_dir(args: &ServiceArgs) -> PathBuf {
+    if let Some(dir) = args.unit_dir.clone() {
+        return dir;
+    }
+    if system() {
+        return PathBuf::from("/etc/systemd/system");
+    }
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(|| PathBuf::from(".config"));
+    config.join("systemd/user")
+}
+
+/// Returns the environment file of the service.
+pub fn env_file(args: &ServiceArgs) -> PathBuf {
+    match args.env_file.clone() {
+        Some(path) => path,
+        None if system() => PathBuf::from("/etc/default").join(&args.name),
+        None => unit_dir(args).join(format!("{}.env", args.name)),
+    }
+}
+
+/// Quote an argument of `ExecStart`, escaping the specifier and variable
+/// expansion characters.
+pub fn quote(arg: &str) -> String {
+    let escaped = arg.replace('%', "%%").replace('$', "$$");
+    if !escaped.is_empty()
+        && !escaped
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';'))
+    {
+        return escaped;
+    }
+    format!(
+        "\"{}\"",
+        escaped
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+/// Returns the command line arguments of the server, the keys are passed
+/// through the environment file instead. Relative paths are made absolute
+/// since the service doesn't run in the current directory.
+pub fn exec_args(args: &BootArgs) -> Result<Vec<String>> {
+    let mut argv = vec!["run".to_owned()];
+    let mut push = |name: &str, value: String| {
+        argv.push(format!("--{name}"));
+        argv.push(value);
+    };
+    let path = |path: &PathBuf| -> Result<String> {
+        Ok(std::path::absolute(path)?.to_string_lossy().into_owned())
+    };
+
+    if let Some(value) = args.log_format.to_possible_value() {
+        push("log-format", value.get_name().to_owned());
+    }
+    if let Some(ref endpoint) = args.otlp_endpoint {
+        push("otlp-endpoint", endpoint.clone());
+    }
+    push("bind", args.bind.to_string());
+    if let Some(ref cert) = args.tls_cert {
+        push("tls-cert", path(cert)?);
+    }
+    if let Some(ref key) = args.tls_key {
+        push("tls-key", path(key)?);
+    }
+    push("limit", args.limit.to_string());
+    if let Some(ref dir) = args.model_dir {
+        push("model-dir", path(dir)?);
+    }
+    if let Some(ref config) = args.config {
+        push("config", path(config)?);
+    }
+    push("drain-grace", args.drain_grace.to_string());
+    push("drain-timeout", args.drain_timeout.to_string());
+    if let Some(ref socket) = args.admin_socket {
+        push("admin-socket", path(socket)?);
+    }
+    push("num-threads", args.num_threads.to_string());
+    let allocator = match args.allocator {
+        ort::AllocatorType::Arena => "arena",
+        _ => "device",
+    };
+    push("allocator", allocator.to_owned());
+    if let Some(ref solver) = args.fallback_solver {
+        push("fallback-solver", solver.clone());
+        if let Some(ref endpoint) = args.fallback_endpoint {
+            push("fallback-endpoint", endpoint.clone());
+        }
+        push(
+            "fallback-image-limit",
+            args.fallback_image_limit.to_string(),
+        );
+        if let Some(timeout) = args.fallback_timeout {
+            push("fallback-timeout", timeout.to_string());
+        }
+        push(
+            "fallback-connect-timeout",
+            args.fallback_connect_timeout.to_string(),
+        );
+    }
+
+    for (flag, set) in [
+        ("--debug", args.debug),
+        ("--update-check", args.update_check),
+        ("--preload", args.preload),
+    ] {
+        if set {
+            argv.push(flag.to_owned());
+        }
+    }
+
+    match args.store {
+        onnx::Config::S3 {
+            ref bucket_name,
+            ref prefix_key,
+            ref url,
+            ref client_id,
+            ..
+        } => {
+            argv.extend(["s3", "--bucket-name", bucket_name].map(str::to_owned));
+            if let Some(prefix_key) = prefix_key {
+                argv.extend(["--prefix-key".to_owned(), prefix_key.clone()]);
+            }
+            argv.extend(["--url", url, "--client-id", client_id].map(str::to_owned));
+        }
+        onnx::Config::Github { ref url } => {
+            argv.extend(["github", "--url", url].map(str::to_owned));
+        }
+    }
+    Ok(argv)
+}
+
+/// Returns the environment variables holding the keys of the server.
+pub fn environment(args: &BootArgs) -> Vec<(&'static str, String)> {
+    let mut env = vec![];
+    if let Some(ref key) = args.api_key {
+        env.push(("FS_API_KEY", key.clone()));
+    }
+    if let Some(ref key) = args.fallback_key {
+        env.push(("FS_FALLBACK_KEY", key.clone()));
+    }
+    if let onnx::Config::S3 { ref secret, .. } = args.store {
+        env.push(("FS_STORE_SECRET", secret.clone()));
+    }
+    env
+}
+
+/// Returns the content of the environment file.
+pub fn env_file_content(args: &ServiceArgs) -> String {
+    let mut content = format!("# Environment of the {} service\n", args.name);
+    for (name, value) in environment(&args.boot) {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        content.push_str(&format!("{name}=\"{value}\"\n"));
+    }
+    content
+}
+
+/// Returns the content of the service unit.
+pub fn service_unit(args: &ServiceArgs, exe: &Path, env_file: &Path) -> Result<String> {
+    let mut boot = args.boot.clone();
+    // Readiness is reported once the models are loaded
+    boot.preload = true;
+    let exec = std::iter::once(exe.to_string_lossy().into_owned())
+        .chain(exec_args(&boot)?)
+        .map(|arg| quote(&arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut unit = String::from("[Unit]\n");
+    unit.push_str(&format!("Description={}\n", env!("CARGO_PKG_DESCRIPTION")));
+    unit.push_str("After=network-online.target\nWants=network-online.target\n");
+    if args.socket {
+        let socket = format!("{}.socket", args.name);
+        unit.push_str(&format!("Requires={socket}\nAfter={socket}\n"));
+    }
+
+    unit.push_str("\n[Service]\nType=notify\nNotifyAccess=main\n");
+    unit.push_str(&format!("ExecStart={exec}\n"));
+    unit.push_str("ExecReload=/bin/kill -HUP $MAINPID\n");
+    unit.push_str(&format!("EnvironmentFile=-{}\n", env_file.display()));
+    unit.push_str("Restart=on-failure\nRestartSec=5\nTimeoutStartSec=600\n");
+    // Leave the server the time to drain before it is killed
+    unit.push_str(&format!(
+        "TimeoutStopSec={}\n",
+        args.boot.drain_grace + args.boot.drain_timeout + 15
+    ));
+    if args.watchdog > 0 {
+        unit.push_str(&format!("WatchdogSec={}\n", args.watchdog));
+    }
+    if let Some(ref user) = args.user.as_ref().filter(|_| system()) {
+        unit.push_str(&format!("User={user}\n"));
+    }
+
+    let target = if system() {
+        "multi-user.target"
+    } else {
+        "default.target"
+    };
+    unit.push_str(&format!("\n[Install]\nWantedBy={target}\n"));
+    Ok(unit)
+}
+
+/// Returns the content of the socket unit, listening on the bind address.
+pub fn socket_unit(args: &ServiceArgs) -> String {
+    format!(
+        "[Unit]\nDescription={} socket\n\n[Socket]\nListenStream={}\n\n[Install]\nWantedBy=sockets.target\n",
+        env!("CARGO_PKG_DESCRIPTION"),
+        args.boot.bind
+    )
+}
+
+/// Write the service unit, the socket unit if enabled and the environment file.
+pub fn install(args: ServiceArgs) -> Result<()> {
+    let dir = unit_dir(&args);
+    let env_file = env_file(&args);
+    let exe = std::env::current_exe()?;
+
+    std::fs::create_dir_all(&dir)?;
+    let service = dir.join(format!("{}.service", args.name));
+    std::fs::write(&service, service_unit(&args, &exe, &env_file)?)?;
+    std::fs::set_permissions(&service, Permissions::from_mode(0o644))?;
+    println!("Wrote {}", service.display());
+
+    if args.socket {
+        let socket = dir.join(format!("{}.socket", args.name));
+        std::fs::write(&socket, socket_unit(&args))?;
+        std::fs::set_permissions(&socket, Permissions::from_mode(0o644))?;
+        println!("Wrote {}", socket.display());
+    }
+
+    // The keys are readable by the owner only
+    if let Some(parent) = env_file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&env_file, env_file_content(&args))?;
+    std::fs::set_permissions(&env_file, Permissions::from_mode(0o600))?;
+    println!("Wrote {}", env_file.display());
+
+    let systemctl = if system() {
+        "systemctl"
+    } else {
+        "systemctl --user"
+    };
+    let unit = if args.socket {
+        format!("{}.socket", args.name)
+    } else {
+        args.name.clone()
+    };
+    println!("Run `{systemctl} daemon-reload && {systemctl} enable --now {unit}` to start it");
+    Ok(())
+}
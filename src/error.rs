@@ -1,4 +1,4 @@
-use crate::{onnx::Variant, serve::TaskResult};
+use crate::serve::TaskResult;
 use axum::{response::IntoResponse, Json};
 
 #[derive(thiserror::Error, Debug)]
@@ -9,6 +9,24 @@ pub enum Error {
     #[error("fs is not running")]
     NotRunning,
 
+    #[error("Invalid PID file {0}: {1:?}, remove it if fs is not running")]
+    InvalidPidFile(std::path::PathBuf, String),
+
+    #[error("Failed to start the daemon: {0}")]
+    DaemonizeError(String),
+
+    #[error("{0}")]
+    AdminError(String),
+
+    #[error("Server is shutting down")]
+    ShuttingDown,
+
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("Failed to load the model of {0}: {1}")]
+    PreloadError(String, Box<Error>),
+
     #[error(transparent)]
     IoError(#[from] std::io::Error),
 
@@ -33,6 +51,9 @@ pub enum Error {
     #[error("unknown variant type: {0}")]
     UnknownVariantType(String),
 
+    #[error("unknown instruction variant for {0}: {1}")]
+    UnknownInstructionVariant(String, String),
+
     #[error("model name is not valid: {0}")]
     InvalidModelName(String),
 
@@ -66,26 +87,64 @@ pub enum Error {
     #[error("ONNX session not initialized")]
     OnnxSessionNotInitialized,
 
-    #[error("Predictor: {0:?} not active")]
-    PredictorNotActive(Variant),
+    #[error("Model output has no score at index {0}")]
+    InvalidModelOutput(usize),
+
+    #[error("Invalid model {0}: {1}")]
+    InvalidModel(String, String),
+
+    #[error("Predictor: {0} not active")]
+    PredictorNotActive(String),
+
+    #[error("Circuit open: {0}")]
+    CircuitOpen(String),
+
+    #[error("Invalid response from fallback {0}: {1}")]
+    InvalidFallbackResponse(String, String),
+
+    #[error("Fallback daily budget of {0} exceeded")]
+    BudgetExceeded(f64),
+
+    #[error("Task not found: {0}")]
+    TaskNotFound(String),
+
+    #[error("Invalid feedback: {0}")]
+    InvalidFeedback(String),
+
+    #[error("Invalid tile memory: {0}")]
+    InvalidMemory(String),
+
+    #[error("Tile memory not enabled")]
+    MemoryDisabled,
+
+    #[error("OTLP exporter error: {0}")]
+    OtlpError(String),
 
     #[error(transparent)]
     ProcessBarrierError(#[from] indicatif::style::TemplateError),
 }
 
-impl IntoResponse for Error {
-    fn into_response(self) -> axum::response::Response {
+impl Error {
+    /// Returns the HTTP status code of the error.
+    pub fn status(&self) -> axum::http::StatusCode {
         use axum::http::StatusCode;
 
-        let status = match self {
+        match self {
             Error::IoError(_)
             | Error::SerdeJsonError(_)
             | Error::OnnxError(_)
             | Error::InvalidModelName(_)
             | Error::ReqwestError(_)
             | Error::OnnxSessionNotInitialized
+            | Error::InvalidModelOutput(_)
+            | Error::InvalidModel(_, _)
+            | Error::ShapeError(_)
             | Error::PredictorNotActive(_)
-            | Error::FallbackSolverError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            | Error::FallbackSolverError(_)
+            | Error::OtlpError(_)
+            | Error::AdminError(_)
+            | Error::InvalidConfig(_)
+            | Error::PreloadError(..) => StatusCode::INTERNAL_SERVER_ERROR,
 
             Error::InvalidSubmitLimit
             | Error::InvalidApiKey
@@ -94,12 +153,28 @@ impl IntoResponse for Error {
             | Error::InvalidAllocator(_)
             | Error::InvalidSolverType(_)
             | Error::UnknownVariantType(_)
+            | Error::UnknownInstructionVariant(..)
             | Error::InvalidImageSize(_)
-            | Error::ShapeError(_)
+            | Error::InvalidFeedback(_)
+            | Error::InvalidMemory(_)
             | Error::ImageError(_) => StatusCode::BAD_REQUEST,
 
+            Error::TaskNotFound(_) | Error::MemoryDisabled => StatusCode::NOT_FOUND,
+
+            Error::CircuitOpen(_) | Error::BudgetExceeded(_) | Error::ShuttingDown => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+
+            Error::InvalidFallbackResponse(_, _) => StatusCode::BAD_GATEWAY,
+
             _ => StatusCode::BAD_GATEWAY,
-        };
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> axum::response::Response {
+        let status = self.status();
 
         tracing::warn!("Error: {}", self);
 
@@ -1,4 +1,5 @@
 pub mod alloc;
+pub mod config;
 #[cfg(target_family = "unix")]
 pub mod daemon;
 pub mod error;
@@ -28,19 +29,26 @@ pub enum Commands {
     Run(BootArgs),
     /// Start server daemon
     #[cfg(target_family = "unix")]
-    Start(BootArgs),
+    Start(StartArgs),
     /// Restart server daemon
     #[cfg(target_family = "unix")]
-    Restart(BootArgs),
+    Restart(StartArgs),
     /// Stop server daemon
     #[cfg(target_family = "unix")]
-    Stop,
+    Stop(DaemonPaths),
     /// Show the server daemon log
     #[cfg(target_family = "unix")]
-    Log,
+    Log(LogArgs),
     /// Show the server daemon process
     #[cfg(target_family = "unix")]
-    PS,
+    PS(StatusArgs),
+    /// Reload the server daemon configuration
+    #[cfg(target_family = "unix")]
+    Reload(StatusArgs),
+    /// Manage the systemd service
+    #[cfg(target_family = "unix")]
+    #[clap(subcommand)]
+    Service(ServiceCommand),
     /// Update the application
     Update,
 }
@@ -51,6 +59,14 @@ pub struct BootArgs {
     #[clap(short, long)]
     pub debug: bool,
 
+    /// Log format, the level is taken from RUST_LOG if set
+    #[clap(long, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// OTLP/HTTP collector endpoint the traces are exported to, e.g. http://localhost:4318
+    #[clap(long)]
+    pub otlp_endpoint: Option<String>,
+
     /// Bind address
     #[clap(short, long, default_value = "0.0.0.0:8000")]
     pub bind: SocketAddr,
@@ -64,7 +80,7 @@ pub struct BootArgs {
     pub tls_key: Option<PathBuf>,
 
     /// Export API key
-    #[clap(short = 'A', long)]
+    #[clap(short = 'A', long, env = "FS_API_KEY", hide_env_values = true)]
     pub api_key: Option<String>,
 
     /// Multiple image submission limits
@@ -79,6 +95,10 @@ pub struct BootArgs {
     #[clap(short = 'M', long)]
     pub model_dir: Option<PathBuf>,
 
+    /// Configuration file (JSON), e.g. models and instruction routes
+    #[clap(short = 'C', long)]
+    pub config: Option<PathBuf>,
+
     /// Number of threads (ONNX Runtime)
     #[clap(short = 'N', long, default_value = "1")]
     pub num_threads: u16,
@@ -92,7 +112,13 @@ pub struct BootArgs {
     pub fallback_solver: Option<String>,
 
     /// Fallback solver client key
-    #[clap(short = 'K', long, requires = "fallback_solver")]
+    #[clap(
+        short = 'K',
+        long,
+        requires = "fallback_solver",
+        env = "FS_FALLBACK_KEY",
+        hide_env_values = true
+    )]
     pub fallback_key: Option<String>,
 
     /// Fallback solver endpoint
@@ -103,10 +129,152 @@ pub struct BootArgs {
     #[clap(short = 'D', long, requires = "fallback_solver", default_value = "1")]
     pub fallback_image_limit: usize,
 
+    /// Fallback solver request timeout in seconds, unlimited if unset
+    #[clap(long, requires = "fallback_solver")]
+    pub fallback_timeout: Option<u64>,
+
+    /// Fallback solver connect timeout in seconds
+    #[clap(long, requires = "fallback_solver", default_value = "10")]
+    pub fallback_connect_timeout: u64,
+
+    /// Load the models of all variants at startup, before the server is reported ready
+    #[clap(long)]
+    pub preload: bool,
+
+    /// Seconds to keep accepting connections on shutdown, reporting not ready,
+    /// before the listener is closed
+    #[clap(long, default_value = "5")]
+    pub drain_grace: u64,
+
+    /// Seconds to wait for the requests and tasks in flight on shutdown
+    #[clap(long, default_value = "30")]
+    pub drain_timeout: u64,
+
+    /// Local admin socket `fs ps` queries the server status on, disabled if unset
+    #[cfg(target_family = "unix")]
+    #[clap(long)]
+    pub admin_socket: Option<PathBuf>,
+
     #[clap(subcommand)]
     pub store: onnx::Config,
 }
 
+/// Daemon PID and log files, in the runtime directory by default: `/var/run/fs`
+/// for root, `$XDG_RUNTIME_DIR/fs` for other users
+#[derive(Args, Clone, Debug, Default)]
+pub struct DaemonPaths {
+    /// Daemon PID file [default: <runtime dir>/fs.pid]
+    #[clap(long)]
+    pub pid_file: Option<PathBuf>,
+
+    /// Daemon stdout log file [default: <runtime dir>/fs.out]
+    #[clap(long)]
+    pub stdout_log: Option<PathBuf>,
+
+    /// Daemon stderr log file [default: <runtime dir>/fs.err]
+    #[clap(long)]
+    pub stderr_log: Option<PathBuf>,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct StartArgs {
+    #[clap(flatten)]
+    pub paths: DaemonPaths,
+
+    /// Rotate a log file once it reaches this size in MB, 0 disables
+    #[clap(long, default_value = "64")]
+    pub log_max_size: u64,
+
+    /// Rotate a log file after this many seconds, 0 disables
+    #[clap(long, default_value = "86400")]
+    pub log_rotate_interval: u64,
+
+    /// Number of rotated files kept per log
+    #[clap(long, default_value = "7")]
+    pub log_retention: usize,
+
+    #[clap(flatten)]
+    pub boot: BootArgs,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct StatusArgs {
+    #[clap(flatten)]
+    pub paths: DaemonPaths,
+
+    /// Admin socket of the server [default: the PID file with the .sock extension]
+    #[clap(long)]
+    pub admin_socket: Option<PathBuf>,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct LogArgs {
+    #[clap(flatten)]
+    pub paths: DaemonPaths,
+
+    /// Keep printing lines as they are written
+    #[clap(short, long)]
+    pub follow: bool,
+
+    /// Print only the last N lines of each log
+    #[clap(short = 'n', long)]
+    pub tail: Option<usize>,
+
+    /// Print only lines of at least this level, e.g. warn
+    #[clap(short, long)]
+    pub level: Option<tracing::Level>,
+
+    /// Print only the stderr log
+    #[clap(long)]
+    pub stderr: bool,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum ServiceCommand {
+    /// Write a systemd unit and environment file running the server with these arguments
+    Install(ServiceArgs),
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct ServiceArgs {
+    /// Service name, the unit is written to <unit dir>/<name>.service
+    #[clap(long, default_value = "fs")]
+    pub name: String,
+
+    /// Unit directory [default: /etc/systemd/system for root, ~/.config/systemd/user otherwise]
+    #[clap(long)]
+    pub unit_dir: Option<PathBuf>,
+
+    /// Environment file holding the keys [default: /etc/default/<name> for root, <unit dir>/<name>.env otherwise]
+    #[clap(long)]
+    pub env_file: Option<PathBuf>,
+
+    /// User the service runs as
+    #[clap(long)]
+    pub user: Option<String>,
+
+    /// Also write a <name>.socket unit listening on the bind address, the server is started on the first connection
+    #[clap(long)]
+    pub socket: bool,
+
+    /// Watchdog timeout in seconds, 0 disables
+    #[clap(long, default_value = "60")]
+    pub watchdog: u64,
+
+    #[clap(flatten)]
+    pub boot: BootArgs,
+}
+
+/// Log output format
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, with the current span and its parents
+    Json,
+}
+
 fn alloc_parser(s: &str) -> Result<ort::AllocatorType> {
     match s {
         "device" => Ok(ort::AllocatorType::Device),
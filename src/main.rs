@@ -11,11 +11,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         #[cfg(target_family = "unix")]
         Commands::Restart(args) => daemon::restart(args)?,
         #[cfg(target_family = "unix")]
-        Commands::Stop => daemon::stop()?,
+        Commands::Stop(paths) => daemon::stop(&paths)?,
         #[cfg(target_family = "unix")]
-        Commands::Log => daemon::log()?,
+        Commands::Log(args) => daemon::log(args)?,
         #[cfg(target_family = "unix")]
-        Commands::PS => daemon::status()?,
+        Commands::PS(args) => daemon::status(args)?,
+        #[cfg(target_family = "unix")]
+        Commands::Reload(args) => daemon::reload(args)?,
+        #[cfg(target_family = "unix")]
+        Commands::Service(command) => daemon::service(command)?,
         Commands::Update => update::update()?,
     };
 
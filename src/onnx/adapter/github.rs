@@ -4,6 +4,7 @@ use tokio::fs;
 
 use super::FetchAdapter;
 
+#[derive(Clone)]
 pub struct GithubAdapter(pub String);
 
 impl Default for GithubAdapter {
@@ -15,7 +16,7 @@ impl Default for GithubAdapter {
 impl FetchAdapter for GithubAdapter {
     async fn fetch_model(
         &self,
-        model_name: &'static str,
+        model_name: &str,
         model_dir: PathBuf,
         update_check: bool,
     ) -> Result<PathBuf> {
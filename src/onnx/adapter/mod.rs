@@ -22,7 +22,7 @@ pub trait FetchAdapter {
     /// - A `Result` containing a `String`. On success, this `String` is the path to the fetched model.
     async fn fetch_model(
         &self,
-        model_name: &'static str,
+        model_name: &str,
         model_dir: std::path::PathBuf,
         update_check: bool,
     ) -> Result<PathBuf>;
@@ -69,7 +69,7 @@ pub enum Config {
         client_id: String,
 
         /// The secret of the Cloudflare KV.
-        #[clap(short = 's', long)]
+        #[clap(short = 's', long, env = "FS_STORE_SECRET", hide_env_values = true)]
         secret: String,
     },
     /// Represents the Github storage option.
@@ -80,6 +80,7 @@ pub enum Config {
     },
 }
 
+#[derive(Clone)]
 pub enum Adapter {
     S3(s3::S3Adapter),
     Github(github::GithubAdapter),
@@ -108,12 +109,20 @@ impl Adapter {
             Config::Github { url } => Adapter::Github(github::GithubAdapter(url)),
         }
     }
+
+    /// Returns the S3 adapter, if the models are stored in S3.
+    pub fn s3(&self) -> Option<&s3::S3Adapter> {
+        match self {
+            Adapter::S3(s3) => Some(s3),
+            Adapter::Github(_) => None,
+        }
+    }
 }
 
 impl FetchAdapter for Adapter {
     async fn fetch_model(
         &self,
-        model_name: &'static str,
+        model_name: &str,
         model_dir: std::path::PathBuf,
         update_check: bool,
     ) -> Result<PathBuf> {
@@ -35,7 +35,7 @@ impl S3Adapter {
         let s3_config = S3_CONFIG
             .get_or_init(|| async {
                 let creds = Credentials::new(client_id, secret, None, None, "onnx");
-                aws_config::load_defaults(BehaviorVersion::v2024_03_28())
+                aws_config::load_defaults(BehaviorVersion::latest())
                     .await
                     .into_builder()
                     .endpoint_url(url)
@@ -63,6 +63,27 @@ impl S3Adapter {
         }
     }
 
+    /// Upload a file to the bucket, the key is prefixed with the prefix key if it exists.
+    pub async fn upload_file(&self, key: &str, filepath: impl AsRef<Path>) -> Result<()> {
+        let key = self
+            .prefix_key
+            .as_ref()
+            .map(|prefix_key| format!("{}/{}", prefix_key, key))
+            .unwrap_or_else(|| key.to_string());
+
+        let body = fs::read(filepath).await?;
+        self.client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .body(body.into())
+            .send()
+            .await
+            .map_err(|e| Error::CloudflareR2SdkError(e.to_string()))?;
+
+        Ok(())
+    }
+
     async fn download_file(&self, key: &str, filepath: impl AsRef<Path>) -> Result<()> {
         // Prefix key with the prefix_key if it exists
         let key = self
@@ -104,7 +125,7 @@ impl S3Adapter {
 impl FetchAdapter for S3Adapter {
     async fn fetch_model(
         &self,
-        model_name: &'static str,
+        model_name: &str,
         model_dir: std::path::PathBuf,
         update_check: bool,
     ) -> Result<PathBuf> {
@@ -0,0 +1,87 @@
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+
+/// Test-time augmentation of a model's input images.
+///
+/// Each transform produces an extra view of the cropped images, the scores of
+/// all views (the original included) are averaged before the argmax. Only
+/// enable flips for games whose answer doesn't depend on the orientation.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Augment {
+    /// Shift the images by this many pixels in each direction
+    pub shift: u32,
+    /// Flip the images horizontally
+    pub flip_horizontal: bool,
+    /// Flip the images vertically
+    pub flip_vertical: bool,
+    /// Brightness offsets, e.g. `[-20, 20]`
+    pub brightness: Vec<i32>,
+}
+
+/// An image transform of the test-time augmentation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transform {
+    Identity,
+    /// Shift by (dx, dy) pixels
+    Shift(i32, i32),
+    FlipHorizontal,
+    FlipVertical,
+    /// Brighten by the offset
+    Brighten(i32),
+}
+
+impl Augment {
+    /// Returns the transforms of the augmentation, starting with the identity.
+    pub fn transforms(&self) -> Vec<Transform> {
+        let mut transforms = vec![Transform::Identity];
+
+        if self.shift > 0 {
+            let shift = self.shift as i32;
+            transforms.extend([
+                Transform::Shift(shift, 0),
+                Transform::Shift(-shift, 0),
+                Transform::Shift(0, shift),
+                Transform::Shift(0, -shift),
+            ]);
+        }
+        if self.flip_horizontal {
+            transforms.push(Transform::FlipHorizontal);
+        }
+        if self.flip_vertical {
+            transforms.push(Transform::FlipVertical);
+        }
+        transforms.extend(
+            self.brightness
+                .iter()
+                .filter(|&&value| value != 0)
+                .map(|&value| Transform::Brighten(value)),
+        );
+
+        transforms
+    }
+}
+
+impl Transform {
+    /// Apply the transform to the image.
+    ///
+    /// Shifts crop the image by the offset on one side, the predictor resizes the
+    /// result to the model input shape.
+    pub fn apply(&self, image: DynamicImage) -> DynamicImage {
+        match *self {
+            Transform::Identity => image,
+            Transform::Shift(dx, dy) => {
+                let (width, height) = (image.width(), image.height());
+                let (x, y) = (dx.max(0) as u32, dy.max(0) as u32);
+                let (dx, dy) = (dx.unsigned_abs(), dy.unsigned_abs());
+                if dx >= width || dy >= height {
+                    return image;
+                }
+                image.crop_imm(x, y, width - dx, height - dy)
+            }
+            Transform::FlipHorizontal => image.fliph(),
+            Transform::FlipVertical => image.flipv(),
+            Transform::Brighten(value) => image.brighten(value),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+/// How the score vectors of an ensemble's models are combined.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Combine {
+    /// Average the score vectors, answer the highest average score
+    #[default]
+    Average,
+    /// Each model votes for its highest score, answer the most voted candidate.
+    /// Ties are broken by the summed scores.
+    Vote,
+}
+
+impl Combine {
+    /// Combine the score vectors, one per model with a score per candidate, and
+    /// return the index of the answer. Returns `None` if there are no candidates.
+    pub fn answer(&self, scores: &[Vec<f32>]) -> Option<usize> {
+        let candidates = scores.iter().map(Vec::len).min()?;
+        let sum = |i: usize| scores.iter().map(|score| score[i]).sum::<f32>();
+
+        match self {
+            Combine::Average => argmax((0..candidates).map(sum)),
+            Combine::Vote => {
+                let mut votes = vec![0usize; candidates];
+                for score in scores {
+                    if let Some(i) = argmax(score[..candidates].iter().copied()) {
+                        votes[i] += 1;
+                    }
+                }
+                let most = votes.iter().copied().max()?;
+                argmax((0..candidates).map(|i| {
+                    if votes[i] == most {
+                        sum(i)
+                    } else {
+                        f32::NEG_INFINITY
+                    }
+                }))
+            }
+        }
+    }
+}
+
+/// Returns the index of the first highest score.
+pub fn argmax(scores: impl IntoIterator<Item = f32>) -> Option<usize> {
+    let mut max: Option<(usize, f32)> = None;
+    for (i, score) in scores.into_iter().enumerate() {
+        if max.is_none_or(|(_, max)| score > max) {
+            max = Some((i, score));
+        }
+    }
+    max.map(|(i, _)| i)
+}
@@ -0,0 +1,158 @@
+use crate::{error::Error, Result};
+use serde::Deserialize;
+
+/// Orientation of the candidate tiles of a pair classifier image.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    /// Candidate tiles are laid out left to right along the top of the image
+    #[default]
+    Horizontal,
+    /// Candidate tiles are laid out top to bottom along the left of the image
+    Vertical,
+}
+
+/// Tile geometry of a pair classifier image.
+///
+/// The default matches the current FunCaptcha layout: a row of 200x200
+/// candidate tiles on top and the reference image cropped from
+/// `(0, 200, 135, 200)` below them.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct TileLayout {
+    /// Candidate tile size (width, height)
+    pub tile: (u32, u32),
+    /// Reference image crop rectangle (x, y, width, height)
+    pub reference: (u32, u32, u32, u32),
+    /// Orientation of the candidate tiles
+    pub orientation: Orientation,
+}
+
+impl Default for TileLayout {
+    fn default() -> Self {
+        Self {
+            tile: (200, 200),
+            reference: (0, 200, 135, 200),
+            orientation: Orientation::Horizontal,
+        }
+    }
+}
+
+impl TileLayout {
+    /// Detect the layout from the image dimensions.
+    ///
+    /// Only the default layout family is detected: square tiles half the image
+    /// height, laid out horizontally, with the reference crop scaled accordingly.
+    /// Returns `None` if the dimensions don't fit it.
+    pub fn detect(dimensions: (u32, u32)) -> Option<TileLayout> {
+        let (width, height) = dimensions;
+        let size = height / 2;
+        if size == 0 || height % 2 != 0 || width % size != 0 {
+            return None;
+        }
+
+        let default = TileLayout::default();
+        Some(TileLayout {
+            tile: (size, size),
+            reference: (0, size, default.reference.2 * size / default.tile.0, size),
+            orientation: Orientation::Horizontal,
+        })
+    }
+
+    /// Validate the image dimensions against the layout and return the number of
+    /// candidate tiles.
+    pub fn candidates(&self, dimensions: (u32, u32)) -> Result<u32> {
+        let (width, height) = dimensions;
+        let (tile_width, tile_height) = self.tile;
+        let (x, y, reference_width, reference_height) = self.reference;
+
+        let count = match self.orientation {
+            Orientation::Horizontal if width % tile_width.max(1) == 0 && height >= tile_height => {
+                width / tile_width.max(1)
+            }
+            Orientation::Vertical if height % tile_height.max(1) == 0 && width >= tile_width => {
+                height / tile_height.max(1)
+            }
+            _ => 0,
+        };
+
+        if count == 0 || x + reference_width > width || y + reference_height > height {
+            return Err(Error::InvalidImageSize(dimensions));
+        }
+
+        Ok(count)
+    }
+
+    /// Returns the crop rectangle (x, y, width, height) of the candidate tile.
+    pub fn tile_rect(&self, index: u32) -> (u32, u32, u32, u32) {
+        let (width, height) = self.tile;
+        match self.orientation {
+            Orientation::Horizontal => (index * width, 0, width, height),
+            Orientation::Vertical => (0, index * height, width, height),
+        }
+    }
+}
+
+/// Grid geometry of a classifier image.
+///
+/// The default matches the current FunCaptcha layout: a 2x3 grid of 100x100
+/// cells, scored left to right, top to bottom.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct GridLayout {
+    /// Number of grid rows
+    pub rows: u32,
+    /// Number of grid columns
+    pub cols: u32,
+    /// Cell size (width, height)
+    pub cell: (u32, u32),
+}
+
+impl Default for GridLayout {
+    fn default() -> Self {
+        Self {
+            rows: 2,
+            cols: 3,
+            cell: (100, 100),
+        }
+    }
+}
+
+impl GridLayout {
+    /// Detect the layout from the image dimensions.
+    ///
+    /// Only the default 2x3 grid is detected, with square cells scaled to the
+    /// image resolution. Returns `None` if the dimensions don't fit it.
+    pub fn detect(dimensions: (u32, u32)) -> Option<GridLayout> {
+        let (width, height) = dimensions;
+        let default = GridLayout::default();
+        let size = width / default.cols;
+        if size == 0 || width % default.cols != 0 || height != size * default.rows {
+            return None;
+        }
+
+        Some(GridLayout {
+            cell: (size, size),
+            ..default
+        })
+    }
+
+    /// Validate the image dimensions against the layout and return the number of
+    /// cells.
+    pub fn cells(&self, dimensions: (u32, u32)) -> Result<u32> {
+        let (width, height) = dimensions;
+        let count = self.rows * self.cols;
+        if count == 0 || width != self.cols * self.cell.0 || height != self.rows * self.cell.1 {
+            return Err(Error::InvalidImageSize(dimensions));
+        }
+
+        Ok(count)
+    }
+
+    /// Returns the crop rectangle (x, y, width, height) of the cell.
+    pub fn cell_rect(&self, index: u32) -> (u32, u32, u32, u32) {
+        let (width, height) = self.cell;
+        let (row, col) = (index / self.cols.max(1), index % self.cols.max(1));
+        (col * width, row * height, width, height)
+    }
+}
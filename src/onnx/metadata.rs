@@ -0,0 +1,176 @@
+use super::preprocess::{ChannelOrder, Preprocess, TensorLayout};
+use crate::{error::Error, Result};
+use ort::{Session, TensorElementType, ValueType};
+use serde::Serialize;
+
+/// Custom metadata key of the model version.
+pub const VERSION_METADATA_KEY: &str = "version";
+
+/// Custom metadata key of the model training date.
+pub const TRAINING_DATE_METADATA_KEY: &str = "training_date";
+
+/// Name, element type and shape of a model input or output.
+#[derive(Serialize, Clone, Debug)]
+pub struct TensorInfo {
+    /// Tensor name
+    pub name: String,
+    /// Element type, e.g. "Float32"
+    pub dtype: String,
+    /// Dimensions, `-1` for dynamic dimensions
+    pub shape: Vec<i64>,
+}
+
+impl TensorInfo {
+    fn new(name: &str, value_type: &ValueType) -> Self {
+        let (dtype, shape) = match value_type {
+            ValueType::Tensor { ty, dimensions } => (format!("{ty:?}"), dimensions.clone()),
+            other => (format!("{other:?}"), vec![]),
+        };
+        Self {
+            name: name.to_owned(),
+            dtype,
+            shape,
+        }
+    }
+
+    fn is_f32(&self) -> bool {
+        self.dtype == format!("{:?}", TensorElementType::Float32)
+    }
+}
+
+/// Information read from an ONNX model session.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ModelInfo {
+    /// Model file name
+    pub model: String,
+    /// Model inputs
+    pub inputs: Vec<TensorInfo>,
+    /// Model outputs
+    pub outputs: Vec<TensorInfo>,
+    /// Producer name, e.g. "pytorch"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub producer: Option<String>,
+    /// Model description
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Model version, the `version` custom metadata
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Training date, the `training_date` custom metadata
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub training_date: Option<String>,
+    /// SHA256 of the model file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Preprocessing pipeline used by the predictor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preprocess: Option<Preprocess>,
+    /// Input shape (width, height) used by the predictor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_shape: Option<(u32, u32)>,
+    /// Load or validation error, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ModelInfo {
+    /// Read the inputs, outputs and metadata of the session.
+    pub fn read(model: &str, session: &Session) -> Result<ModelInfo> {
+        let metadata = session.metadata()?;
+        let non_empty = |value: String| (!value.is_empty()).then_some(value);
+
+        Ok(ModelInfo {
+            model: model.to_owned(),
+            inputs: session
+                .inputs
+                .iter()
+                .map(|input| TensorInfo::new(&input.name, &input.input_type))
+                .collect(),
+            outputs: session
+                .outputs
+                .iter()
+                .map(|output| TensorInfo::new(&output.name, &output.output_type))
+                .collect(),
+            producer: metadata.producer().ok().and_then(non_empty),
+            description: metadata.description().ok().and_then(non_empty),
+            version: metadata.custom(VERSION_METADATA_KEY)?,
+            training_date: metadata.custom(TRAINING_DATE_METADATA_KEY)?,
+            ..Default::default()
+        })
+    }
+
+    /// Returns the input shape (width, height) of the first input, if it is static.
+    pub fn input_shape(&self, layout: TensorLayout) -> Option<(u32, u32)> {
+        let shape = &self.inputs.first()?.shape;
+        let (height, width) = match layout {
+            TensorLayout::Nchw => (*shape.get(2)?, *shape.get(3)?),
+            TensorLayout::Nhwc => (*shape.get(1)?, *shape.get(2)?),
+        };
+        Some((u32::try_from(width).ok()?, u32::try_from(height).ok()?))
+    }
+
+    /// Validate the session against the predictor's expected input names, input
+    /// shape and preprocessing.
+    pub fn validate(
+        &self,
+        inputs: &[String],
+        input_shape: (u32, u32),
+        preprocess: &Preprocess,
+    ) -> Result<()> {
+        let invalid = |reason: String| Err(Error::InvalidModel(self.model.clone(), reason));
+
+        if inputs.len() != self.inputs.len() {
+            return invalid(format!(
+                "expected {} inputs, found {}",
+                inputs.len(),
+                self.inputs.len()
+            ));
+        }
+
+        let channels = match preprocess.channel_order {
+            ChannelOrder::Luma => 1,
+            ChannelOrder::Rgb | ChannelOrder::Bgr => 3,
+        };
+        let (width, height) = (input_shape.0 as i64, input_shape.1 as i64);
+        let expected = match preprocess.layout {
+            TensorLayout::Nchw => [1, channels, height, width],
+            TensorLayout::Nhwc => [1, height, width, channels],
+        };
+
+        for name in inputs {
+            let Some(input) = self.inputs.iter().find(|input| &input.name == name) else {
+                return invalid(format!("input {name} not found"));
+            };
+
+            if !input.is_f32() {
+                return invalid(format!("input {name} is {}, expected Float32", input.dtype));
+            }
+
+            let matches = input.shape.len() == expected.len()
+                && input
+                    .shape
+                    .iter()
+                    .zip(expected)
+                    .all(|(&dim, expected)| dim < 0 || dim == expected);
+            if !matches {
+                return invalid(format!(
+                    "input {name} shape {:?}, expected {expected:?}",
+                    input.shape
+                ));
+            }
+        }
+
+        let output = match preprocess.output {
+            Some(ref name) => self.outputs.iter().find(|output| &output.name == name),
+            None => self.outputs.first(),
+        };
+        match output {
+            Some(output) if !output.is_f32() => invalid(format!(
+                "output {} is {}, expected Float32",
+                output.name, output.dtype
+            )),
+            Some(_) => Ok(()),
+            None => invalid("output not found".to_owned()),
+        }
+    }
+}
@@ -0,0 +1,27 @@
+//! Perceptual hashes of candidate tiles.
+
+use image::{imageops::FilterType, DynamicImage};
+
+/// Returns the 64-bit difference hash (dHash) of the image.
+///
+/// The image is reduced to 9x8 grayscale pixels and each bit records whether a
+/// pixel is brighter than its right neighbour, so re-encodings and small
+/// rescales of an image hash within a few bits of each other.
+pub fn dhash(image: &DynamicImage) -> u64 {
+    let pixels = image.resize_exact(9, 8, FilterType::Triangle).into_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = pixels.get_pixel(x, y)[0];
+            let right = pixels.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+    hash
+}
+
+/// Returns the Hamming distance of two hashes.
+#[inline]
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
@@ -2,16 +2,23 @@ use crate::{
     error::Error,
     homedir,
     onnx::{
-        adapter::FetchAdapter,
+        adapter::{Adapter, FetchAdapter},
+        augment::{Augment, Transform},
+        ensemble::Combine,
+        layout::{GridLayout, TileLayout},
+        metadata::ModelInfo,
+        predictor::{Prediction, Predictor},
+        preprocess::{ChannelOrder, Preprocess},
+        registry::ModelSpec,
         util::{
-            check_input_image_size, process_classifier_image, process_pair_classifier_ans_image,
+            process_classifier_image, process_pair_classifier_ans_image,
             process_pair_classifier_image,
         },
         ONNXConfig,
     },
     Result,
 };
-use image::DynamicImage;
+use image::{DynamicImage, GenericImageView};
 use ndarray::Array4;
 #[cfg(feature = "cuda")]
 use ort::CUDAExecutionProvider;
@@ -21,168 +28,353 @@ use ort::CoreMLExecutionProvider;
 use ort::DirectMLExecutionProvider;
 #[cfg(feature = "rocm")]
 use ort::ROCmExecutionProvider;
-use ort::{AllocationDevice, GraphOptimizationLevel, MemoryInfo, MemoryType, Session};
-use std::f32;
+use ort::{
+    AllocationDevice, DynValue, GraphOptimizationLevel, MemoryInfo, MemoryType, Session,
+    SessionInputValue,
+};
+use std::{borrow::Cow, f32, path::PathBuf};
 use tokio::sync::OnceCell;
+use tracing::Instrument;
 
-pub struct ImageClassifierPredictor {
-    input_shape: (u32, u32),
+/// The input shape used if neither the spec nor the model sets one.
+const DEFAULT_INPUT_SHAPE: (u32, u32) = (52, 52);
+
+/// An ONNX model session and its preprocessing pipeline.
+struct Model {
     session: OnceCell<Session>,
     active: OnceCell<()>,
+    preprocess: Preprocess,
+    inputs: Vec<String>,
+    input_shape: (u32, u32),
+    info: ModelInfo,
 }
 
-impl ImageClassifierPredictor {
-    pub async fn new(
-        onnx: &'static str,
-        input_shape: Option<(u32, u32)>,
-        config: &ONNXConfig,
-    ) -> Result<Self> {
-        let predictor = ImageClassifierPredictor {
+impl Model {
+    /// Create the model session, `inputs` are the default input tensor names
+    /// used if the preprocessing spec doesn't name them.
+    ///
+    /// The session inputs and outputs are validated against the predictor's
+    /// expectations, the model stays inactive if they don't match.
+    async fn new(spec: &ModelSpec, config: &ONNXConfig, inputs: &[&str]) -> Model {
+        let mut model = Model {
             session: OnceCell::new(),
             active: OnceCell::new(),
-            input_shape: input_shape.unwrap_or((52, 52)),
+            preprocess: spec.preprocess.clone().unwrap_or_default(),
+            inputs: vec![],
+            input_shape: DEFAULT_INPUT_SHAPE,
+            info: ModelInfo {
+                model: spec.model.clone(),
+                ..Default::default()
+            },
         };
 
         // If the session is created successfully, set the session and wait for it to be initialized
-        match create_onnx_session(onnx, config).await {
-            Ok(session) => {
-                let _ = predictor.session.set(session);
-                let _ = predictor.active.set(());
+        match create_onnx_session(&spec.model, config).await {
+            Ok((model_file, session)) => {
+                // The model config takes precedence over the sidecar file and metadata
+                if spec.preprocess.is_none() {
+                    match Preprocess::load(&model_file, &session) {
+                        Ok(Some(preprocess)) => model.preprocess = preprocess,
+                        Ok(None) => (),
+                        Err(err) => {
+                            tracing::warn!("Failed to load preprocess of {}: {}", spec.model, err)
+                        }
+                    }
+                }
+                match ModelInfo::read(&spec.model, &session) {
+                    Ok(info) => model.info = info,
+                    Err(err) => {
+                        tracing::warn!("Failed to read metadata of {}: {}", spec.model, err)
+                    }
+                }
+                match Adapter::file_sha256(&model_file).await {
+                    Ok(sha256) => model.info.sha256 = Some(sha256),
+                    Err(err) => tracing::warn!("Failed to hash {}: {}", spec.model, err),
+                }
+                let _ = model.session.set(session);
             }
             Err(err) => {
                 tracing::warn!("Failed to create session: {}", err);
+                model.info.error = Some(err.to_string());
+            }
+        }
+
+        // The grayscale flag forces a single channel input
+        if spec.grayscale {
+            model.preprocess.channel_order = ChannelOrder::Luma;
+        }
+
+        // Fall back to the session's input names if the defaults don't exist
+        model.inputs = model.preprocess.inputs.clone().unwrap_or_else(|| {
+            let found = |name: &&str| model.info.inputs.iter().any(|input| input.name == *name);
+            if !inputs.iter().all(found) && model.info.inputs.len() == inputs.len() {
+                model
+                    .info
+                    .inputs
+                    .iter()
+                    .map(|input| input.name.clone())
+                    .collect()
+            } else {
+                inputs.iter().map(ToString::to_string).collect()
+            }
+        });
+
+        // The spec takes precedence over the static input shape of the session
+        model.input_shape = spec
+            .input_shape
+            .or_else(|| model.info.input_shape(model.preprocess.layout))
+            .unwrap_or(DEFAULT_INPUT_SHAPE);
+
+        model.info.preprocess = Some(model.preprocess.clone());
+        model.info.input_shape = Some(model.input_shape);
+
+        if model.session.initialized() {
+            match model
+                .info
+                .validate(&model.inputs, model.input_shape, &model.preprocess)
+            {
+                Ok(()) => {
+                    let _ = model.active.set(());
+                }
+                Err(err) => {
+                    tracing::error!("{}", err);
+                    model.info.error = Some(err.to_string());
+                }
             }
         }
 
-        Ok(predictor)
+        model
     }
 
     #[inline]
-    pub fn active(&self) -> bool {
+    fn active(&self) -> bool {
         self.active.get().is_some()
     }
 
-    fn run_prediction(&self, image: Array4<f32>) -> Result<Vec<f32>> {
-        let outputs = self
+    /// Run the model and return the score of the output
+    fn run_prediction(&self, tensors: Vec<Array4<f32>>) -> Result<f32> {
+        let mut inputs = Vec::with_capacity(tensors.len());
+        for (name, tensor) in self.inputs.iter().zip(tensors) {
+            inputs.push((
+                Cow::from(name.as_str()),
+                SessionInputValue::from(DynValue::try_from(tensor)?),
+            ));
+        }
+
+        let session = self
             .session
             .get()
-            .ok_or_else(|| Error::OnnxSessionNotInitialized)?
-            .run(ort::inputs! {
-                "input" => image,
-            }?)?;
-        let output = outputs[0]
+            .ok_or_else(|| Error::OnnxSessionNotInitialized)?;
+        let outputs = tracing::info_span!("session_run", model = %self.info.model)
+            .in_scope(|| session.run(inputs))?;
+        let output = match self.preprocess.output {
+            Some(ref name) => &outputs[name.as_str()],
+            None => &outputs[0],
+        };
+        let output = output
             .try_extract_tensor::<f32>()?
             .into_owned()
             .into_iter()
             .collect();
-        Ok(output)
+
+        self.preprocess
+            .activate(output)
+            .get(self.preprocess.score_index)
+            .copied()
+            .ok_or_else(|| Error::InvalidModelOutput(self.preprocess.score_index))
     }
+}
 
+/// The models of a predictor and the test-time augmentation applied to their
+/// inputs. A single model is an ensemble of one.
+struct Ensemble {
+    models: Vec<Model>,
+    combine: Combine,
+    transforms: Vec<Transform>,
+}
+
+impl Ensemble {
+    /// Create the sessions of the spec's model and its ensemble members.
+    async fn new(spec: &ModelSpec, config: &ONNXConfig, inputs: &[&str]) -> Ensemble {
+        let mut models = Vec::with_capacity(1 + spec.ensemble.len());
+        for model in std::iter::once(&spec.model).chain(&spec.ensemble) {
+            let spec = ModelSpec {
+                model: model.clone(),
+                ..spec.clone()
+            };
+            models.push(Model::new(&spec, config, inputs).await);
+        }
+
+        Ensemble {
+            models,
+            combine: spec.combine,
+            transforms: spec
+                .augment
+                .as_ref()
+                .map(Augment::transforms)
+                .unwrap_or_else(|| vec![Transform::Identity]),
+        }
+    }
+
+    /// The ensemble is active if all of its models are.
     #[inline]
-    pub fn predict(&self, mut image: DynamicImage) -> Result<i32> {
-        let mut max_prediction = f32::NEG_INFINITY;
-        let mut max_index: i32 = -1;
-
-        for i in 0..6 {
-            let ts = process_classifier_image(&mut image, i, self.input_shape)?;
-
-            let prediction = self.run_prediction(ts)?;
-            let prediction_value = prediction[0];
-            if prediction_value > max_prediction {
-                max_prediction = prediction_value;
-                max_index = i as i32;
+    fn active(&self) -> bool {
+        self.models.iter().all(Model::active)
+    }
+
+    fn models(&self) -> Vec<ModelInfo> {
+        self.models.iter().map(|model| model.info.clone()).collect()
+    }
+
+    /// Score the candidates with every model and combine the score vectors.
+    ///
+    /// `tensors` returns the input tensors of each candidate for a model and
+    /// transform, the scores of all transforms are averaged per model.
+    fn predict<F>(&self, candidates: u32, tensors: F) -> Result<Prediction>
+    where
+        F: Fn(&Model, Transform) -> Result<Vec<Vec<Array4<f32>>>>,
+    {
+        let mut scores = Vec::with_capacity(self.models.len());
+        for model in &self.models {
+            let mut score = vec![0.0; candidates as usize];
+            for &transform in &self.transforms {
+                for (i, inputs) in tensors(model, transform)?.into_iter().enumerate() {
+                    score[i] += model.run_prediction(inputs)? / self.transforms.len() as f32;
+                }
             }
+            scores.push(score);
         }
 
-        Ok(max_index)
+        let answer = self
+            .combine
+            .answer(&scores)
+            .ok_or(Error::InvalidModelOutput(0))?;
+        Ok(Prediction {
+            answer: answer as i32,
+            scores,
+        })
     }
 }
 
-pub struct ImagePairClassifierPredictor {
-    input_shape: (u32, u32),
-    session: OnceCell<Session>,
-    active: OnceCell<()>,
-    is_grayscale: bool,
+pub struct ImageClassifierPredictor {
+    ensemble: Ensemble,
+    grid: Option<GridLayout>,
 }
 
-impl ImagePairClassifierPredictor {
-    pub async fn new(
-        onnx: &'static str,
-        input_shape: Option<(u32, u32)>,
-        config: &ONNXConfig,
-        is_grayscale: bool,
-    ) -> Result<Self> {
-        let predictor = ImagePairClassifierPredictor {
-            session: OnceCell::new(),
-            active: OnceCell::new(),
-            input_shape: input_shape.unwrap_or((52, 52)),
-            is_grayscale,
-        };
+impl ImageClassifierPredictor {
+    pub async fn new(spec: &ModelSpec, config: &ONNXConfig) -> Result<Self> {
+        Ok(ImageClassifierPredictor {
+            ensemble: Ensemble::new(spec, config, &["input"]).await,
+            grid: spec.grid,
+        })
+    }
 
-        // If the session is created successfully, set the session and wait for it to be initialized
-        match create_onnx_session(onnx, config).await {
-            Ok(session) => {
-                let _ = predictor.session.set(session);
-                let _ = predictor.active.set(());
-            }
-            Err(err) => {
-                tracing::warn!("Failed to create session: {}", err);
-            }
-        }
+    /// Resolve the grid layout of the image, the configured layout or the one
+    /// detected from the image dimensions.
+    fn grid_layout(&self, dimensions: (u32, u32)) -> Result<GridLayout> {
+        self.grid
+            .or_else(|| GridLayout::detect(dimensions))
+            .ok_or(Error::InvalidImageSize(dimensions))
+    }
+}
+
+impl Predictor for ImageClassifierPredictor {
+    #[inline]
+    fn prediction(&self, image: DynamicImage) -> Result<Prediction> {
+        let dimensions = image.dimensions();
+        let layout = self.grid_layout(dimensions)?;
+        let cells = layout.cells(dimensions)?;
 
-        Ok(predictor)
+        self.ensemble.predict(cells, |model, transform| {
+            (0..cells)
+                .map(|i| {
+                    let ts = process_classifier_image(
+                        &image,
+                        layout.cell_rect(i),
+                        model.input_shape,
+                        &model.preprocess,
+                        transform,
+                    )?;
+                    Ok(vec![ts])
+                })
+                .collect()
+        })
     }
 
     #[inline]
-    pub fn active(&self) -> bool {
-        self.active.get().is_some()
+    fn active(&self) -> bool {
+        self.ensemble.active()
     }
 
-    /// Run prediction on the model
-    pub fn run_prediction(&self, left: Array4<f32>, right: Array4<f32>) -> Result<Vec<f32>> {
-        let inputs = ort::inputs! {
-            "input_left" => left,
-            "input_right" => right,
-        }?;
+    fn models(&self) -> Vec<ModelInfo> {
+        self.ensemble.models()
+    }
+}
 
-        let outputs = self
-            .session
-            .get()
-            .ok_or_else(|| Error::OnnxSessionNotInitialized)?
-            .run(inputs)?;
-        let output = outputs[0]
-            .try_extract_tensor::<f32>()?
-            .into_owned()
-            .into_iter()
-            .collect();
-        Ok(output)
+pub struct ImagePairClassifierPredictor {
+    ensemble: Ensemble,
+    tiles: Option<TileLayout>,
+}
+
+impl ImagePairClassifierPredictor {
+    pub async fn new(spec: &ModelSpec, config: &ONNXConfig) -> Result<Self> {
+        Ok(ImagePairClassifierPredictor {
+            ensemble: Ensemble::new(spec, config, &["input_left", "input_right"]).await,
+            tiles: spec.tiles,
+        })
     }
 
+    /// Resolve the tile layout of the image, the configured layout or the one
+    /// detected from the image dimensions.
+    fn tile_layout(&self, dimensions: (u32, u32)) -> Result<TileLayout> {
+        self.tiles
+            .or_else(|| TileLayout::detect(dimensions))
+            .ok_or(Error::InvalidImageSize(dimensions))
+    }
+}
+
+impl Predictor for ImagePairClassifierPredictor {
     #[inline]
-    pub fn predict(&self, mut image: DynamicImage) -> Result<i32> {
-        check_input_image_size(&image)?;
-
-        let mut max_prediction = f32::NEG_INFINITY;
-        let width = image.width();
-        let mut max_index = 0;
-        let left =
-            process_pair_classifier_ans_image(&mut image, self.input_shape, self.is_grayscale)?;
-
-        for i in 0..(width / 200) {
-            let right =
-                process_pair_classifier_image(&image, (0, i), self.input_shape, self.is_grayscale)?;
-            let prediction = self.run_prediction(left.clone(), right)?;
-            let prediction_value = prediction[0];
-            if prediction_value > max_prediction {
-                max_prediction = prediction_value;
-                max_index = i;
-            }
-        }
-        Ok(max_index as i32)
+    fn prediction(&self, image: DynamicImage) -> Result<Prediction> {
+        let dimensions = image.dimensions();
+        let layout = self.tile_layout(dimensions)?;
+        let candidates = layout.candidates(dimensions)?;
+
+        self.ensemble.predict(candidates, |model, transform| {
+            let left = process_pair_classifier_ans_image(
+                &image,
+                layout.reference,
+                model.input_shape,
+                &model.preprocess,
+                transform,
+            )?;
+
+            (0..candidates)
+                .map(|i| {
+                    let right = process_pair_classifier_image(
+                        &image,
+                        layout.tile_rect(i),
+                        model.input_shape,
+                        &model.preprocess,
+                        transform,
+                    )?;
+                    Ok(vec![left.clone(), right])
+                })
+                .collect()
+        })
+    }
+
+    #[inline]
+    fn active(&self) -> bool {
+        self.ensemble.active()
+    }
+
+    fn models(&self) -> Vec<ModelInfo> {
+        self.ensemble.models()
     }
 }
 
-async fn create_onnx_session(onnx: &'static str, config: &ONNXConfig) -> Result<Session> {
+async fn create_onnx_session(onnx: &str, config: &ONNXConfig) -> Result<(PathBuf, Session)> {
     let model_dir = config
         .model_dir
         .as_ref()
@@ -193,6 +385,7 @@ async fn create_onnx_session(onnx: &'static str, config: &ONNXConfig) -> Result<
     let model_file = config
         .onnx_store
         .fetch_model(onnx, model_dir, config.update_check)
+        .instrument(tracing::info_span!("fetch_model", model = onnx))
         .await?;
 
     // Create a new session
@@ -221,6 +414,6 @@ async fn create_onnx_session(onnx: &'static str, config: &ONNXConfig) -> Result<
             config.allocator,
             MemoryType::Default,
         )?)?
-        .commit_from_file(model_file)?;
-    Ok(session)
+        .commit_from_file(&model_file)?;
+    Ok((model_file, session))
 }
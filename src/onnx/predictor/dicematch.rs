@@ -1,23 +0,0 @@
-use super::{base::ImageClassifierPredictor, Predictor};
-use crate::{onnx::ONNXConfig, Result};
-use image::DynamicImage;
-pub struct DicematchMatchPredictor(ImageClassifierPredictor);
-
-impl DicematchMatchPredictor {
-    /// Create a new instance of the DicematchMatchPredictor
-    pub async fn new(config: &ONNXConfig) -> Result<Self> {
-        Ok(Self(
-            ImageClassifierPredictor::new("dicematch.onnx", None, config).await?,
-        ))
-    }
-}
-
-impl Predictor for DicematchMatchPredictor {
-    fn predict(&self, image: DynamicImage) -> Result<i32> {
-        self.0.predict(image)
-    }
-
-    fn active(&self) -> bool {
-        self.0.active()
-    }
-}
@@ -1,42 +1,90 @@
 mod base;
-#[allow(non_snake_case)]
-pub mod brokenJigsawbrokenjigsaw_swap;
-pub mod card;
-pub mod cardistance;
-pub mod conveyor;
-pub mod coordinatesmatch;
-pub mod counting;
-pub mod dice_pair;
-pub mod diceico;
-pub mod dicematch;
-pub mod frankenhead;
-pub mod hand_number_puzzle;
-pub mod hopscotch_highsec;
-pub mod knots_crosses_circle;
-pub mod lumber_length_game;
-pub mod maze2;
-pub mod numericalmatch;
-pub mod orbit_match_game;
-pub mod penguins;
-pub mod penguins_icon;
-pub mod rockstack;
-pub mod rollball_animals_multi;
-pub mod rollball_objects;
-pub mod shadows;
-pub mod train_coordinates;
-pub mod unbentobjects;
 
+use super::{metadata::ModelInfo, preprocess::Preprocess};
+pub use base::{ImageClassifierPredictor, ImagePairClassifierPredictor};
 use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+
+/// Predictor overrides, applied on top of the model spec of the game variant.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct PredictorOptions {
+    /// ONNX model file name, e.g. "3d_rollball_objects_v2.onnx"
+    pub model: Option<String>,
+    /// Model input shape (width, height)
+    pub input_shape: Option<(u32, u32)>,
+    /// Whether the model expects grayscale input
+    pub grayscale: Option<bool>,
+    /// Preprocessing pipeline
+    pub preprocess: Option<Preprocess>,
+}
+
+/// The answer of a predictor and the score vectors it was chosen from, one per
+/// model of the predictor.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Prediction {
+    pub answer: i32,
+    pub scores: Vec<Vec<f32>>,
+}
+
+impl Prediction {
+    /// The mean score of the answer across the models.
+    pub fn confidence(&self) -> f32 {
+        let scores = self
+            .scores
+            .iter()
+            .filter_map(|score| score.get(self.answer as usize))
+            .collect::<Vec<_>>();
+        scores.iter().copied().sum::<f32>() / scores.len().max(1) as f32
+    }
+}
+
+/// Decode a base64 image, with or without the data URL prefix.
+pub fn decode_base64(image: &str) -> crate::Result<Vec<u8>> {
+    Ok(general_purpose::STANDARD.decode(image.split(',').nth(1).unwrap_or(image))?)
+}
 
 pub trait Predictor: Send + Sync {
-    fn predict_base64(&self, image: &String) -> crate::Result<i32> {
-        let image_bytes =
-            general_purpose::STANDARD.decode(image.split(',').nth(1).unwrap_or(image))?;
-        let image = image::load_from_memory(&image_bytes)?;
-        self.predict(image)
+    fn predict_base64(&self, image: &str) -> crate::Result<i32> {
+        Ok(self.prediction_base64(image)?.answer)
     }
 
-    fn predict(&self, image: image::DynamicImage) -> crate::Result<i32>;
+    fn prediction_base64(&self, image: &str) -> crate::Result<Prediction> {
+        let bytes = tracing::info_span!("decode_base64").in_scope(|| decode_base64(image))?;
+        let image =
+            tracing::info_span!("decode_image").in_scope(|| image::load_from_memory(&bytes))?;
+        self.prediction(image)
+    }
+
+    fn predict(&self, image: image::DynamicImage) -> crate::Result<i32> {
+        Ok(self.prediction(image)?.answer)
+    }
+
+    /// Predict the answer along with the score vectors.
+    fn prediction(&self, image: image::DynamicImage) -> crate::Result<Prediction>;
 
     fn active(&self) -> bool;
+
+    /// Information of the models backing the predictor.
+    fn models(&self) -> Vec<ModelInfo>;
+
+    /// Version label of the predictor, the model versions (or file names if
+    /// unset) joined by `+`.
+    fn version(&self) -> String {
+        self.models()
+            .iter()
+            .map(|info| info.version.as_deref().unwrap_or(&info.model).to_owned())
+            .collect::<Vec<_>>()
+            .join("+")
+    }
+
+    /// Fingerprint of the predictor's weights, the model file hashes (or file
+    /// names if unknown) joined by `+`. Unlike the version it changes whenever
+    /// a model file does.
+    fn fingerprint(&self) -> String {
+        self.models()
+            .iter()
+            .map(|info| info.sha256.as_deref().unwrap_or(&info.model).to_owned())
+            .collect::<Vec<_>>()
+            .join("+")
+    }
 }
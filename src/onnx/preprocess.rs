@@ -0,0 +1,186 @@
+use crate::Result;
+use image::imageops::FilterType;
+use ndarray::Array4;
+use ort::Session;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// The custom metadata key holding the preprocessing spec of a model.
+pub const PREPROCESS_METADATA_KEY: &str = "preprocess";
+
+/// Image resize filter.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResizeFilter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    #[default]
+    Lanczos3,
+}
+
+impl From<ResizeFilter> for FilterType {
+    fn from(filter: ResizeFilter) -> Self {
+        match filter {
+            ResizeFilter::Nearest => FilterType::Nearest,
+            ResizeFilter::Triangle => FilterType::Triangle,
+            ResizeFilter::CatmullRom => FilterType::CatmullRom,
+            ResizeFilter::Gaussian => FilterType::Gaussian,
+            ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// Input tensor channel order.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelOrder {
+    #[default]
+    Rgb,
+    Bgr,
+    /// Single grayscale channel
+    Luma,
+}
+
+/// Input tensor memory layout.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TensorLayout {
+    #[default]
+    Nchw,
+    Nhwc,
+}
+
+/// Activation applied to the model output before scoring.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Activation {
+    #[default]
+    None,
+    Sigmoid,
+    Softmax,
+}
+
+/// Preprocessing pipeline of a model.
+///
+/// The spec is taken from the model config, a `<model>.json` sidecar file next
+/// to the model, or the `preprocess` custom metadata of the model, in that
+/// order. The default matches the pipeline the bundled models are trained with.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Preprocess {
+    /// Resize filter
+    pub filter: ResizeFilter,
+    /// Per-channel mean, subtracted after scaling pixels to `[0, 1]`
+    pub mean: [f32; 3],
+    /// Per-channel standard deviation, divided after the mean is subtracted
+    pub std: [f32; 3],
+    /// Input channel order
+    pub channel_order: ChannelOrder,
+    /// Input tensor layout
+    pub layout: TensorLayout,
+    /// Input tensor names, the predictor's defaults if unset
+    pub inputs: Option<Vec<String>>,
+    /// Output tensor name, the first output if unset
+    pub output: Option<String>,
+    /// Output activation
+    pub activation: Activation,
+    /// Index of the score in the output vector
+    pub score_index: usize,
+}
+
+impl Default for Preprocess {
+    fn default() -> Self {
+        Self {
+            filter: ResizeFilter::default(),
+            mean: [0.0; 3],
+            std: [1.0; 3],
+            channel_order: ChannelOrder::default(),
+            layout: TensorLayout::default(),
+            inputs: None,
+            output: None,
+            activation: Activation::default(),
+            score_index: 0,
+        }
+    }
+}
+
+impl Preprocess {
+    /// Returns the sidecar file path of the model, e.g. `card.onnx.json`.
+    pub fn sidecar(model_file: &Path) -> PathBuf {
+        let mut sidecar = model_file.as_os_str().to_owned();
+        sidecar.push(".json");
+        PathBuf::from(sidecar)
+    }
+
+    /// Load the spec from the sidecar file or the model metadata.
+    /// Returns `None` if neither provides one.
+    pub fn load(model_file: &Path, session: &Session) -> Result<Option<Preprocess>> {
+        let sidecar = Self::sidecar(model_file);
+        if sidecar.exists() {
+            let data = std::fs::read(&sidecar)?;
+            return Ok(Some(serde_json::from_slice(&data)?));
+        }
+
+        match session.metadata()?.custom(PREPROCESS_METADATA_KEY)? {
+            Some(data) => Ok(Some(serde_json::from_str(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Convert the image to the model input tensor.
+    pub fn tensor(
+        &self,
+        image: image::DynamicImage,
+        input_shape: (u32, u32),
+    ) -> Result<Array4<f32>> {
+        let image = image.resize_exact(input_shape.0, input_shape.1, self.filter.into());
+        let (width, height) = (input_shape.0 as usize, input_shape.1 as usize);
+
+        let (channels, pixels) = match self.channel_order {
+            ChannelOrder::Luma => (1, image.into_luma8().into_raw()),
+            ChannelOrder::Rgb => (3, image.into_rgb8().into_raw()),
+            ChannelOrder::Bgr => {
+                let mut pixels = image.into_rgb8().into_raw();
+                pixels
+                    .chunks_exact_mut(3)
+                    .for_each(|pixel| pixel.swap(0, 2));
+                (3, pixels)
+            }
+        };
+
+        let normalized_vec: Vec<f32> = pixels
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let channel = i % channels;
+                (v as f32 / 255.0 - self.mean[channel]) / self.std[channel]
+            })
+            .collect();
+
+        let normalized_image =
+            Array4::from_shape_vec((1, height, width, channels), normalized_vec)?;
+        match self.layout {
+            TensorLayout::Nhwc => Ok(normalized_image),
+            TensorLayout::Nchw => Ok(normalized_image.permuted_axes([0, 3, 1, 2])),
+        }
+    }
+
+    /// Apply the output activation.
+    pub fn activate(&self, mut output: Vec<f32>) -> Vec<f32> {
+        match self.activation {
+            Activation::None => {}
+            Activation::Sigmoid => output
+                .iter_mut()
+                .for_each(|v| *v = 1.0 / (1.0 + (-*v).exp())),
+            Activation::Softmax => {
+                let max = output.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                output.iter_mut().for_each(|v| *v = (*v - max).exp());
+                let sum: f32 = output.iter().sum();
+                output.iter_mut().for_each(|v| *v /= sum);
+            }
+        }
+        output
+    }
+}
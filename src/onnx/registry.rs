@@ -0,0 +1,257 @@
+use super::{
+    augment::Augment,
+    ensemble::Combine,
+    layout::{GridLayout, TileLayout},
+    phash::dhash,
+    predictor::PredictorOptions,
+    preprocess::Preprocess,
+    util::{crop_funcaptcha_ans_image, crop_funcaptcha_image},
+    Variant,
+};
+use crate::{error::Error, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// The predictor implementation used to run a model.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PredictorKind {
+    /// Scores each cell of the image grid, answers the highest scoring cell
+    Classifier,
+    /// Scores each candidate tile against the reference image, answers the highest scoring tile
+    PairClassifier,
+}
+
+/// Model description of a game variant.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ModelSpec {
+    /// ONNX model file name, e.g. "3d_rollball_objects.onnx"
+    pub model: String,
+    /// Predictor implementation
+    pub kind: PredictorKind,
+    /// Model input shape (width, height), default (52, 52)
+    #[serde(default)]
+    pub input_shape: Option<(u32, u32)>,
+    /// Whether the model expects grayscale input
+    #[serde(default)]
+    pub grayscale: bool,
+    /// Tile geometry of the pair classifier, detected from the image size if unset
+    #[serde(default)]
+    pub tiles: Option<TileLayout>,
+    /// Grid geometry of the classifier, detected from the image size if unset
+    #[serde(default)]
+    pub grid: Option<GridLayout>,
+    /// Preprocessing pipeline, read from the sidecar file or model metadata if unset
+    #[serde(default)]
+    pub preprocess: Option<Preprocess>,
+    /// Additional ONNX model files ensembled with `model`
+    #[serde(default)]
+    pub ensemble: Vec<String>,
+    /// How the ensemble's score vectors are combined
+    #[serde(default)]
+    pub combine: Combine,
+    /// Test-time augmentation
+    #[serde(default)]
+    pub augment: Option<Augment>,
+}
+
+impl ModelSpec {
+    /// Returns a copy of the spec with the predictor overrides applied.
+    pub fn with_options(&self, options: &PredictorOptions) -> ModelSpec {
+        ModelSpec {
+            model: options.model.clone().unwrap_or_else(|| self.model.clone()),
+            kind: self.kind,
+            input_shape: options.input_shape.or(self.input_shape),
+            grayscale: options.grayscale.unwrap_or(self.grayscale),
+            tiles: self.tiles,
+            grid: self.grid,
+            preprocess: options
+                .preprocess
+                .clone()
+                .or_else(|| self.preprocess.clone()),
+            ensemble: self.ensemble.clone(),
+            combine: self.combine,
+            augment: self.augment.clone(),
+        }
+    }
+
+    /// Returns the number of candidates of an image of the dimensions, the grid
+    /// cells of a classifier or the candidate tiles of a pair classifier.
+    pub fn candidates(&self, dimensions: (u32, u32)) -> Result<u32> {
+        match self.kind {
+            PredictorKind::Classifier => self
+                .grid
+                .or_else(|| GridLayout::detect(dimensions))
+                .ok_or(Error::InvalidImageSize(dimensions))?
+                .cells(dimensions),
+            PredictorKind::PairClassifier => self
+                .tiles
+                .or_else(|| TileLayout::detect(dimensions))
+                .ok_or(Error::InvalidImageSize(dimensions))?
+                .candidates(dimensions),
+        }
+    }
+
+    /// Returns the perceptual hashes of the image's tiles: the grid cells of a
+    /// classifier, or the reference image followed by the candidate tiles of a
+    /// pair classifier.
+    pub fn tile_hashes(&self, image: &image::DynamicImage) -> Result<Vec<u64>> {
+        use image::GenericImageView;
+
+        let dimensions = image.dimensions();
+        let hashes = match self.kind {
+            PredictorKind::Classifier => {
+                let layout = self
+                    .grid
+                    .or_else(|| GridLayout::detect(dimensions))
+                    .ok_or(Error::InvalidImageSize(dimensions))?;
+                (0..layout.cells(dimensions)?)
+                    .map(|i| dhash(&crop_funcaptcha_image(image, layout.cell_rect(i))))
+                    .collect()
+            }
+            PredictorKind::PairClassifier => {
+                let layout = self
+                    .tiles
+                    .or_else(|| TileLayout::detect(dimensions))
+                    .ok_or(Error::InvalidImageSize(dimensions))?;
+                let reference = dhash(&crop_funcaptcha_ans_image(image, layout.reference));
+                std::iter::once(reference)
+                    .chain(
+                        (0..layout.candidates(dimensions)?)
+                            .map(|i| dhash(&crop_funcaptcha_image(image, layout.tile_rect(i)))),
+                    )
+                    .collect()
+            }
+        };
+        Ok(hashes)
+    }
+}
+
+impl From<Variant> for ModelSpec {
+    /// The compiled-in model spec of the variant.
+    fn from(variant: Variant) -> Self {
+        let (model, kind, input_shape) = match variant {
+            Variant::RollballAnimals => (
+                "3d_rollball_objects.onnx",
+                PredictorKind::PairClassifier,
+                None,
+            ),
+            Variant::RollballObjects => (
+                "3d_rollball_objects.onnx",
+                PredictorKind::PairClassifier,
+                None,
+            ),
+            Variant::RollballAnimalsMulti => (
+                "3d_rollball_animals_multi.onnx",
+                PredictorKind::PairClassifier,
+                None,
+            ),
+            Variant::Coordinatesmatch => {
+                ("coordinatesmatch.onnx", PredictorKind::PairClassifier, None)
+            }
+            Variant::HopscotchHighsec => (
+                "hopscotch_highsec.onnx",
+                PredictorKind::PairClassifier,
+                None,
+            ),
+            Variant::TrainCoordinates => (
+                "train_coordinates.onnx",
+                PredictorKind::PairClassifier,
+                None,
+            ),
+            Variant::Penguins => ("penguin.onnx", PredictorKind::Classifier, None),
+            Variant::Shadows => ("shadows.onnx", PredictorKind::Classifier, None),
+            Variant::BrokenJigsawbrokenjigsaw_swap => (
+                "BrokenJigsawbrokenjigsaw_swap.onnx",
+                PredictorKind::PairClassifier,
+                None,
+            ),
+            Variant::Frankenhead => ("frankenhead.onnx", PredictorKind::Classifier, None),
+            Variant::Counting => ("counting.onnx", PredictorKind::Classifier, None),
+            Variant::Card => ("card.onnx", PredictorKind::Classifier, None),
+            Variant::Rockstack => ("rockstack.onnx", PredictorKind::PairClassifier, None),
+            Variant::Cardistance => ("cardistance.onnx", PredictorKind::PairClassifier, None),
+            Variant::PenguinsIcon => ("penguins-icon.onnx", PredictorKind::Classifier, None),
+            Variant::KnotsCrossesCircle => {
+                ("knotsCrossesCircle.onnx", PredictorKind::Classifier, None)
+            }
+            Variant::HandNumberPuzzle => {
+                ("hand_number_puzzle.onnx", PredictorKind::Classifier, None)
+            }
+            Variant::Dicematch => ("dicematch.onnx", PredictorKind::Classifier, None),
+            Variant::Numericalmatch => (
+                "numericalmatch.onnx",
+                PredictorKind::PairClassifier,
+                Some((100, 100)),
+            ),
+            Variant::Conveyor => ("conveyor.onnx", PredictorKind::PairClassifier, None),
+            Variant::Unbentobjects => ("knotsCrossesCircle.onnx", PredictorKind::Classifier, None),
+            Variant::LumberLengthGame => (
+                "lumber-length-game.onnx",
+                PredictorKind::PairClassifier,
+                None,
+            ),
+            Variant::DicePair => ("dice_pair.onnx", PredictorKind::Classifier, None),
+            Variant::OrbitMatchGame => {
+                ("orbit_match_game.onnx", PredictorKind::PairClassifier, None)
+            }
+            Variant::Diceico => ("diceico.onnx", PredictorKind::PairClassifier, None),
+            Variant::Maze2 => ("maze2.onnx", PredictorKind::Classifier, None),
+        };
+
+        ModelSpec {
+            model: model.to_owned(),
+            kind,
+            input_shape,
+            grayscale: false,
+            tiles: None,
+            grid: None,
+            preprocess: None,
+            ensemble: vec![],
+            combine: Combine::default(),
+            augment: None,
+        }
+    }
+}
+
+/// Model registry, maps the game variant key to its model spec.
+///
+/// The compiled-in table is the default, entries loaded from the configuration
+/// file replace or extend it, so a new game whose model fits an existing
+/// [`PredictorKind`] only needs an `.onnx` file and a config entry.
+#[derive(Deserialize, Debug)]
+#[serde(from = "HashMap<String, ModelSpec>")]
+pub struct Registry(HashMap<String, ModelSpec>);
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self(
+            Variant::ALL
+                .into_iter()
+                .map(|variant| (variant.as_str().to_owned(), ModelSpec::from(variant)))
+                .collect(),
+        )
+    }
+}
+
+impl From<HashMap<String, ModelSpec>> for Registry {
+    fn from(models: HashMap<String, ModelSpec>) -> Self {
+        let mut registry = Registry::default();
+        registry.0.extend(models);
+        registry
+    }
+}
+
+impl Registry {
+    /// Get the model spec of a game variant.
+    pub fn get(&self, game_variant: &str) -> Result<&ModelSpec> {
+        self.0
+            .get(game_variant)
+            .ok_or_else(|| Error::UnknownVariantType(game_variant.to_owned()))
+    }
+
+    /// Returns an iterator over the registered game variant keys.
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.0.keys()
+    }
+}
@@ -0,0 +1,147 @@
+use super::predictor::PredictorOptions;
+use crate::{error::Error, Result};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+/// A routing rule that selects predictor options for a game variant and
+/// instruction pattern.
+///
+/// The instruction text is normalised (lowercase, punctuation stripped and
+/// whitespace collapsed) before matching, so `instructions` and `pattern` are
+/// written against the normalised form.
+#[derive(Deserialize, Debug)]
+pub struct Route {
+    /// Game variant key, e.g. "3d_rollball_objects"
+    pub game_variant: String,
+    /// Normalised instruction text to match exactly
+    #[serde(default)]
+    instructions: Option<String>,
+    /// Regular expression matched against the normalised instruction text
+    #[serde(default, deserialize_with = "deserialize_regex")]
+    pattern: Option<Regex>,
+    /// Predictor overrides, e.g. model file, input shape or grayscale
+    #[serde(flatten)]
+    pub options: PredictorOptions,
+    /// Answer remapping, indexed by the predicted answer
+    #[serde(default)]
+    pub answers: Option<Vec<i32>>,
+}
+
+impl Route {
+    /// Check if the route matches the game variant and normalised instructions.
+    /// A route without `instructions` or `pattern` matches every instruction.
+    fn matches(&self, game_variant: &str, instructions: &str) -> bool {
+        if self.game_variant != game_variant {
+            return false;
+        }
+
+        if let Some(ref expected) = self.instructions {
+            if expected != instructions {
+                return false;
+            }
+        }
+
+        if let Some(ref pattern) = self.pattern {
+            if !pattern.is_match(instructions) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Map a predicted answer through the route's answer table.
+    /// Answers outside the table are returned unchanged.
+    pub fn map_answer(&self, answer: i32) -> i32 {
+        self.answers
+            .as_ref()
+            .and_then(|answers| usize::try_from(answer).ok().and_then(|i| answers.get(i)))
+            .copied()
+            .unwrap_or(answer)
+    }
+}
+
+/// Ordered list of routing rules, the first matching rule wins.
+#[derive(Deserialize, Default, Debug)]
+#[serde(from = "Vec<Route>")]
+pub struct RouteTable(Vec<Route>);
+
+impl From<Vec<Route>> for RouteTable {
+    fn from(mut routes: Vec<Route>) -> Self {
+        for route in routes.iter_mut() {
+            if let Some(instructions) = route.instructions.take() {
+                route.instructions = Some(normalize(&instructions));
+            }
+        }
+        Self(routes)
+    }
+}
+
+impl RouteTable {
+    /// Returns the number of routes.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if there are no routes.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns an iterator over the routes.
+    pub fn iter(&self) -> std::slice::Iter<'_, Route> {
+        self.0.iter()
+    }
+
+    /// Resolve the route for a game variant and its instructions.
+    ///
+    /// # Returns
+    /// - `Ok(None)` if no route is configured for the game variant, the compiled-in
+    ///   predictor should be used.
+    /// - `Ok(Some((index, route)))` with the first matching route.
+    /// - `Err(Error::UnknownInstructionVariant)` if routes are configured for the game
+    ///   variant but none of them matches the instructions.
+    pub fn resolve(
+        &self,
+        game_variant: &str,
+        instructions: &str,
+    ) -> Result<Option<(usize, &Route)>> {
+        if !self
+            .0
+            .iter()
+            .any(|route| route.game_variant == game_variant)
+        {
+            return Ok(None);
+        }
+
+        let normalized = normalize(instructions);
+        self.0
+            .iter()
+            .enumerate()
+            .find(|(_, route)| route.matches(game_variant, &normalized))
+            .map(Some)
+            .ok_or_else(|| {
+                Error::UnknownInstructionVariant(game_variant.to_owned(), instructions.to_owned())
+            })
+    }
+}
+
+/// Normalise instruction text: lowercase, non-alphanumeric characters replaced
+/// by spaces and whitespace collapsed.
+pub fn normalize(instructions: &str) -> String {
+    instructions
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> std::result::Result<Option<Regex>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|pattern| Regex::new(&pattern).map_err(serde::de::Error::custom))
+        .transpose()
+}
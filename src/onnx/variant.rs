@@ -32,48 +32,89 @@ pub enum Variant {
 }
 
 impl Variant {
+    /// All compiled-in variants.
+    pub const ALL: [Variant; LENGTH] = [
+        Variant::RollballAnimals,
+        Variant::RollballObjects,
+        Variant::RollballAnimalsMulti,
+        Variant::Coordinatesmatch,
+        Variant::HopscotchHighsec,
+        Variant::TrainCoordinates,
+        Variant::Penguins,
+        Variant::Shadows,
+        Variant::BrokenJigsawbrokenjigsaw_swap,
+        Variant::Frankenhead,
+        Variant::Counting,
+        Variant::Card,
+        Variant::Rockstack,
+        Variant::Cardistance,
+        Variant::PenguinsIcon,
+        Variant::KnotsCrossesCircle,
+        Variant::HandNumberPuzzle,
+        Variant::Dicematch,
+        Variant::Numericalmatch,
+        Variant::Conveyor,
+        Variant::Unbentobjects,
+        Variant::LumberLengthGame,
+        Variant::DicePair,
+        Variant::OrbitMatchGame,
+        Variant::Diceico,
+        Variant::Maze2,
+    ];
+
     /// Returns the number of variants in the enum.
     pub const fn const_count() -> usize {
         LENGTH
     }
+
+    /// Returns the game variant key, e.g. "3d_rollball_objects".
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Variant::RollballAnimals => "3d_rollball_animals",
+            Variant::RollballObjects => "3d_rollball_objects",
+            Variant::RollballAnimalsMulti => "3d_rollball_animals_multi",
+            Variant::Coordinatesmatch => "coordinatesmatch",
+            Variant::HopscotchHighsec => "hopscotch_highsec",
+            Variant::TrainCoordinates => "train_coordinates",
+            Variant::Penguins => "penguins",
+            Variant::Shadows => "shadows",
+            Variant::BrokenJigsawbrokenjigsaw_swap => "BrokenJigsawbrokenjigsaw_swap",
+            Variant::Frankenhead => "frankenhead",
+            Variant::Counting => "counting",
+            Variant::Card => "card",
+            Variant::Rockstack => "rockstack",
+            Variant::Cardistance => "cardistance",
+            Variant::PenguinsIcon => "penguins-icon",
+            Variant::KnotsCrossesCircle => "knotsCrossesCircle",
+            Variant::HandNumberPuzzle => "hand_number_puzzle",
+            Variant::Dicematch => "dicematch",
+            Variant::Numericalmatch => "numericalmatch",
+            Variant::Conveyor => "conveyor",
+            Variant::Unbentobjects => "unbentobjects",
+            Variant::LumberLengthGame => "lumber-length-game",
+            Variant::DicePair => "dice_pair",
+            Variant::OrbitMatchGame => "orbit_match_game",
+            Variant::Diceico => "diceico",
+            Variant::Maze2 => "maze2",
+        }
+    }
+}
+
+impl TryFrom<&str> for Variant {
+    type Error = Error;
+
+    fn try_from(game_variant: &str) -> Result<Self> {
+        Variant::ALL
+            .into_iter()
+            .find(|variant| variant.as_str() == game_variant)
+            .ok_or_else(|| Error::UnknownVariantType(game_variant.to_owned()))
+    }
 }
 
 impl TryFrom<&Task> for Variant {
     type Error = Error;
 
     fn try_from(task: &Task) -> Result<Self> {
-        let variant = match task.game_variant_instructions.0.as_str() {
-            "3d_rollball_animals" => Variant::RollballAnimals,
-            "3d_rollball_objects" => Variant::RollballObjects,
-            "3d_rollball_animals_multi" => Variant::RollballAnimalsMulti,
-            "coordinatesmatch" => Variant::Coordinatesmatch,
-            "hopscotch_highsec" => Variant::HopscotchHighsec,
-            "train_coordinates" => Variant::TrainCoordinates,
-            "penguins" => Variant::Penguins,
-            "shadows" => Variant::Shadows,
-            "BrokenJigsawbrokenjigsaw_swap" => Variant::BrokenJigsawbrokenjigsaw_swap,
-            "frankenhead" => Variant::Frankenhead,
-            "counting" => Variant::Counting,
-            "card" => Variant::Card,
-            "rockstack" => Variant::Rockstack,
-            "cardistance" => Variant::Cardistance,
-            "penguins-icon" => Variant::PenguinsIcon,
-            "knotsCrossesCircle" => Variant::KnotsCrossesCircle,
-            "hand_number_puzzle" => Variant::HandNumberPuzzle,
-            "dicematch" => Variant::Dicematch,
-            "numericalmatch" => Variant::Numericalmatch,
-            "conveyor" => Variant::Conveyor,
-            "unbentobjects" => Variant::Unbentobjects,
-            "lumber-length-game" => Variant::LumberLengthGame,
-            "dice_pair" => Variant::DicePair,
-            "orbit_match_game" => Variant::OrbitMatchGame,
-            "diceico" => Variant::Diceico,
-            "maze2" => Variant::Maze2,
-            _ => Err(Error::UnknownVariantType(
-                task.game_variant_instructions.0.clone(),
-            ))?,
-        };
-
-        Ok(variant)
+        Variant::try_from(task.game_variant_instructions.0.as_str())
     }
 }
@@ -0,0 +1,163 @@
+//! Local admin socket: one JSON line is answered per command line, e.g.
+//! `status`, errors are answered as `{"error": "..."}`.
+
+use crate::{error::Error, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    future::Future,
+    io::{BufRead, BufReader, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    time::Duration,
+};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+/// Longest command line accepted.
+const MAX_COMMAND_LEN: u64 = 1024;
+
+/// Time a client has to send its command and the server to answer it.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Server status answered to the `status` command
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AdminStatus {
+    /// Server process ID
+    pub pid: u32,
+    /// Server version
+    pub version: String,
+    /// Seconds since the server started
+    pub uptime: u64,
+    /// Address the server listens on, unset until it listens
+    pub bind: Option<String>,
+    /// Tasks, inference and fallback calls in flight, awaited on shutdown
+    pub in_flight: usize,
+    /// Loaded models
+    pub models: Vec<ModelStatus>,
+    /// Tasks served per game variant
+    pub requests: Vec<VariantRequests>,
+    /// Fallback provider usage
+    pub fallbacks: Vec<FallbackUsage>,
+}
+
+/// A loaded model
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ModelStatus {
+    /// Game variant, e.g. "3d_rollball_objects"
+    pub game_variant: String,
+    /// Model file name
+    pub model: String,
+    /// Model version, the `version` custom metadata
+    pub version: Option<String>,
+    /// Whether the predictor is active
+    pub active: bool,
+}
+
+/// Tasks served for a game variant
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VariantRequests {
+    /// Game variant, e.g. "3d_rollball_objects"
+    pub game_variant: String,
+    /// Tasks answered
+    pub ok: u64,
+    /// Tasks failed
+    pub error: u64,
+}
+
+/// Usage of a fallback provider
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FallbackUsage {
+    /// Provider name
+    pub provider: String,
+    /// Tasks answered by the provider
+    pub tasks: u64,
+    /// Images answered by the provider
+    pub images: u64,
+    /// Spend of the provider
+    pub spend: f64,
+}
+
+/// Bind the admin socket, accessible to the current user only. A socket file
+/// left by a server that is gone is replaced. The socket may be bound before
+/// the daemon drops its privileges, it is served once the runtime is started.
+pub fn bind(path: &Path) -> Result<UnixListener> {
+    if UnixStream::connect(path).is_ok() {
+        return Err(Error::AdminError(format!(
+            "Admin socket {} is in use by another server",
+            path.display()
+        )));
+    }
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+        _ => {}
+    }
+    let listener = UnixListener::bind(path)?;
+    listener.set_nonblocking(true)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Answer the commands of the connections to the admin socket with the handler.
+pub async fn serve<H, F>(listener: UnixListener, handler: H)
+where
+    H: Fn(String) -> F + Clone + Send + 'static,
+    F: Future<Output = Result<String>> + Send,
+{
+    let listener = match tokio::net::UnixListener::from_std(listener) {
+        Ok(listener) => listener,
+        Err(err) => return tracing::warn!("Failed to serve the admin socket: {}", err),
+    };
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                tracing::warn!("Failed to accept an admin connection: {}", err);
+                continue;
+            }
+        };
+
+        let handler = handler.clone();
+        tokio::spawn(async move {
+            let (reader, mut writer) = stream.into_split();
+            let answer = tokio::time::timeout(TIMEOUT, async move {
+                let mut command = String::new();
+                tokio::io::BufReader::new(reader.take(MAX_COMMAND_LEN))
+                    .read_line(&mut command)
+                    .await?;
+                handler(command.trim().to_owned()).await
+            })
+            .await
+            .unwrap_or_else(|_| Err(Error::AdminError("Admin command timed out".to_owned())));
+
+            let mut line = answer
+                .unwrap_or_else(|err| serde_json::json!({ "error": err.to_string() }).to_string());
+            line.push('\n');
+            if let Err(err) = writer.write_all(line.as_bytes()).await {
+                tracing::debug!("Failed to answer an admin command: {}", err);
+            }
+        });
+    }
+}
+
+/// Send a command to the admin socket, returning the JSON answer.
+pub fn query(path: &Path, command: &str) -> Result<serde_json::Value> {
+    let mut stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    stream.write_all(format!("{command}\n").as_bytes())?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    let answer: serde_json::Value = serde_json::from_str(&line)?;
+    match answer.get("error").and_then(|err| err.as_str()) {
+        Some(err) => Err(Error::AdminError(err.to_owned())),
+        None => Ok(answer),
+    }
+}
+
+/// Query the status of the server listening on the admin socket.
+pub fn status(path: &Path) -> Result<AdminStatus> {
+    Ok(serde_json::from_value(query(path, "status")?)?)
+}
@@ -0,0 +1,218 @@
+use super::metrics::metrics;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+/// Circuit breaker configuration.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct BreakerConfig {
+    /// Consecutive failures that open the circuit, 0 disables the breaker
+    pub failures: u32,
+    /// Seconds the circuit stays open before a trial call is let through
+    pub cooldown: u64,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            failures: 5,
+            cooldown: 30,
+        }
+    }
+}
+
+/// Circuit state
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Calls go through
+    Closed,
+    /// Calls fail fast until the cooldown elapses
+    Open,
+    /// A trial call is let through, its outcome closes or reopens the circuit
+    HalfOpen,
+}
+
+/// Circuit breaker status
+#[derive(Serialize)]
+pub struct BreakerStatus {
+    /// What the circuit guards, "variant" or "fallback"
+    pub kind: &'static str,
+    /// Game variant or fallback provider name
+    pub name: String,
+    /// Circuit state
+    pub state: BreakerState,
+    /// Consecutive failures
+    pub failures: u32,
+}
+
+struct Inner {
+    state: BreakerState,
+    failures: u32,
+    since: Instant,
+}
+
+/// A circuit breaker guarding calls to a local model or fallback provider.
+pub struct CircuitBreaker {
+    kind: &'static str,
+    name: String,
+    config: RwLock<BreakerConfig>,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(kind: &'static str, name: &str, config: BreakerConfig) -> CircuitBreaker {
+        CircuitBreaker {
+            kind,
+            name: name.to_owned(),
+            config: RwLock::new(config),
+            inner: Mutex::new(Inner {
+                state: BreakerState::Closed,
+                failures: 0,
+                since: Instant::now(),
+            }),
+        }
+    }
+
+    fn config(&self) -> BreakerConfig {
+        self.config
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// Apply a reloaded configuration, the circuit keeps its state.
+    pub fn configure(&self, config: BreakerConfig) {
+        *self.config.write().unwrap_or_else(|err| err.into_inner()) = config;
+    }
+
+    /// Whether a call may go through. An open circuit lets one trial call
+    /// through once the cooldown elapses.
+    pub fn allow(&self) -> bool {
+        let cooldown = Duration::from_secs(self.config().cooldown);
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        match inner.state {
+            BreakerState::Closed => true,
+            BreakerState::Open | BreakerState::HalfOpen if inner.since.elapsed() >= cooldown => {
+                self.transition(&mut inner, BreakerState::HalfOpen);
+                true
+            }
+            BreakerState::Open | BreakerState::HalfOpen => false,
+        }
+    }
+
+    /// Record a successful call, closing the circuit.
+    pub fn success(&self) {
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        inner.failures = 0;
+        self.transition(&mut inner, BreakerState::Closed);
+    }
+
+    /// Record a failed call, opening the circuit after too many consecutive
+    /// failures or a failed trial call.
+    pub fn failure(&self) {
+        let failures = self.config().failures;
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        inner.failures += 1;
+        let open =
+            failures > 0 && (inner.state == BreakerState::HalfOpen || inner.failures >= failures);
+        if open {
+            self.transition(&mut inner, BreakerState::Open);
+        }
+    }
+
+    fn transition(&self, inner: &mut Inner, state: BreakerState) {
+        if state != BreakerState::Closed {
+            // Open and half-open restart the cooldown
+            inner.since = Instant::now();
+        }
+        if inner.state == state {
+            return;
+        }
+
+        tracing::warn!(
+            "Circuit of {} {} {:?} -> {:?} after {} failures",
+            self.kind,
+            self.name,
+            inner.state,
+            state,
+            inner.failures
+        );
+        inner.state = state;
+        metrics().set(
+            "fs_circuit_state",
+            &[("kind", self.kind), ("name", &self.name)],
+            state as u8 as f64,
+        );
+    }
+
+    pub fn status(&self) -> BreakerStatus {
+        let inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        BreakerStatus {
+            kind: self.kind,
+            name: self.name.clone(),
+            state: inner.state,
+            failures: inner.failures,
+        }
+    }
+}
+
+/// Circuit breakers created on first use, keyed by kind and name.
+#[derive(Default)]
+pub struct Breakers {
+    config: RwLock<BreakerConfig>,
+    breakers: Mutex<HashMap<(&'static str, String), Arc<CircuitBreaker>>>,
+}
+
+impl Breakers {
+    pub fn new(config: BreakerConfig) -> Breakers {
+        Breakers {
+            config: RwLock::new(config),
+            ..Default::default()
+        }
+    }
+
+    /// Apply a reloaded configuration to the existing and future breakers,
+    /// the circuits keep their state.
+    pub fn configure(&self, config: BreakerConfig) {
+        let breakers = self.breakers.lock().unwrap_or_else(|err| err.into_inner());
+        for breaker in breakers.values() {
+            breaker.configure(config.clone());
+        }
+        *self.config.write().unwrap_or_else(|err| err.into_inner()) = config;
+    }
+
+    /// Returns the circuit breaker of the game variant's local model. Only
+    /// called for registered game variants, the breakers are never dropped.
+    pub fn variant(&self, game_variant: &str) -> Arc<CircuitBreaker> {
+        self.get("variant", game_variant)
+    }
+
+    /// Returns the circuit breaker of the fallback provider.
+    pub fn fallback(&self, provider: &str) -> Arc<CircuitBreaker> {
+        self.get("fallback", provider)
+    }
+
+    fn get(&self, kind: &'static str, name: &str) -> Arc<CircuitBreaker> {
+        let mut breakers = self.breakers.lock().unwrap_or_else(|err| err.into_inner());
+        breakers
+            .entry((kind, name.to_owned()))
+            .or_insert_with(|| {
+                let config = self.config.read().unwrap_or_else(|err| err.into_inner());
+                Arc::new(CircuitBreaker::new(kind, name, config.clone()))
+            })
+            .clone()
+    }
+
+    /// Returns the status of the circuit breakers, sorted by kind and name.
+    pub fn status(&self) -> Vec<BreakerStatus> {
+        let breakers = self.breakers.lock().unwrap_or_else(|err| err.into_inner());
+        let mut status: Vec<_> = breakers.values().map(|breaker| breaker.status()).collect();
+        status.sort_by(|a, b| (a.kind, &a.name).cmp(&(b.kind, &b.name)));
+        status
+    }
+}
@@ -0,0 +1,269 @@
+use super::{metrics::metrics, persist};
+use crate::{
+    onnx::{decode_base64, Prediction},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Result cache configuration.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Maximum number of cached predictions, the least recently used are evicted first
+    pub capacity: usize,
+    /// Seconds a cached prediction is used
+    pub ttl: u64,
+    /// File the cache is persisted to, kept in memory only if unset
+    pub path: Option<PathBuf>,
+    /// Seconds between saves of the persisted cache
+    pub save_interval: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 10000,
+            ttl: 3600,
+            path: None,
+            save_interval: 60,
+        }
+    }
+}
+
+/// A cached prediction.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Entry {
+    game_variant: String,
+    /// Fingerprint of the model files, see `Predictor::fingerprint`
+    #[serde(alias = "version")]
+    fingerprint: String,
+    prediction: Prediction,
+    /// Expiry in seconds since the epoch
+    expires: u64,
+    /// Recency, the least recently used entry has the lowest
+    #[serde(skip)]
+    used: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<String, Entry>,
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl Inner {
+    /// Mark the entry as the most recently used.
+    fn touch(&mut self, key: &str) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.recency.remove(&entry.used);
+            entry.used = tick;
+            self.recency.insert(tick, key.to_owned());
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.used);
+        }
+    }
+}
+
+/// LRU cache of the predictions of recently seen images, keyed by the sha256
+/// of the decoded image, the game variant and the fingerprint of the model
+/// files, so that a model file replaced in place doesn't serve stale results.
+#[derive(Default)]
+pub struct ResultCache {
+    config: CacheConfig,
+    inner: Mutex<Inner>,
+}
+
+impl ResultCache {
+    /// Create the cache, loading the persisted entries if any.
+    pub fn new(config: CacheConfig) -> ResultCache {
+        let cache = ResultCache {
+            config,
+            ..Default::default()
+        };
+        if let Err(err) = cache.load() {
+            tracing::warn!("Failed to load the result cache: {}", err);
+        }
+        cache
+    }
+
+    #[inline]
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    /// Returns the cache key of the base64 image, `None` if it can't be decoded.
+    pub fn key(game_variant: &str, fingerprint: &str, image: &str) -> Option<String> {
+        let bytes = decode_base64(image).ok()?;
+        let digest = Sha256::digest(&bytes);
+        Some(format!("{game_variant}:{fingerprint}:{digest:x}"))
+    }
+
+    /// Returns the cached prediction, if any and not expired.
+    pub fn get(&self, game_variant: &str, key: &str) -> Option<Prediction> {
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        let prediction = match inner.entries.get(key) {
+            Some(entry) if entry.expires > now() => Some(entry.prediction.clone()),
+            Some(_) => {
+                inner.remove(key);
+                None
+            }
+            None => None,
+        };
+
+        match prediction {
+            Some(_) => {
+                inner.touch(key);
+                metrics().inc("fs_cache_hits_total", &[("variant", game_variant)]);
+            }
+            None => metrics().inc("fs_cache_misses_total", &[("variant", game_variant)]),
+        }
+        prediction
+    }
+
+    /// Cache the prediction, evicting the least recently used entries if full.
+    pub fn insert(
+        &self,
+        game_variant: &str,
+        fingerprint: &str,
+        key: String,
+        prediction: Prediction,
+    ) {
+        self.put(
+            key,
+            Entry {
+                game_variant: game_variant.to_owned(),
+                fingerprint: fingerprint.to_owned(),
+                prediction,
+                expires: now() + self.config.ttl,
+                used: 0,
+            },
+        );
+    }
+
+    fn put(&self, key: String, entry: Entry) {
+        if self.config.capacity == 0 {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        inner.remove(&key);
+        while inner.entries.len() >= self.config.capacity {
+            let Some((_, oldest)) = inner.recency.pop_first() else {
+                break;
+            };
+            inner.entries.remove(&oldest);
+            metrics().inc("fs_cache_evictions_total", &[]);
+        }
+
+        inner.entries.insert(key.clone(), entry);
+        inner.touch(&key);
+        metrics().set("fs_cache_entries", &[], inner.entries.len() as f64);
+    }
+
+    /// Drop the game variant's entries computed by models other than the given
+    /// fingerprints, called when a model is loaded.
+    pub fn retain(&self, game_variant: &str, fingerprints: &[String]) {
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        let stale = inner
+            .entries
+            .iter()
+            .filter(|(_, entry)| {
+                entry.game_variant == game_variant && !fingerprints.contains(&entry.fingerprint)
+            })
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        if stale.is_empty() {
+            return;
+        }
+
+        tracing::info!(
+            "Invalidated {} cached results of {}",
+            stale.len(),
+            game_variant
+        );
+        for key in stale {
+            inner.remove(&key);
+        }
+        metrics().set("fs_cache_entries", &[], inner.entries.len() as f64);
+    }
+
+    /// Returns the number of cached predictions, including expired ones not yet evicted.
+    pub fn len(&self) -> usize {
+        let inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        inner.entries.len()
+    }
+
+    /// Returns true if the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Save the unexpired entries to the configured file, least recently used first.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = self.config.path.as_ref() else {
+            return Ok(());
+        };
+
+        let entries = {
+            let inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+            let now = now();
+            inner
+                .recency
+                .values()
+                .filter_map(|key| inner.entries.get_key_value(key))
+                .filter(|(_, entry)| entry.expires > now)
+                .map(|(key, entry)| (key.clone(), entry.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        persist::save(path, &entries)
+    }
+
+    /// Load the unexpired entries from the configured file, if it exists.
+    fn load(&self) -> Result<()> {
+        let Some(path) = self.config.path.as_ref() else {
+            return Ok(());
+        };
+        let Some(entries) = persist::load::<Vec<(String, Entry)>>(path)? else {
+            return Ok(());
+        };
+
+        let now = now();
+        for (key, entry) in entries {
+            if entry.expires > now {
+                self.put(key, entry);
+            }
+        }
+        Ok(())
+    }
+
+    /// Save the cache every save interval.
+    pub async fn persist(&self) {
+        if self.config.path.is_none() || self.config.save_interval == 0 {
+            return;
+        }
+        persist::every(self.config.save_interval, "result cache", || self.save()).await
+    }
+}
+
+/// Returns the current time in seconds since the epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,110 @@
+//! Process-wide tracking of the work in flight, awaited before the server exits.
+
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        OnceLock,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{sync::Notify, task::JoinHandle};
+
+/// Tasks, inference and fallback calls in flight, and whether the server is
+/// draining.
+#[derive(Default)]
+pub struct Tracker {
+    in_flight: AtomicUsize,
+    idle: Notify,
+    draining: OnceLock<Instant>,
+}
+
+/// Counts work as in flight until dropped.
+pub struct Guard<'a>(&'a Tracker);
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        if self.0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+static TRACKER: OnceLock<Tracker> = OnceLock::new();
+
+/// Returns the process-wide tracker.
+pub fn tracker() -> &'static Tracker {
+    TRACKER.get_or_init(Tracker::default)
+}
+
+impl Tracker {
+    /// Count work as in flight until the guard is dropped.
+    pub fn guard(&self) -> Guard<'_> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        Guard(self)
+    }
+
+    /// Spawn a task, counted as in flight until it completes.
+    pub fn spawn<F>(&'static self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let guard = self.guard();
+        tokio::spawn(async move {
+            let _guard = guard;
+            future.await
+        })
+    }
+
+    /// Run a blocking function on the blocking thread pool, counted as in
+    /// flight until it returns.
+    pub fn spawn_blocking<F, R>(&'static self, f: F) -> JoinHandle<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let guard = self.guard();
+        tokio::task::spawn_blocking(move || {
+            let _guard = guard;
+            f()
+        })
+    }
+
+    /// Returns the number of tasks and calls in flight.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Start draining, the server is no longer ready for new tasks.
+    pub fn drain(&self) {
+        let _ = self.draining.set(Instant::now());
+    }
+
+    /// Whether the server is draining.
+    pub fn draining(&self) -> bool {
+        self.draining.get().is_some()
+    }
+
+    /// Returns when draining started, if it did.
+    pub fn drain_started(&self) -> Option<Instant> {
+        self.draining.get().copied()
+    }
+
+    /// Wait until nothing is in flight, at most the timeout. Returns whether
+    /// everything completed.
+    pub async fn wait(&self, timeout: Duration) -> bool {
+        let idle = async {
+            loop {
+                let notified = self.idle.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                if self.in_flight() == 0 {
+                    return;
+                }
+                notified.await;
+            }
+        };
+        tokio::time::timeout(timeout, idle).await.is_ok()
+    }
+}
@@ -0,0 +1,141 @@
+use super::{metrics::metrics, random::sample};
+use crate::{
+    onnx::{decode_base64, Prediction, Predictor, PredictorOptions},
+    Result,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// How a candidate model is evaluated.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExperimentMode {
+    /// The current model answers, the candidate runs on the same images in the
+    /// background and disagreements are logged
+    #[default]
+    Shadow,
+    /// A `weight` fraction of the tasks is answered by the candidate
+    Split,
+}
+
+/// Evaluation of a candidate model for a game variant.
+///
+/// The candidate is the game variant's model spec with the predictor overrides
+/// applied, e.g. `{ "model": "3d_rollball_objects_v2.onnx" }`. Tasks matching
+/// one of the variant's routes are left out of the experiment.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Experiment {
+    /// Evaluation mode
+    #[serde(default)]
+    pub mode: ExperimentMode,
+    /// Fraction of the tasks answered by the candidate in split mode, 0 to 1
+    #[serde(default)]
+    pub weight: f64,
+    /// Directory the images of shadow disagreements are saved to
+    #[serde(default)]
+    pub save_dir: Option<PathBuf>,
+    /// Candidate predictor overrides
+    #[serde(flatten)]
+    pub candidate: PredictorOptions,
+}
+
+impl Experiment {
+    /// Whether the candidate answers the next task.
+    pub fn split(&self) -> bool {
+        self.mode == ExperimentMode::Split && sample() < self.weight
+    }
+}
+
+/// Run the candidate on the task's images and compare its answers with the
+/// current predictions. Disagreements are logged with both score vectors and
+/// the images optionally saved. Runs on a blocking thread.
+pub fn shadow(
+    game_variant: String,
+    save_dir: Option<PathBuf>,
+    candidate: Arc<dyn Predictor>,
+    images: Vec<Arc<String>>,
+    predictions: Vec<Option<Prediction>>,
+) {
+    let version = candidate.version();
+    for (index, (image, current)) in images.iter().zip(predictions).enumerate() {
+        let Some(current) = current else {
+            continue;
+        };
+
+        let shadow = match candidate.prediction_base64(image) {
+            Ok(shadow) => shadow,
+            Err(err) => {
+                tracing::warn!("Shadow model {} failed: {}", version, err);
+                metrics().inc(
+                    "fs_prediction_errors_total",
+                    &[("variant", &game_variant), ("version", &version)],
+                );
+                continue;
+            }
+        };
+
+        metrics().inc(
+            "fs_shadow_comparisons_total",
+            &[("variant", &game_variant), ("version", &version)],
+        );
+        if shadow.answer == current.answer {
+            continue;
+        }
+
+        metrics().inc(
+            "fs_shadow_disagreements_total",
+            &[("variant", &game_variant), ("version", &version)],
+        );
+        tracing::warn!(
+            "Shadow disagreement on {}: current {} {:?}, candidate {} {} {:?}",
+            game_variant,
+            current.answer,
+            current.scores,
+            version,
+            shadow.answer,
+            shadow.scores
+        );
+
+        if let Some(ref dir) = save_dir {
+            if let Err(err) = save(dir, &game_variant, index, image, &current, &shadow) {
+                tracing::warn!("Failed to save shadow disagreement: {}", err);
+            }
+        }
+    }
+}
+
+/// Save the image and both predictions, e.g. `3d_rollball_objects_1718000000000_0.jpg`
+/// along with `3d_rollball_objects_1718000000000_0.json`.
+fn save(
+    dir: &Path,
+    game_variant: &str,
+    index: usize,
+    image: &str,
+    current: &Prediction,
+    shadow: &Prediction,
+) -> Result<()> {
+    let bytes = decode_base64(image)?;
+    let extension = image::guess_format(&bytes)?
+        .extensions_str()
+        .first()
+        .copied()
+        .unwrap_or("jpg");
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let name = format!("{game_variant}_{millis}_{index}");
+
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(&name).with_extension(extension), bytes)?;
+    std::fs::write(
+        dir.join(name).with_extension("json"),
+        serde_json::to_vec_pretty(&json!({ "current": current, "candidate": shadow }))?,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,563 @@
+use super::{metrics::metrics, random::sample, spend::Spend, Solver, Task, TaskResult};
+use crate::{error::Error, Result};
+use axum::Json;
+use futures_util::{StreamExt, TryStreamExt};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{ops::Deref, str::FromStr, sync::Arc, time::Duration};
+use tracing::Instrument;
+use typed_builder::TypedBuilder;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TypedFallback {
+    Yescaptcha,
+    Capsolver,
+}
+
+impl TypedFallback {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Yescaptcha => "yescaptcha",
+            Self::Capsolver => "capsolver",
+        }
+    }
+}
+
+impl FromStr for TypedFallback {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "yescaptcha" => Ok(Self::Yescaptcha),
+            "capsolver" => Ok(Self::Capsolver),
+            _ => Err(Error::InvalidSolverType(s.to_string())),
+        }
+    }
+}
+
+/// Fallback provider configuration.
+#[derive(Deserialize, Clone, Debug)]
+pub struct FallbackConfig {
+    /// Provider type
+    pub provider: TypedFallback,
+    /// Provider name, the provider type if unset
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Client key
+    pub key: String,
+    /// Endpoint, the provider's default if unset
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// Balance endpoint, derived from the endpoint if unset
+    #[serde(default)]
+    pub balance_endpoint: Option<String>,
+    /// Game variants the provider is used for, all if unset
+    #[serde(default)]
+    pub variants: Option<Vec<String>>,
+    /// Maximum number of images per submitted task
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    /// Request timeout in seconds
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// Connect timeout in seconds
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+    /// Retries of a submission failing with a connection error, a timeout or a
+    /// 5xx/429 response
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Base retry delay in milliseconds, doubled on every retry and jittered
+    #[serde(default = "default_backoff")]
+    pub backoff: u64,
+    /// Maximum number of submissions of one task in flight at once
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    /// Cost per image, used to order the providers cheapest first
+    #[serde(default)]
+    pub cost: f64,
+}
+
+fn default_limit() -> usize {
+    1
+}
+
+fn default_connect_timeout() -> u64 {
+    10
+}
+
+fn default_retries() -> u32 {
+    2
+}
+
+fn default_backoff() -> u64 {
+    500
+}
+
+fn default_concurrency() -> usize {
+    4
+}
+
+/// Whether the provider may bill a submission: it answered, whatever the
+/// answers, or the request timed out after reaching it.
+fn billable(result: &Result<Vec<i32>>) -> bool {
+    match result {
+        Ok(_) => true,
+        Err(Error::ReqwestError(err)) => err.is_timeout(),
+        Err(_) => false,
+    }
+}
+
+/// Whether a failed submission is worth retrying: connection errors, timeouts,
+/// server errors and rate limiting. Errors reported by the provider are not.
+pub fn retryable(err: &Error) -> bool {
+    match err {
+        Error::ReqwestError(err) => {
+            err.is_connect()
+                || err.is_timeout()
+                || err.status().is_some_and(|status| {
+                    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Returns the delay before the retry following the attempt: the base delay
+/// doubled per attempt, capped at 30s, with up to ±50% jitter.
+pub fn backoff(base: Duration, attempt: u32) -> Duration {
+    let delay = base
+        .saturating_mul(1 << attempt.min(16))
+        .min(Duration::from_secs(30));
+    delay.mul_f64(0.5 + sample())
+}
+
+/// The order the fallback providers are tried in.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FallbackOrder {
+    /// In configuration order
+    #[default]
+    Ordered,
+    /// Cheapest first by configured cost, configuration order among equal costs
+    Cheapest,
+}
+
+/// Validate the answers of a provider against the task's images: one answer per
+/// image, each within the image's candidate count where it is known.
+pub fn validate(provider: &str, answers: &[i32], candidates: &[Option<u32>]) -> Result<()> {
+    let invalid = |reason| Err(Error::InvalidFallbackResponse(provider.to_owned(), reason));
+    if answers.len() != candidates.len() {
+        return invalid(format!(
+            "expected {} answers, found {}",
+            candidates.len(),
+            answers.len()
+        ));
+    }
+
+    for (index, (&answer, candidates)) in answers.iter().zip(candidates).enumerate() {
+        let in_range = match candidates {
+            Some(candidates) => u32::try_from(answer).is_ok_and(|answer| answer < *candidates),
+            None => answer >= 0,
+        };
+        if !in_range {
+            return invalid(format!("answer {answer} of image {index} out of range"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the fallback providers allowed for the game variant, in the order
+/// they are tried.
+pub fn chain<'a, S>(solvers: &'a [S], game_variant: &str, order: FallbackOrder) -> Vec<&'a S>
+where
+    S: Deref<Target = FallbackSolver>,
+{
+    let mut chain = solvers
+        .iter()
+        .filter(|solver| solver.allows(game_variant))
+        .collect::<Vec<_>>();
+    if order == FallbackOrder::Cheapest {
+        // Stable, equal costs keep the configuration order
+        chain.sort_by(|a, b| a.cost().total_cmp(&b.cost()));
+    }
+    chain
+}
+
+/// `FallbackSolver` is a struct that encapsulates the logic for handling tasks using a fallback solver.
+///
+/// It contains a `TypedFallback` instance, which holds the configuration for the fallback solver,
+/// a `reqwest::Client` instance for making HTTP requests, a client key for authentication,
+/// an optional endpoint URL, and a limit for the number of tasks that can be processed.
+///
+/// # Fields
+/// * `typed`: The fallback solver configuration.
+/// * `client`: The HTTP client used to make requests to the fallback solver.
+/// * `client_key`: The client key used for authentication with the fallback solver.
+/// * `endpoint`: The endpoint URL of the fallback solver. If not provided, a default endpoint is used based on the `SolverType`.
+/// * `balance_endpoint`: The balance endpoint URL. If not provided, it is derived from the endpoint.
+/// * `limit`: The maximum number of tasks that can be processed by the fallback solver.
+/// * `name`: The provider name, used in logs, metrics and circuit breakers.
+/// * `variants`: The game variants the provider is used for, all if unset.
+/// * `timeout`: The request timeout.
+/// * `retries`: The retries of a submission failing with a retryable error.
+/// * `backoff`: The base retry delay.
+/// * `concurrency`: The maximum number of submissions of one task in flight at once.
+/// * `cost`: The cost per image.
+/// * `spend`: The spend every billable submission is recorded in.
+#[derive(TypedBuilder)]
+pub struct FallbackSolver {
+    typed: TypedFallback,
+    client: reqwest::Client,
+    client_key: String,
+    endpoint: Option<String>,
+    #[builder(default)]
+    balance_endpoint: Option<String>,
+    limit: usize,
+    #[builder(default = typed.as_str().to_owned())]
+    name: String,
+    #[builder(default)]
+    variants: Option<Vec<String>>,
+    #[builder(default)]
+    timeout: Option<Duration>,
+    #[builder(default = default_retries())]
+    retries: u32,
+    #[builder(default = Duration::from_millis(default_backoff()))]
+    backoff: Duration,
+    #[builder(default = default_concurrency())]
+    concurrency: usize,
+    #[builder(default)]
+    cost: f64,
+    #[builder(default)]
+    spend: Arc<Spend>,
+}
+
+impl FallbackSolver {
+    /// Create the fallback solver of a configured provider with its own HTTP
+    /// client, applying the connect timeout, recording its submissions in the spend.
+    pub fn from_config(config: FallbackConfig, spend: Arc<Spend>) -> Result<FallbackSolver> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(config.connect_timeout))
+            .build()?;
+        Ok(FallbackSolver::builder()
+            .name(
+                config
+                    .name
+                    .unwrap_or_else(|| config.provider.as_str().to_owned()),
+            )
+            .typed(config.provider)
+            .client(client)
+            .client_key(config.key)
+            .endpoint(config.endpoint)
+            .balance_endpoint(config.balance_endpoint)
+            .limit(config.limit)
+            .variants(config.variants)
+            .timeout(config.timeout.map(Duration::from_secs))
+            .retries(config.retries)
+            .backoff(Duration::from_millis(config.backoff))
+            .concurrency(config.concurrency)
+            .cost(config.cost)
+            .spend(spend)
+            .build())
+    }
+
+    /// Returns the provider name, e.g. "capsolver".
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the cost per image.
+    pub fn cost(&self) -> f64 {
+        self.cost
+    }
+
+    /// Returns the number of tasks the images are submitted in.
+    pub fn submissions(&self, images: usize) -> usize {
+        match self.typed {
+            TypedFallback::Yescaptcha => images,
+            TypedFallback::Capsolver => images.div_ceil(self.limit.max(1)),
+        }
+    }
+
+    /// Query the provider's balance.
+    ///
+    /// The balance endpoint defaults to the `getBalance` method next to the
+    /// configured `createTask` endpoint, or the provider's default.
+    pub async fn balance(&self) -> Result<f64> {
+        let endpoint = match (&self.balance_endpoint, &self.endpoint) {
+            (Some(endpoint), _) => endpoint.clone(),
+            (None, Some(endpoint)) => match endpoint.strip_suffix("createTask") {
+                Some(base) => format!("{base}getBalance"),
+                None => endpoint.clone(),
+            },
+            (None, None) => match self.typed {
+                TypedFallback::Yescaptcha => "https://api.yescaptcha.com/getBalance".to_owned(),
+                TypedFallback::Capsolver => "https://api.capsolver.com/getBalance".to_owned(),
+            },
+        };
+
+        let mut request = self
+            .client
+            .post(endpoint)
+            .json(&json!({ "clientKey": self.client_key }));
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        let resp = request
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<BalanceResp>()
+            .await?;
+        if let Some(error_description) = resp.error_description {
+            return Err(Error::FallbackSolverError(error_description));
+        }
+
+        Ok(resp.balance)
+    }
+
+    /// Whether the provider is used for the game variant.
+    pub fn allows(&self, game_variant: &str) -> bool {
+        self.variants
+            .as_ref()
+            .is_none_or(|variants| variants.iter().any(|v| v == game_variant))
+    }
+
+    /// This method is responsible for submitting a task to the solver.
+    ///
+    /// It takes a `SubmitTask` object as an argument, which contains the
+    /// details of the task to be submitted. Depending on the `SolverType`,
+    /// it prepares the endpoint URL and the request body. For `SolverType::Yescaptcha`, it uses the endpoint "https://api.yescaptcha.com/createTask" by default.
+    /// For `SolverType::Capsolver`, it uses the endpoint "https://api.capsolver.com/createTask" by default.
+    /// The request body is a JSON object that includes the client key, task
+    /// details (type, image(s), and question), and either a softID or an appId.
+    /// After preparing the endpoint and the request body, it sends a request to
+    /// the solver.
+    ///
+    /// This method is asynchronous and returns a `Result<Vec<i32>>`.
+    /// If the task is successfully submitted and solved, it returns a `Result`
+    /// wrapping a vector of integers. If there is an error during the
+    /// process, it returns a `Result` wrapping an error.
+    async fn submit_task(&self, submit_task: SubmitTask<'_>) -> Result<Vec<i32>> {
+        let (endpoint, body) = match self.typed {
+            TypedFallback::Yescaptcha => (
+                self.endpoint
+                    .as_deref()
+                    .unwrap_or("https://api.yescaptcha.com/createTask"),
+                json!({
+                    "clientKey": self.client_key,
+                    "task": {
+                        "type": "FunCaptchaClassification",
+                        "image": submit_task.image,
+                        "question": &submit_task.game_variant_instructions.1,
+                    },
+                    "softID": "26299"
+                }),
+            ),
+            TypedFallback::Capsolver => (
+                self.endpoint
+                    .as_deref()
+                    .unwrap_or("https://api.capsolver.com/createTask"),
+                json!({
+                    "clientKey": self.client_key,
+                    "task": {
+                        "type": "FunCaptchaClassification",
+                        "images": submit_task.images,
+                        "question": submit_task.game_variant_instructions.0
+                    },
+                    "appId": "60632CB0-8BE8-41D3-808F-60CC2442F16E"
+                }),
+            ),
+        };
+
+        // Send request
+        let mut request = self.client.post(endpoint).json(&body);
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        let resp = request.send().await?.error_for_status()?;
+
+        // Task response
+        let task = resp.json::<TaskResp0>().await?;
+        // If error
+        if let Some(error_description) = task.error_description {
+            return Err(Error::FallbackSolverError(error_description));
+        }
+
+        Ok(task.solution.objects)
+    }
+
+    /// Submit the task and retry retryable errors with a jittered exponential backoff.
+    ///
+    /// Every billable attempt is recorded in the spend, including answers that
+    /// turn out to be invalid and timed out attempts that are retried.
+    async fn submit_with_retry(&self, submit_task: SubmitTask<'_>) -> Result<Vec<i32>> {
+        let mut attempt = 0;
+        loop {
+            let span = tracing::info_span!(
+                "fallback",
+                provider = %self.name,
+                variant = submit_task.game_variant_instructions.0,
+                images = submit_task.images.as_ref().map_or(1, Vec::len),
+                attempt,
+                outcome = tracing::field::Empty,
+            );
+            let result = self
+                .submit_task(submit_task.clone())
+                .instrument(span.clone())
+                .await;
+            span.record("outcome", super::otlp::outcome(&result));
+            if billable(&result) {
+                let images = submit_task.images.as_ref().map_or(1, Vec::len);
+                self.spend.record(&self.name, images, 1, self.cost);
+            }
+            match result {
+                Err(err) if attempt < self.retries && retryable(&err) => {
+                    let delay = backoff(self.backoff, attempt);
+                    tracing::warn!(
+                        "Fallback {} attempt {} failed: {}, retrying in {:?}",
+                        self.name,
+                        attempt + 1,
+                        err,
+                        delay
+                    );
+                    metrics().inc("fs_fallback_retries_total", &[("provider", &self.name)]);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Submit the task's images and return the answers.
+    ///
+    /// Yescaptcha takes one image per submission, Capsolver up to `limit`. The
+    /// submissions run concurrently, at most `concurrency` at once, and the
+    /// answers keep the order of the images. A submission answered with the
+    /// wrong number of answers fails the task. Dropping the returned future, as
+    /// the server does when the client of `/task` disconnects, aborts the
+    /// submissions in flight and submits no more.
+    pub async fn solve(&self, task: &Task) -> Result<Vec<i32>> {
+        // Get game variant and instructions
+        let (game_variant, instructions) = &task.game_variant_instructions;
+
+        let chunk_size = match self.typed {
+            TypedFallback::Yescaptcha => 1,
+            TypedFallback::Capsolver => self.limit.max(1),
+        };
+
+        // The submissions are lazy, they start once polled by the stream
+        let mut submissions = Vec::new();
+        for chunk in task.images.chunks(chunk_size) {
+            let builder =
+                SubmitTask::builder().game_variant_instructions((game_variant, instructions));
+            let submit_task = match self.typed {
+                // Single image
+                TypedFallback::Yescaptcha => builder.image(chunk[0].deref()).build(),
+                // Multiple images
+                TypedFallback::Capsolver => builder
+                    .images(chunk.iter().map(|s| s.deref()).collect::<Vec<_>>())
+                    .build(),
+            };
+            let expected = chunk.len();
+            submissions.push(async move {
+                let answers = self.submit_with_retry(submit_task).await?;
+                if answers.len() != expected {
+                    return Err(Error::InvalidFallbackResponse(
+                        self.name.clone(),
+                        format!("expected {expected} answers, found {}", answers.len()),
+                    ));
+                }
+                Ok(answers)
+            });
+        }
+
+        let mut guard = Cancellation::new(&self.name);
+        let answers = futures_util::stream::iter(submissions)
+            .buffered(self.concurrency.max(1))
+            .try_collect::<Vec<_>>()
+            .await;
+        guard.done = true;
+
+        Ok(answers?.into_iter().flatten().collect())
+    }
+}
+
+/// Counts the submissions of a task as cancelled if its future is dropped
+/// before they complete.
+struct Cancellation<'a> {
+    provider: &'a str,
+    done: bool,
+}
+
+impl<'a> Cancellation<'a> {
+    fn new(provider: &'a str) -> Self {
+        Self {
+            provider,
+            done: false,
+        }
+    }
+}
+
+impl Drop for Cancellation<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            tracing::info!("Fallback {} cancelled", self.provider);
+            metrics().inc(
+                "fs_fallback_cancelled_total",
+                &[("provider", self.provider)],
+            );
+        }
+    }
+}
+
+impl Solver for FallbackSolver {
+    async fn process(&self, task: &Task) -> Result<Json<TaskResult>> {
+        let answers = self.solve(task).await?;
+        Ok(Json(
+            TaskResult::builder().solved(true).objects(answers).build(),
+        ))
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TaskResp0 {
+    #[serde(rename = "errorId")]
+    error_id: i32,
+    #[serde(rename = "errorCode")]
+    error_code: String,
+    #[serde(rename = "errorDescription")]
+    error_description: Option<String>,
+    status: String,
+    solution: SolutionResp,
+    #[serde(rename = "taskId")]
+    task_id: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct BalanceResp {
+    #[serde(rename = "errorDescription")]
+    error_description: Option<String>,
+    balance: f64,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct SolutionResp {
+    objects: Vec<i32>,
+}
+
+#[derive(typed_builder::TypedBuilder, Clone)]
+pub struct SubmitTask<'a> {
+    #[builder(default, setter(strip_option))]
+    pub image: Option<&'a String>,
+    #[builder(default, setter(strip_option))]
+    pub images: Option<Vec<&'a String>>,
+    pub game_variant_instructions: (&'a str, &'a str),
+}
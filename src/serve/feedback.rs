@@ -0,0 +1,261 @@
+use super::{metrics::metrics, random::id};
+use crate::{error::Error, onnx::Prediction, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+/// Feedback configuration.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct FeedbackConfig {
+    /// Maximum number of recent tasks feedback can be reported for
+    pub capacity: usize,
+    /// Seconds a task accepts feedback
+    pub ttl: u64,
+    /// Number of recent local answers the rolling accuracy is computed over
+    pub window: usize,
+    /// Disable the variant's local model, forcing the fallback solver, when its
+    /// rolling accuracy over a full window drops below this threshold
+    pub min_accuracy: Option<f32>,
+    /// Seconds a disabled local model stays disabled
+    pub cooldown: u64,
+}
+
+impl Default for FeedbackConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1000,
+            ttl: 600,
+            window: 100,
+            min_accuracy: None,
+            cooldown: 600,
+        }
+    }
+}
+
+/// Feedback request
+#[derive(Deserialize)]
+pub struct FeedbackRequest {
+    /// API key
+    pub api_key: Option<String>,
+    /// Whether the answers were correct
+    pub correct: bool,
+    /// The true labels, one per image
+    pub labels: Option<Vec<i32>>,
+}
+
+/// A solved task awaiting feedback.
+#[derive(Clone, Debug)]
+pub struct TaskRecord {
+    /// Game variant and instructions of the task
+    pub game_variant_instructions: (String, String),
+    /// Base64 images, kept only if labelled images are saved
+    pub images: Vec<Arc<String>>,
+    /// The answers
+    pub answers: Vec<i32>,
+    /// The local predictions, empty if the fallback solver answered
+    pub predictions: Vec<Option<Prediction>>,
+    /// Whether the local models answered
+    pub local: bool,
+}
+
+/// Recent tasks in insertion order.
+#[derive(Default)]
+struct TaskLog {
+    records: HashMap<String, (Instant, TaskRecord)>,
+    order: VecDeque<String>,
+}
+
+/// Rolling accuracy of a game variant's local answers.
+#[derive(Default)]
+struct Accuracy {
+    window: VecDeque<bool>,
+    disabled_until: Option<Instant>,
+}
+
+/// Accuracy status of a game variant
+#[derive(Serialize)]
+pub struct AccuracyStatus {
+    /// Game variant, e.g. "3d_rollball_objects"
+    pub game_variant: String,
+    /// Rolling accuracy of the local answers
+    pub accuracy: f32,
+    /// Number of answers the accuracy is computed over
+    pub samples: usize,
+    /// Whether the local model is disabled
+    pub disabled: bool,
+}
+
+/// Recent tasks and the per-variant accuracy reported through the feedback endpoint.
+#[derive(Default)]
+pub struct Feedback {
+    config: RwLock<FeedbackConfig>,
+    tasks: Mutex<TaskLog>,
+    accuracy: Mutex<HashMap<String, Accuracy>>,
+}
+
+impl Feedback {
+    pub fn new(config: FeedbackConfig) -> Feedback {
+        Feedback {
+            config: RwLock::new(config),
+            ..Default::default()
+        }
+    }
+
+    fn config(&self) -> FeedbackConfig {
+        self.config
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// Apply a reloaded configuration, the recent tasks and the accuracy are
+    /// kept.
+    pub fn configure(&self, config: FeedbackConfig) {
+        *self.config.write().unwrap_or_else(|err| err.into_inner()) = config;
+    }
+
+    /// Record a solved task and return its id.
+    pub fn record(&self, record: TaskRecord) -> String {
+        let id = id();
+        let capacity = self.config().capacity;
+        if capacity == 0 {
+            return id;
+        }
+
+        let mut tasks = self.tasks.lock().unwrap_or_else(|err| err.into_inner());
+        let TaskLog { records, order } = &mut *tasks;
+        while records.len() >= capacity {
+            match order.pop_front() {
+                Some(oldest) => records.remove(&oldest),
+                None => break,
+            };
+        }
+        records.insert(id.clone(), (Instant::now(), record));
+        order.push_back(id.clone());
+        id
+    }
+
+    /// Apply the feedback of a task, update the accuracy counters and return
+    /// the task with the true labels, one per image. A task accepts feedback once.
+    pub fn report(&self, id: &str, request: &FeedbackRequest) -> Result<(TaskRecord, Vec<i32>)> {
+        let ttl = Duration::from_secs(self.config().ttl);
+        let record = {
+            let mut tasks = self.tasks.lock().unwrap_or_else(|err| err.into_inner());
+            let TaskLog { records, order } = &mut *tasks;
+            let expired = |created: &Instant| created.elapsed() > ttl;
+            match records.get(id) {
+                Some((created, _)) if expired(created) => (),
+                Some((_, record)) => match request.labels {
+                    Some(ref labels) if labels.len() != record.answers.len() => {
+                        return Err(Error::InvalidFeedback(format!(
+                            "expected {} labels, found {}",
+                            record.answers.len(),
+                            labels.len()
+                        )))
+                    }
+                    _ => (),
+                },
+                None => return Err(Error::TaskNotFound(id.to_owned())),
+            }
+
+            order.retain(|task| task != id);
+            match records.remove(id) {
+                Some((created, record)) if !expired(&created) => record,
+                _ => return Err(Error::TaskNotFound(id.to_owned())),
+            }
+        };
+
+        let labels = match request.labels {
+            Some(ref labels) => labels.clone(),
+            None if request.correct => record.answers.clone(),
+            // The true labels are unknown
+            None => vec![],
+        };
+
+        let game_variant = &record.game_variant_instructions.0;
+        let solver = if record.local { "local" } else { "fallback" };
+        for (index, &answer) in record.answers.iter().enumerate() {
+            let correct = labels
+                .get(index)
+                .map_or(request.correct, |&label| label == answer);
+            metrics().inc(
+                "fs_feedback_total",
+                &[
+                    ("variant", game_variant),
+                    ("solver", solver),
+                    ("result", if correct { "correct" } else { "incorrect" }),
+                ],
+            );
+            if record.local {
+                self.update_accuracy(game_variant, correct);
+            }
+        }
+
+        Ok((record, labels))
+    }
+
+    fn update_accuracy(&self, game_variant: &str, correct: bool) {
+        let config = self.config();
+        let mut accuracy = self.accuracy.lock().unwrap_or_else(|err| err.into_inner());
+        let state = accuracy.entry(game_variant.to_owned()).or_default();
+        state.window.push_back(correct);
+        while state.window.len() > config.window.max(1) {
+            state.window.pop_front();
+        }
+
+        let rate = rate(&state.window);
+        metrics().set("fs_accuracy", &[("variant", game_variant)], rate as f64);
+
+        let Some(min_accuracy) = config.min_accuracy else {
+            return;
+        };
+        if state.window.len() >= config.window.max(1) && rate < min_accuracy {
+            tracing::warn!(
+                "Accuracy of {} dropped to {:.2}, disabling the local model for {}s",
+                game_variant,
+                rate,
+                config.cooldown
+            );
+            state.window.clear();
+            state.disabled_until = Some(Instant::now() + Duration::from_secs(config.cooldown));
+        }
+    }
+
+    /// Whether the game variant's local model is disabled.
+    pub fn disabled(&self, game_variant: &str) -> bool {
+        let accuracy = self.accuracy.lock().unwrap_or_else(|err| err.into_inner());
+        accuracy
+            .get(game_variant)
+            .and_then(|state| state.disabled_until)
+            .is_some_and(|until| Instant::now() < until)
+    }
+
+    /// Returns the rolling accuracy of the game variants, sorted by game variant.
+    pub fn status(&self) -> Vec<AccuracyStatus> {
+        let accuracy = self.accuracy.lock().unwrap_or_else(|err| err.into_inner());
+        let now = Instant::now();
+        let mut status: Vec<_> = accuracy
+            .iter()
+            .map(|(game_variant, state)| AccuracyStatus {
+                game_variant: game_variant.clone(),
+                accuracy: rate(&state.window),
+                samples: state.window.len(),
+                disabled: state.disabled_until.is_some_and(|until| now < until),
+            })
+            .collect();
+        status.sort_by(|a, b| a.game_variant.cmp(&b.game_variant));
+        status
+    }
+}
+
+/// The fraction of correct answers, 1 if there are none.
+fn rate(window: &VecDeque<bool>) -> f32 {
+    if window.is_empty() {
+        return 1.0;
+    }
+    window.iter().filter(|&&correct| correct).count() as f32 / window.len() as f32
+}
@@ -0,0 +1,266 @@
+use super::{metrics::metrics, persist};
+use crate::{
+    error::Error,
+    onnx::{decode_base64, distance, normalize, ModelSpec, Prediction},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+/// Tile memory configuration.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MemoryConfig {
+    /// Maximum Hamming distance between the hashes of two tiles considered the same
+    pub threshold: u32,
+    /// Remember predictions whose score of the answer is at least this
+    /// threshold, only answers confirmed through the feedback endpoint if unset
+    pub min_confidence: Option<f32>,
+    /// Maximum number of tile sets per game variant and instructions, the
+    /// oldest are forgotten first
+    pub capacity: usize,
+    /// File the memory is persisted to, kept in memory only if unset
+    pub path: Option<PathBuf>,
+    /// Seconds between saves of the persisted memory
+    pub save_interval: u64,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 4,
+            min_confidence: None,
+            capacity: 10000,
+            path: None,
+            save_interval: 300,
+        }
+    }
+}
+
+/// A remembered tile set, the exported form of the memory.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MemoryEntry {
+    /// Game variant, e.g. "3d_rollball_objects"
+    pub game_variant: String,
+    /// Normalised instructions
+    pub instructions: String,
+    /// Hex-encoded perceptual hashes of the tiles
+    pub hashes: Vec<String>,
+    /// The answer
+    pub answer: i32,
+}
+
+struct Remembered {
+    hashes: Vec<u64>,
+    answer: i32,
+}
+
+type Key = (String, String);
+
+/// Answers of recurring challenge images, matched by the perceptual hashes of
+/// their tiles.
+#[derive(Default)]
+pub struct TileMemory {
+    config: MemoryConfig,
+    sets: RwLock<HashMap<Key, VecDeque<Remembered>>>,
+}
+
+impl TileMemory {
+    /// Create the memory, loading the persisted tile sets if any.
+    pub fn new(config: MemoryConfig) -> TileMemory {
+        let memory = TileMemory {
+            config,
+            ..Default::default()
+        };
+        if let Err(err) = memory.load() {
+            tracing::warn!("Failed to load the tile memory: {}", err);
+        }
+        memory
+    }
+
+    #[inline]
+    pub fn config(&self) -> &MemoryConfig {
+        &self.config
+    }
+
+    /// Whether the prediction is confident enough to be remembered.
+    pub fn confident(&self, prediction: &Prediction) -> bool {
+        self.config
+            .min_confidence
+            .is_some_and(|threshold| prediction.confidence() >= threshold)
+    }
+
+    /// Returns the remembered answer of the closest matching tile set, if any.
+    pub fn lookup(&self, game_variant: &str, instructions: &str, hashes: &[u64]) -> Option<i32> {
+        let sets = self.sets.read().unwrap_or_else(|err| err.into_inner());
+        let answer = sets
+            .get(&key(game_variant, instructions))?
+            .iter()
+            .filter_map(|set| Some((self.distance(&set.hashes, hashes)?, set.answer)))
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, answer)| answer);
+
+        if answer.is_some() {
+            metrics().inc("fs_memory_hits_total", &[("variant", game_variant)]);
+        }
+        answer
+    }
+
+    /// Remember the answer of the tile set, replacing the answer of a matching set.
+    pub fn remember(&self, game_variant: &str, instructions: &str, hashes: Vec<u64>, answer: i32) {
+        if self.config.capacity == 0 || hashes.is_empty() {
+            return;
+        }
+
+        let mut sets = self.sets.write().unwrap_or_else(|err| err.into_inner());
+        let remembered = sets.entry(key(game_variant, instructions)).or_default();
+        remembered.retain(|set| self.distance(&set.hashes, &hashes).is_none());
+        while remembered.len() >= self.config.capacity {
+            remembered.pop_front();
+        }
+        remembered.push_back(Remembered { hashes, answer });
+
+        let entries = sets.values().map(VecDeque::len).sum::<usize>();
+        metrics().set("fs_memory_entries", &[], entries as f64);
+    }
+
+    /// Forget the tile sets matching the hashes.
+    pub fn forget(&self, game_variant: &str, instructions: &str, hashes: &[u64]) {
+        let mut sets = self.sets.write().unwrap_or_else(|err| err.into_inner());
+        if let Some(remembered) = sets.get_mut(&key(game_variant, instructions)) {
+            remembered.retain(|set| self.distance(&set.hashes, hashes).is_none());
+        }
+    }
+
+    /// Returns the total distance of two tile sets if every tile is within the
+    /// threshold of its counterpart.
+    fn distance(&self, a: &[u64], b: &[u64]) -> Option<u32> {
+        if a.len() != b.len() {
+            return None;
+        }
+        a.iter().zip(b).try_fold(0, |total, (&a, &b)| {
+            let distance = distance(a, b);
+            (distance <= self.config.threshold).then_some(total + distance)
+        })
+    }
+
+    /// Apply the true labels of a task's images reported through the feedback
+    /// endpoint: the tile sets are remembered with their labels, or forgotten
+    /// if the answer was wrong and the label is unknown.
+    pub fn confirm(
+        &self,
+        spec: &ModelSpec,
+        game_variant_instructions: &(String, String),
+        images: &[Arc<String>],
+        labels: &[i32],
+        correct: bool,
+    ) {
+        let (game_variant, instructions) = game_variant_instructions;
+        for (index, image) in images.iter().enumerate() {
+            let Some(hashes) = tile_hashes(spec, image) else {
+                continue;
+            };
+            match labels.get(index) {
+                Some(&label) => self.remember(game_variant, instructions, hashes, label),
+                None if !correct => self.forget(game_variant, instructions, &hashes),
+                None => (),
+            }
+        }
+    }
+
+    /// Export the remembered tile sets, oldest first.
+    pub fn export(&self) -> Vec<MemoryEntry> {
+        let sets = self.sets.read().unwrap_or_else(|err| err.into_inner());
+        let mut entries = sets
+            .iter()
+            .flat_map(|((game_variant, instructions), remembered)| {
+                remembered.iter().map(|set| MemoryEntry {
+                    game_variant: game_variant.clone(),
+                    instructions: instructions.clone(),
+                    hashes: set
+                        .hashes
+                        .iter()
+                        .map(|hash| format!("{hash:016x}"))
+                        .collect(),
+                    answer: set.answer,
+                })
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| {
+            (&a.game_variant, &a.instructions).cmp(&(&b.game_variant, &b.instructions))
+        });
+        entries
+    }
+
+    /// Import exported tile sets, merged into the memory.
+    pub fn import(&self, entries: Vec<MemoryEntry>) -> Result<usize> {
+        let parsed = entries
+            .into_iter()
+            .map(|entry| {
+                let hashes = entry
+                    .hashes
+                    .iter()
+                    .map(|hash| u64::from_str_radix(hash, 16))
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(|err| {
+                        Error::InvalidMemory(format!("{}: {}", entry.game_variant, err))
+                    })?;
+                Ok((entry, hashes))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let count = parsed.len();
+        for (entry, hashes) in parsed {
+            self.remember(
+                &entry.game_variant,
+                &entry.instructions,
+                hashes,
+                entry.answer,
+            );
+        }
+        Ok(count)
+    }
+
+    /// Save the tile sets to the configured file.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = self.config.path.as_ref() else {
+            return Ok(());
+        };
+
+        persist::save(path, &self.export())
+    }
+
+    /// Load the tile sets from the configured file, if it exists.
+    fn load(&self) -> Result<()> {
+        let Some(path) = self.config.path.as_ref() else {
+            return Ok(());
+        };
+        if let Some(entries) = persist::load(path)? {
+            self.import(entries)?;
+        }
+        Ok(())
+    }
+
+    /// Save the memory every save interval.
+    pub async fn persist(&self) {
+        if self.config.path.is_none() || self.config.save_interval == 0 {
+            return;
+        }
+        persist::every(self.config.save_interval, "tile memory", || self.save()).await
+    }
+}
+
+/// Returns the tile hashes of the base64 image, `None` if it can't be read or
+/// doesn't fit the layout.
+pub fn tile_hashes(spec: &ModelSpec, image: &str) -> Option<Vec<u64>> {
+    let image = image::load_from_memory(&decode_base64(image).ok()?).ok()?;
+    spec.tile_hashes(&image).ok()
+}
+
+fn key(game_variant: &str, instructions: &str) -> Key {
+    (game_variant.to_owned(), normalize(instructions))
+}
@@ -0,0 +1,129 @@
+//! Process-wide metrics, rendered in the Prometheus text format on `/metrics`.
+
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex};
+
+/// Metric type
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Counter,
+    Gauge,
+}
+
+type Labels = Vec<(String, String)>;
+
+/// Metrics of one name, keyed by their labels.
+type Family = (Kind, BTreeMap<Labels, f64>);
+
+/// A set of labelled counters and gauges.
+#[derive(Default)]
+pub struct Metrics {
+    families: Mutex<BTreeMap<&'static str, Family>>,
+}
+
+static METRICS: Metrics = Metrics {
+    families: Mutex::new(BTreeMap::new()),
+};
+
+/// Returns the process-wide metrics.
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    fn update(
+        &self,
+        name: &'static str,
+        kind: Kind,
+        labels: &[(&str, &str)],
+        f: impl Fn(&mut f64),
+    ) {
+        let labels = labels
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let mut families = self.families.lock().unwrap_or_else(|err| err.into_inner());
+        let (_, family) = families
+            .entry(name)
+            .or_insert_with(|| (kind, BTreeMap::new()));
+        f(family.entry(labels).or_default());
+    }
+
+    /// Increment a counter by one.
+    pub fn inc(&self, name: &'static str, labels: &[(&str, &str)]) {
+        self.add(name, labels, 1.0)
+    }
+
+    /// Increment a counter by the value.
+    pub fn add(&self, name: &'static str, labels: &[(&str, &str)], value: f64) {
+        self.update(name, Kind::Counter, labels, |v| *v += value)
+    }
+
+    /// Set a gauge to the value.
+    pub fn set(&self, name: &'static str, labels: &[(&str, &str)], value: f64) {
+        self.update(name, Kind::Gauge, labels, |v| *v = value)
+    }
+
+    /// Returns the current value of a metric, zero if it was never updated.
+    pub fn get(&self, name: &str, labels: &[(&str, &str)]) -> f64 {
+        let families = self.families.lock().unwrap_or_else(|err| err.into_inner());
+        families
+            .get(name)
+            .and_then(|(_, family)| {
+                family.iter().find_map(|(key, value)| {
+                    let matches = key.len() == labels.len()
+                        && key
+                            .iter()
+                            .zip(labels)
+                            .all(|((k, v), (lk, lv))| k == lk && v == lv);
+                    matches.then_some(*value)
+                })
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the labels and values of the metrics of one name.
+    pub fn family(&self, name: &str) -> Vec<(Vec<(String, String)>, f64)> {
+        let families = self.families.lock().unwrap_or_else(|err| err.into_inner());
+        families
+            .get(name)
+            .map(|(_, family)| {
+                family
+                    .iter()
+                    .map(|(labels, value)| (labels.clone(), *value))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Render the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let families = self.families.lock().unwrap_or_else(|err| err.into_inner());
+        let mut out = String::new();
+        for (name, (kind, family)) in families.iter() {
+            let kind = match kind {
+                Kind::Counter => "counter",
+                Kind::Gauge => "gauge",
+            };
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+            for (labels, value) in family {
+                let labels = labels
+                    .iter()
+                    .map(|(key, value)| {
+                        let value = value
+                            .replace('\\', "\\\\")
+                            .replace('"', "\\\"")
+                            .replace('\n', "\\n");
+                        format!("{key}=\"{value}\"")
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",");
+                if labels.is_empty() {
+                    let _ = writeln!(out, "{name} {value}");
+                } else {
+                    let _ = writeln!(out, "{name}{{{labels}}} {value}");
+                }
+            }
+        }
+        out
+    }
+}
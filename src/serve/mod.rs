@@ -1,126 +1,602 @@
-mod signal;
+#[cfg(target_family = "unix")]
+pub mod admin;
+mod breaker;
+mod cache;
+pub mod drain;
+mod experiment;
+mod fallback;
+mod feedback;
+mod memory;
+pub mod metrics;
+pub mod otlp;
+mod persist;
+mod random;
+pub mod request_id;
+mod sample;
+pub mod signal;
 mod solver;
+mod spend;
+mod status;
+#[cfg(target_family = "unix")]
+pub mod systemd;
 mod task;
 
-use self::solver::FallbackSolver;
+pub use self::breaker::{BreakerConfig, BreakerState, Breakers, CircuitBreaker};
+pub use self::cache::{CacheConfig, ResultCache};
+pub use self::experiment::{Experiment, ExperimentMode};
+pub use self::fallback::{
+    backoff, chain, retryable, validate, FallbackConfig, FallbackOrder, FallbackSolver,
+    TypedFallback,
+};
+pub use self::feedback::{Feedback, FeedbackConfig, FeedbackRequest, TaskRecord};
+pub use self::memory::{tile_hashes, MemoryConfig, MemoryEntry, TileMemory};
+pub use self::sample::{Sample, SampleConfig, SampleReason, SampleStore};
+pub use self::spend::{ProviderSpend, Spend, SpendConfig, SpendStatus};
 pub use self::task::Task;
 use crate::{
+    config::Config,
     error::Error,
     onnx::{Adapter, ONNXConfig},
-    serve::solver::TypedFallback,
-    BootArgs, Result,
+    BootArgs, LogFormat, Result,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use axum_server::{
+    tls_rustls::{RustlsAcceptor, RustlsConfig},
+    Handle,
 };
-use axum::{extract::State, routing::post, Json, Router};
-use axum_server::{tls_rustls::RustlsConfig, Handle};
 use solver::{DefaultSolver, Solver, SolverHelper};
-use std::{str::FromStr, sync::Arc};
+use status::{Status, StatusQuery};
+use std::{
+    io::IsTerminal,
+    net::SocketAddr,
+    str::FromStr,
+    sync::{Arc, OnceLock, RwLock},
+    time::{Duration, Instant},
+};
 pub use task::TaskResult;
-use tower_http::trace::{DefaultMakeSpan, DefaultOnFailure, DefaultOnResponse, TraceLayer};
-use tracing::Level;
+use tokio::sync::Notify;
+use tracing::{Instrument, Level};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
 
 /// Application state
-struct AppState {
+pub struct AppState {
+    // Settings, replaced as a whole on reload
+    settings: RwLock<Arc<Settings>>,
+    // State kept across reloads
+    shared: Shared,
+    // TLS certificates, read again on reload
+    tls: Option<RustlsConfig>,
+    // Boot arguments, the configuration file is read again on reload
+    args: BootArgs,
+    // Held while reloading
+    reloading: tokio::sync::Mutex<()>,
+    // Notified once reloaded
+    reloaded: Notify,
+    // Start time
+    started: Instant,
+    // Address the server listens on
+    listening: OnceLock<SocketAddr>,
+}
+
+/// Settings read from the command line and the configuration file
+pub struct Settings {
     // API key
     api_key: Option<String>,
     // Solver
     solver: SolverHelper,
 }
 
-#[tokio::main]
-pub async fn run(args: BootArgs) -> Result<()> {
+impl Settings {
+    /// Returns the API key tasks must carry, if any.
+    pub fn api_key(&self) -> Option<&str> {
+        self.api_key.as_deref()
+    }
+
+    /// Returns the multiple image submission limit.
+    pub fn limit(&self) -> usize {
+        self.solver.limit()
+    }
+
+    /// Solve the task with the local models, or the fallback providers if they
+    /// fail.
+    pub async fn process(&self, task: &Task) -> Result<Json<TaskResult>> {
+        self.solver.process(task).await
+    }
+}
+
+/// State kept across reloads: the model store, the result cache, the tile
+/// memory, the sample store, the feedback, the circuit breakers and the spend.
+/// The feedback, breaker and spend settings are applied on reload.
+struct Shared {
+    onnx_store: Adapter,
+    cache: Option<Arc<ResultCache>>,
+    memory: Option<Arc<TileMemory>>,
+    samples: Option<Arc<SampleStore>>,
+    feedback: Arc<Feedback>,
+    breakers: Arc<Breakers>,
+    spend: Arc<Spend>,
+    restart: RestartSettings,
+}
+
+/// Settings of the configuration file that take effect on restart only.
+#[derive(PartialEq)]
+struct RestartSettings {
+    samples: Option<SampleConfig>,
+    cache: Option<CacheConfig>,
+    memory: Option<MemoryConfig>,
+}
+
+impl RestartSettings {
+    fn take(config: &mut Config) -> Self {
+        Self {
+            samples: config.samples.take(),
+            cache: config.cache.take(),
+            memory: config.memory.take(),
+        }
+    }
+}
+
+impl AppState {
+    /// Load the configuration file, the persisted cache and memory and the TLS
+    /// certificates, and the models with `--preload`.
+    pub async fn new(args: BootArgs) -> Result<Arc<AppState>> {
+        let mut config = load_config(&args)?;
+        let restart = RestartSettings::take(&mut config);
+        tracing::info!(
+            "Samples: {:?}",
+            restart.samples.as_ref().map(|samples| &samples.dir)
+        );
+
+        // Initialize the model store, the S3 store is shared with the sample store.
+        let onnx_store = Adapter::new(args.store.clone()).await;
+        let samples = restart
+            .samples
+            .clone()
+            .map(|samples| Arc::new(SampleStore::new(samples, onnx_store.s3().cloned())));
+
+        // Initialize the result cache, loading the persisted entries.
+        let cache = restart
+            .cache
+            .clone()
+            .map(|cache| Arc::new(ResultCache::new(cache)));
+        if let Some(cache) = cache.clone() {
+            tracing::info!("Cache: {} entries", cache.len());
+            tokio::spawn(async move { cache.persist().await });
+        }
+
+        // Initialize the tile memory, loading the persisted tile sets.
+        let memory = restart
+            .memory
+            .clone()
+            .map(|memory| Arc::new(TileMemory::new(memory)));
+        if let Some(memory) = memory.clone() {
+            tracing::info!("Memory: {} tile sets", memory.export().len());
+            tokio::spawn(async move { memory.persist().await });
+        }
+
+        let shared = Shared {
+            onnx_store,
+            cache,
+            memory,
+            samples,
+            feedback: Arc::new(Feedback::new(std::mem::take(&mut config.feedback))),
+            breakers: Arc::new(Breakers::new(std::mem::take(&mut config.breaker))),
+            spend: Arc::new(Spend::new(std::mem::take(&mut config.spend))),
+            restart,
+        };
+        let settings = build_settings(&args, config, &shared)?;
+
+        // If TLS certificate and key are provided, use them.
+        let tls = match (&args.tls_cert, &args.tls_key) {
+            (Some(cert), Some(key)) => Some(RustlsConfig::from_pem_file(cert, key).await?),
+            _ => None,
+        };
+
+        // Load the models before the server is reported ready.
+        if args.preload {
+            match settings.solver.preload().await {
+                Ok(loaded) => tracing::info!("Preloaded {} predictors", loaded),
+                Err(_) => {
+                    tracing::warn!("The models that failed to preload load on their next task")
+                }
+            }
+        }
+
+        Ok(Arc::new(AppState {
+            settings: RwLock::new(Arc::new(settings)),
+            shared,
+            tls,
+            args,
+            reloading: tokio::sync::Mutex::new(()),
+            reloaded: Notify::new(),
+            started: Instant::now(),
+            listening: OnceLock::new(),
+        }))
+    }
+
+    /// Returns the current settings, requests keep the settings they started
+    /// with.
+    pub fn settings(&self) -> Arc<Settings> {
+        self.settings
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// Read the configuration file and the TLS certificates again, and apply
+    /// them at once. With `--preload` the models are loaded before. Nothing is
+    /// applied if any of them is invalid or a model fails to load, the running
+    /// settings are kept.
+    pub async fn reload(&self) -> Result<()> {
+        let _reloading = self.reloading.lock().await;
+        let mut config = load_config(&self.args)?;
+        let restart = RestartSettings::take(&mut config);
+        let feedback = std::mem::take(&mut config.feedback);
+        let breaker = std::mem::take(&mut config.breaker);
+        let spend = std::mem::take(&mut config.spend);
+        let settings = build_settings(&self.args, config, &self.shared)?;
+        let tls = match (&self.tls, &self.args.tls_cert, &self.args.tls_key) {
+            (Some(_), Some(cert), Some(key)) => Some(
+                RustlsConfig::from_pem_file(cert, key)
+                    .await
+                    .map_err(|err| Error::InvalidConfig(format!("TLS certificate: {err}")))?,
+            ),
+            _ => None,
+        };
+        if self.args.preload {
+            tracing::info!("Preloaded {} predictors", settings.solver.preload().await?);
+        }
+        if restart != self.shared.restart {
+            tracing::warn!("The samples, cache and memory settings take effect on restart");
+        }
+
+        *self.settings.write().unwrap_or_else(|err| err.into_inner()) = Arc::new(settings);
+        self.shared.feedback.configure(feedback);
+        self.shared.breakers.configure(breaker);
+        self.shared.spend.configure(spend);
+        if let (Some(current), Some(tls)) = (&self.tls, tls) {
+            current.reload_from_config(tls.get_inner());
+        }
+        self.reloaded.notify_waiters();
+        Ok(())
+    }
+}
+
+/// The admin socket bound before the server starts, if any.
+#[cfg(target_family = "unix")]
+type AdminListener = Option<std::os::unix::net::UnixListener>;
+#[cfg(not(target_family = "unix"))]
+type AdminListener = ();
+
+/// Run the server until it is shut down and drained.
+pub fn run(args: BootArgs) -> Result<()> {
+    block_on(serve(args, AdminListener::default()))
+}
+
+/// Run the server on the admin socket bound by the daemon before it dropped
+/// its privileges.
+#[cfg(target_family = "unix")]
+pub fn run_with_admin(args: BootArgs, admin: std::os::unix::net::UnixListener) -> Result<()> {
+    block_on(serve(args, Some(admin)))
+}
+
+fn block_on(server: impl std::future::Future<Output = Result<()>>) -> Result<()> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    let result = runtime.block_on(server);
+    // Don't wait for the blocking calls abandoned at the drain timeout
+    runtime.shutdown_timeout(Duration::from_secs(1));
+    result
+}
+
+#[cfg_attr(not(target_family = "unix"), allow(unused_variables))]
+async fn serve(args: BootArgs, admin: AdminListener) -> Result<()> {
     // Disable the AWS SDK's default region detection.
     std::env::set_var("AWS_REGION", "us-west-2");
 
-    // Initialize the logger.
-    tracing_subscriber::fmt()
-        .with_max_level(if args.debug {
-            Level::DEBUG
-        } else {
-            Level::INFO
-        })
+    // Initialize the logger, RUST_LOG overrides the level.
+    let filter = EnvFilter::builder()
+        .with_default_directive(
+            if args.debug {
+                Level::DEBUG
+            } else {
+                Level::INFO
+            }
+            .into(),
+        )
+        .from_env_lossy();
+    let fmt = match args.log_format {
+        // No color codes in the daemon's log files
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            .with_ansi(std::io::stdout().is_terminal())
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+    };
+
+    // Export the traces to the OTLP collector, if configured.
+    let tracer = args
+        .otlp_endpoint
+        .as_deref()
+        .map(otlp::provider)
+        .transpose()?;
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt)
+        .with(tracer.as_ref().map(otlp::layer))
         .init();
 
     // Print boot arguments.
     tracing::info!("Version: {}", env!("CARGO_PKG_VERSION"));
-    tracing::info!("Limit: {}", args.limit);
     tracing::info!("Model dir: {:?}", args.model_dir);
     tracing::info!("Update check: {}", args.update_check);
     tracing::info!("Threads: {}", args.num_threads);
     tracing::info!("Allocator: {:?}", args.allocator);
+    tracing::info!("Config: {:?}", args.config);
+    tracing::info!("OTLP endpoint: {:?}", args.otlp_endpoint);
 
-    // Initialize the application state.
-    let state = AppState {
-        api_key: args.api_key,
-        solver: SolverHelper::builder()
-            .limit(args.limit)
-            .onnx_solver(
-                DefaultSolver::builder()
-                    .config(
-                        ONNXConfig::builder()
-                            .model_dir(args.model_dir)
-                            .update_check(args.update_check)
-                            .num_threads(args.num_threads)
-                            .allocator(args.allocator)
-                            .onnx_store(Adapter::new(args.store).await)
-                            .build(),
-                    )
-                    .predictors(Default::default())
-                    .build(),
-            )
-            .fallback_solver(match (args.fallback_solver, args.fallback_key) {
-                (Some(solver), Some(key)) => Some(
-                    FallbackSolver::builder()
-                        .typed(TypedFallback::from_str(&solver)?)
-                        .client(reqwest::Client::new())
-                        .client_key(key)
-                        .endpoint(args.fallback_endpoint)
-                        .limit(args.fallback_image_limit)
-                        .build(),
-                ),
-                _ => None,
-            })
-            .build(),
-    };
+    // Load the configuration file and initialize the application state.
+    let state = AppState::new(args.clone()).await?;
+
+    // Spawn a task to poll the fallback providers' balances, the interval and
+    // the providers change on reload.
+    tokio::spawn({
+        let state = state.clone();
+        async move {
+            loop {
+                let interval = state.settings().solver.balance_interval();
+                if interval > 0 {
+                    state.settings().solver.refresh_balances().await;
+                }
+                let wait = async {
+                    match interval {
+                        0 => std::future::pending().await,
+                        _ => tokio::time::sleep(Duration::from_secs(interval)).await,
+                    }
+                };
+                tokio::select! {
+                    _ = wait => {}
+                    _ = state.reloaded.notified() => {}
+                }
+            }
+        }
+    });
 
     // Create the router.
     let route = Router::new()
         .route("/task", post(task))
-        .layer(
-            TraceLayer::new_for_http()
-                .make_span_with(DefaultMakeSpan::default().level(Level::INFO))
-                .on_response(DefaultOnResponse::new().level(Level::INFO))
-                .on_failure(DefaultOnFailure::new().level(Level::WARN)),
-        )
-        .with_state(Arc::new(state));
+        .route("/task/:id/feedback", post(feedback))
+        .route("/status", get(status))
+        .route("/ready", get(ready))
+        .route("/metrics", get(metrics))
+        .route("/memory", get(export_memory).post(import_memory));
+    let route = request_id::traced(route).with_state(state.clone());
 
-    tracing::info!("Listening on {}", args.bind);
+    // Answer `fs ps` on the local admin socket, the server runs without it if
+    // it can't be bound.
+    #[cfg(target_family = "unix")]
+    let admin = admin.or_else(|| {
+        let path = args.admin_socket.as_ref()?;
+        admin::bind(path)
+            .inspect_err(|err| tracing::warn!("Failed to bind the admin socket: {}", err))
+            .ok()
+    });
+    #[cfg(target_family = "unix")]
+    if let (Some(listener), Some(path)) = (admin, &args.admin_socket) {
+        tracing::info!("Admin socket: {}", path.display());
+        let state = state.clone();
+        tokio::spawn(admin::serve(listener, move |command| {
+            let state = state.clone();
+            async move { admin_command(&state, &command).await }
+        }));
+    }
 
     // Signal the server to shut down using Handle.
     let handle = Handle::new();
 
-    // Spawn a task to gracefully shutdown server.
-    tokio::spawn(signal::graceful_shutdown(handle.clone()));
+    // Spawn a task to gracefully shutdown server, SIGHUP reloads the configuration.
+    let drain_grace = Duration::from_secs(args.drain_grace);
+    let drain_timeout = Duration::from_secs(args.drain_timeout);
+    tokio::spawn(signal::graceful_shutdown(
+        handle.clone(),
+        drain_grace,
+        drain_timeout,
+        {
+            let state = state.clone();
+            move || {
+                let state = state.clone();
+                async move {
+                    let _ = reload(&state).await;
+                }
+            }
+        },
+    ));
 
-    // If TLS certificate and key are provided, use them.
-    match (args.tls_cert, args.tls_key) {
-        (Some(cert), Some(key)) => {
-            let config = RustlsConfig::from_pem_file(cert, key).await?;
-            axum_server::bind_rustls(args.bind, config)
-                .handle(handle)
-                .serve(route.into_make_service())
-                .await?;
+    // Report the server ready to systemd once listening, and keep its watchdog fed.
+    tokio::spawn({
+        let handle = handle.clone();
+        let state = state.clone();
+        async move {
+            if let Some(addr) = handle.listening().await {
+                let _ = state.listening.set(addr);
+                #[cfg(target_family = "unix")]
+                systemd::notify_or_log(&format!("READY=1\nSTATUS=Listening on {addr}"));
+            }
+        }
+    });
+    #[cfg(target_family = "unix")]
+    tokio::spawn(systemd::watchdog());
+
+    // Listen on the socket passed by systemd socket activation, if any.
+    #[cfg(target_family = "unix")]
+    let listener = systemd::listener();
+    #[cfg(not(target_family = "unix"))]
+    let listener = None;
+    let server = match listener {
+        Some(listener) => {
+            tracing::info!(
+                "Listening on the activated socket {:?}",
+                listener.local_addr()
+            );
+            axum_server::from_tcp(listener)
         }
-        _ => {
+        None => {
+            tracing::info!("Listening on {}", args.bind);
             axum_server::bind(args.bind)
-                .handle(handle)
+        }
+    }
+    .handle(handle);
+
+    match state.tls.clone() {
+        Some(config) => {
+            server
+                .acceptor(RustlsAcceptor::new(config))
                 .serve(route.into_make_service())
                 .await?;
         }
+        None => server.serve(route.into_make_service()).await?,
+    }
+
+    // Wait for the inference and fallback calls still in flight.
+    let deadline = drain::tracker()
+        .drain_started()
+        .unwrap_or_else(Instant::now)
+        + drain_grace
+        + drain_timeout;
+    let in_flight = drain::tracker().in_flight();
+    if in_flight > 0 {
+        tracing::info!("Waiting for {} tasks in flight", in_flight);
+    }
+    if !drain::tracker()
+        .wait(deadline.saturating_duration_since(Instant::now()))
+        .await
+    {
+        tracing::warn!(
+            "Drain timeout, abandoning {} tasks in flight",
+            drain::tracker().in_flight()
+        );
+    }
+
+    // Save the result cache and the tile memory.
+    if let Some(ref cache) = state.shared.cache {
+        if let Err(err) = cache.save() {
+            tracing::warn!("Failed to save the result cache: {}", err);
+        }
+    }
+    if let Some(ref memory) = state.shared.memory {
+        if let Err(err) = memory.save() {
+            tracing::warn!("Failed to save the tile memory: {}", err);
+        }
+    }
+
+    // Remove the admin socket, the next server binds it again.
+    #[cfg(target_family = "unix")]
+    if let Some(ref path) = args.admin_socket {
+        let _ = std::fs::remove_file(path);
+    }
+
+    // Flush the spans not yet exported.
+    if let Some(tracer) = tracer {
+        let _ = tokio::task::spawn_blocking(move || {
+            if let Err(err) = tracer.shutdown() {
+                tracing::warn!("Failed to flush the traces: {}", err);
+            }
+        })
+        .await;
     }
 
     Ok(())
 }
 
+/// Load the configuration file, the defaults if unset.
+fn load_config(args: &BootArgs) -> Result<Config> {
+    match args.config {
+        Some(ref path) => Config::from_file(path),
+        None => Ok(Config::default()),
+    }
+}
+
+/// Build the settings of the configuration, sharing the state kept across
+/// reloads. The API key and limit of the configuration file override the
+/// command line ones.
+fn build_settings(args: &BootArgs, config: Config, shared: &Shared) -> Result<Settings> {
+    config.validate()?;
+    let limit = config.limit.unwrap_or(args.limit);
+    tracing::info!("Limit: {}", limit);
+    tracing::info!("Models: {}", config.models.keys().count());
+    tracing::info!("Routes: {}", config.routes.len());
+    tracing::info!("Experiments: {}", config.experiments.len());
+
+    // The fallback provider given on the command line is tried first.
+    let client = reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(args.fallback_connect_timeout))
+        .build()?;
+    let mut fallback_solvers = vec![];
+    if let (Some(solver), Some(key)) = (&args.fallback_solver, &args.fallback_key) {
+        fallback_solvers.push(
+            FallbackSolver::builder()
+                .typed(TypedFallback::from_str(solver)?)
+                .client(client)
+                .client_key(key.clone())
+                .endpoint(args.fallback_endpoint.clone())
+                .limit(args.fallback_image_limit)
+                .timeout(args.fallback_timeout.map(Duration::from_secs))
+                .spend(shared.spend.clone())
+                .build(),
+        );
+    }
+    for fallback in config.fallbacks {
+        fallback_solvers.push(FallbackSolver::from_config(fallback, shared.spend.clone())?);
+    }
+    tracing::info!(
+        "Fallbacks: {:?}",
+        fallback_solvers
+            .iter()
+            .map(FallbackSolver::name)
+            .collect::<Vec<_>>()
+    );
+
+    let solver = SolverHelper::builder()
+        .limit(limit)
+        .onnx_solver(
+            DefaultSolver::builder()
+                .config(
+                    ONNXConfig::builder()
+                        .model_dir(args.model_dir.clone())
+                        .update_check(args.update_check)
+                        .num_threads(args.num_threads)
+                        .allocator(args.allocator)
+                        .onnx_store(shared.onnx_store.clone())
+                        .build(),
+                )
+                .registry(config.models)
+                .routes(config.routes)
+                .experiments(config.experiments)
+                .cache(shared.cache.clone())
+                .memory(shared.memory.clone())
+                .build(),
+        )
+        .fallback_solvers(fallback_solvers)
+        .fallback_order(config.fallback_order)
+        .fallback_retry_invalid(config.fallback_retry_invalid)
+        .samples(shared.samples.clone())
+        .feedback(shared.feedback.clone())
+        .breakers(shared.breakers.clone())
+        .spend(shared.spend.clone())
+        .build();
+    Ok(Settings {
+        api_key: config.api_key.or_else(|| args.api_key.clone()),
+        solver,
+    })
+}
+
 /// Handle the task
 /// This function is responsible for handling tasks. It takes the application
 /// state and a task as input. It first validates the task, then tries to
@@ -133,13 +609,234 @@ async fn task(
     Json(task): Json<Task>,
 ) -> Result<Json<TaskResult>> {
     // Check if API key is provided and matches the one in the state
-    match &state.api_key {
-        Some(api_key) if task.api_key.as_deref() != Some(api_key) => {
-            return Err(Error::InvalidApiKey);
+    let settings = state.settings();
+    authorize(&settings, task.api_key.as_deref())?;
+
+    // New tasks are rejected once draining, the tasks in flight complete
+    if drain::tracker().draining() {
+        return Err(Error::ShuttingDown);
+    }
+
+    // Process the solver task in a span tagged with the variant, image count and outcome
+    let span = tracing::info_span!(
+        "task",
+        variant = %task.game_variant_instructions.0,
+        images = task.images.len(),
+        outcome = tracing::field::Empty,
+    );
+    let _work = drain::tracker().guard();
+    let result = settings.process(&task).instrument(span.clone()).await;
+    let outcome = otlp::outcome(&result);
+    span.record("outcome", outcome);
+    // Unregistered variants share a label, clients can't grow the metric
+    let game_variant = &task.game_variant_instructions.0;
+    let variant = match settings.solver.registered(game_variant) {
+        true => game_variant.as_str(),
+        false => "unknown",
+    };
+    metrics::metrics().inc(
+        "fs_tasks_total",
+        &[("variant", variant), ("outcome", outcome)],
+    );
+    result
+}
+
+/// Handle the feedback
+/// This function applies the client's feedback on the answers of a task, it
+/// updates the accuracy counters and saves wrongly answered images if enabled.
+async fn feedback(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(request): Json<FeedbackRequest>,
+) -> Result<StatusCode> {
+    let settings = state.settings();
+    authorize(&settings, request.api_key.as_deref())?;
+    settings.solver.feedback(&id, &request)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Handle the status
+/// This function returns the server version and the loaded predictors along
+/// with the inputs, outputs and metadata of their models.
+async fn status(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<StatusQuery>,
+) -> Result<Json<Status>> {
+    let settings = state.settings();
+    authorize(&settings, query.api_key.as_deref())?;
+
+    let solver = &settings.solver;
+    Ok(Json(Status {
+        version: env!("CARGO_PKG_VERSION"),
+        predictors: solver.status(),
+        accuracy: solver.accuracy(),
+        breakers: solver.breakers(),
+        spend: solver.spend(),
+    }))
+}
+
+/// Handle the readiness probe
+/// This function answers `503` once the server is draining, so that load
+/// balancers stop sending it tasks.
+async fn ready() -> StatusCode {
+    if drain::tracker().draining() {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    }
+}
+
+/// Handle the metrics
+/// This function returns the metrics in the Prometheus text format.
+async fn metrics(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<StatusQuery>,
+) -> Result<String> {
+    authorize(&state.settings(), query.api_key.as_deref())?;
+    Ok(metrics::metrics().render())
+}
+
+/// Handle the tile memory export
+/// This function returns the remembered tile sets and their answers.
+async fn export_memory(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<StatusQuery>,
+) -> Result<Json<Vec<MemoryEntry>>> {
+    authorize(&state.settings(), query.api_key.as_deref())?;
+    let memory = state.shared.memory.as_ref().ok_or(Error::MemoryDisabled)?;
+    Ok(Json(memory.export()))
+}
+
+/// Handle the tile memory import
+/// This function merges exported tile sets into the tile memory.
+async fn import_memory(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<StatusQuery>,
+    Json(entries): Json<Vec<MemoryEntry>>,
+) -> Result<StatusCode> {
+    authorize(&state.settings(), query.api_key.as_deref())?;
+    let memory = state.shared.memory.as_ref().ok_or(Error::MemoryDisabled)?;
+    let count = memory.import(entries)?;
+    tracing::info!("Imported {} tile sets", count);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Answer a command of the admin socket
+#[cfg(target_family = "unix")]
+async fn admin_command(state: &AppState, command: &str) -> Result<String> {
+    match command {
+        "status" => Ok(serde_json::to_string(&admin_status(state))?),
+        "reload" => {
+            reload(state).await?;
+            Ok(serde_json::json!({ "reloaded": true }).to_string())
+        }
+        _ => Err(Error::AdminError(format!(
+            "Unknown admin command {command:?}"
+        ))),
+    }
+}
+
+/// Reload the configuration, logging the outcome
+async fn reload(state: &AppState) -> Result<()> {
+    let result = state.reload().await;
+    match result {
+        Ok(()) => tracing::info!("Reloaded the configuration"),
+        Err(ref err) => tracing::error!("{}, keeping the running configuration", err),
+    }
+    metrics::metrics().inc(
+        "fs_config_reloads_total",
+        &[("outcome", otlp::outcome(&result))],
+    );
+    result
+}
+
+/// Returns the status answered on the admin socket
+#[cfg(target_family = "unix")]
+fn admin_status(state: &AppState) -> admin::AdminStatus {
+    use admin::{FallbackUsage, ModelStatus, VariantRequests};
+    use std::collections::BTreeMap;
+
+    let label = |labels: &[(String, String)], key: &str| {
+        labels
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default()
+    };
+    let metrics = metrics::metrics();
+
+    let models = state
+        .settings()
+        .solver
+        .status()
+        .into_iter()
+        .filter(|predictor| !predictor.candidate)
+        .flat_map(|predictor| {
+            predictor.models.into_iter().map(move |model| ModelStatus {
+                game_variant: predictor.game_variant.clone(),
+                model: model.model,
+                version: model.version,
+                active: predictor.active,
+            })
+        })
+        .collect();
+
+    let mut requests = BTreeMap::<String, VariantRequests>::new();
+    for (labels, value) in metrics.family("fs_tasks_total") {
+        let game_variant = label(&labels, "variant");
+        let entry = requests
+            .entry(game_variant.clone())
+            .or_insert_with(|| VariantRequests {
+                game_variant,
+                ok: 0,
+                error: 0,
+            });
+        match label(&labels, "outcome").as_str() {
+            "ok" => entry.ok += value as u64,
+            _ => entry.error += value as u64,
+        }
+    }
+
+    let mut fallbacks = BTreeMap::<String, FallbackUsage>::new();
+    for (name, family) in [
+        ("tasks", "fs_fallback_tasks_total"),
+        ("images", "fs_fallback_images_total"),
+        ("spend", "fs_fallback_spend_total"),
+    ] {
+        for (labels, value) in metrics.family(family) {
+            let provider = label(&labels, "provider");
+            let usage = fallbacks
+                .entry(provider.clone())
+                .or_insert_with(|| FallbackUsage {
+                    provider,
+                    tasks: 0,
+                    images: 0,
+                    spend: 0.0,
+                });
+            match name {
+                "tasks" => usage.tasks += value as u64,
+                "images" => usage.images += value as u64,
+                _ => usage.spend += value,
+            }
         }
-        _ => (),
     }
 
-    // Process the solver task
-    state.solver.process(&task).await
+    admin::AdminStatus {
+        pid: std::process::id(),
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        uptime: state.started.elapsed().as_secs(),
+        bind: state.listening.get().map(ToString::to_string),
+        in_flight: drain::tracker().in_flight(),
+        models,
+        requests: requests.into_values().collect(),
+        fallbacks: fallbacks.into_values().collect(),
+    }
+}
+
+/// Check if the API key matches the one in the state, if any
+fn authorize(settings: &Settings, api_key: Option<&str>) -> Result<()> {
+    match &settings.api_key {
+        Some(expected) if api_key != Some(expected) => Err(Error::InvalidApiKey),
+        _ => Ok(()),
+    }
 }
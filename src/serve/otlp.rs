@@ -0,0 +1,54 @@
+//! OTLP trace export, the spans of the server are sent to an OpenTelemetry
+//! collector over OTLP/HTTP (protobuf) when an endpoint is configured.
+
+use crate::{error::Error, Result};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    trace::{SdkTracerProvider, Tracer},
+    Resource,
+};
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// The service name the spans are reported under.
+const SERVICE_NAME: &str = "fs";
+
+/// Create the tracer provider exporting spans in batches to the collector.
+///
+/// The endpoint is the collector's base URL, e.g. `http://localhost:4318`, or
+/// its traces URL ending in `/v1/traces`.
+pub fn provider(endpoint: &str) -> Result<SdkTracerProvider> {
+    let endpoint = endpoint.trim_end_matches('/');
+    let endpoint = if endpoint.ends_with("/v1/traces") {
+        endpoint.to_owned()
+    } else {
+        format!("{endpoint}/v1/traces")
+    };
+
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|err| Error::OtlpError(err.to_string()))?;
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+        .build())
+}
+
+/// The tracing layer exporting the spans through the provider.
+pub fn layer<S>(provider: &SdkTracerProvider) -> OpenTelemetryLayer<S, Tracer>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME))
+}
+
+/// The outcome a span is tagged with.
+pub fn outcome<T>(result: &Result<T>) -> &'static str {
+    match result {
+        Ok(_) => "ok",
+        Err(_) => "error",
+    }
+}
@@ -0,0 +1,47 @@
+//! State persisted to JSON files, e.g. the result cache and the tile memory.
+
+use crate::Result;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Returns the temporary file the file is written to, e.g. `fs.cache.tmp` for
+/// `fs.cache`, distinct for every persisted file.
+fn temporary(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".tmp");
+    PathBuf::from(name)
+}
+
+/// Save the value to the file, written to a temporary file first so a crash
+/// never leaves a truncated file.
+pub fn save<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<()> {
+    let tmp = temporary(path);
+    std::fs::write(&tmp, serde_json::to_vec(value)?)?;
+    std::fs::rename(tmp, path)?;
+    Ok(())
+}
+
+/// Load the value from the file, `None` if it doesn't exist.
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    Ok(Some(serde_json::from_slice(&data)?))
+}
+
+/// Save every interval in seconds, failures are logged with what is saved.
+pub async fn every(interval: u64, what: &str, save: impl Fn() -> Result<()>) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        if let Err(err) = save() {
+            tracing::warn!("Failed to save the {}: {}", what, err);
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use std::{
+    hash::{BuildHasher, RandomState},
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Returns a random number in `[0, 1)`.
+pub fn sample() -> f64 {
+    let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    (RandomState::new().hash_one((SystemTime::now(), sequence)) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Returns a random unique id, e.g. for tasks and requests.
+pub fn id() -> String {
+    let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    let random = RandomState::new().hash_one((SystemTime::now(), sequence));
+    format!("{random:016x}{sequence:08x}")
+}
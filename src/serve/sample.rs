@@ -0,0 +1,295 @@
+use super::drain::tracker;
+use crate::{
+    onnx::{decode_base64, Prediction, S3Adapter},
+    Result,
+};
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Sample store configuration.
+///
+/// Samples are saved in the layout the model tests read: one directory per game
+/// variant holding `<id>_marked_<label>.jpg` images, their `.txt` labels and a
+/// `.json` with the instructions, answers and scores. Samples without a known
+/// label are kept apart in its `unlabelled` directory, as `<id>.jpg` and `.json`.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct SampleConfig {
+    /// Sample directory
+    pub dir: PathBuf,
+    /// Save predictions whose score of the answer is below this threshold
+    #[serde(default)]
+    pub min_confidence: Option<f32>,
+    /// Submit low-confidence tasks to the fallback solver in the background and
+    /// save the images it answers differently
+    #[serde(default)]
+    pub fallback_disagreement: bool,
+    /// Save images reported as wrongly answered through the feedback endpoint
+    #[serde(default = "default_true")]
+    pub feedback: bool,
+    /// Maximum total size of the samples in bytes, the oldest are removed first
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+    /// Maximum age of the samples in seconds
+    #[serde(default)]
+    pub max_age: Option<u64>,
+    /// Upload the samples through the S3 model store, if configured
+    #[serde(default)]
+    pub upload: bool,
+}
+
+/// Directory of the samples without a known label, under the game variant's.
+const UNLABELLED: &str = "unlabelled";
+
+fn default_true() -> bool {
+    true
+}
+
+/// Why a sample was captured.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleReason {
+    LowConfidence,
+    FallbackDisagreement,
+    Feedback,
+}
+
+/// A captured sample.
+#[derive(Clone, Debug)]
+pub struct Sample {
+    /// Game variant and instructions of the task
+    pub game_variant_instructions: (String, String),
+    /// Base64 image
+    pub image: Arc<String>,
+    /// Why the sample was captured
+    pub reason: SampleReason,
+    /// The true label, the fallback solver's or the reported answer, if known.
+    /// The local model's answer is not a label.
+    pub label: Option<i32>,
+    /// The local model's prediction, if any
+    pub prediction: Option<Prediction>,
+    /// The fallback solver's answer, if any
+    pub fallback: Option<i32>,
+}
+
+/// Opt-in store of hard or disputed samples for retraining.
+pub struct SampleStore {
+    config: SampleConfig,
+    s3: Option<S3Adapter>,
+    sequence: AtomicU64,
+}
+
+impl SampleStore {
+    pub fn new(config: SampleConfig, s3: Option<S3Adapter>) -> SampleStore {
+        if config.upload && s3.is_none() {
+            tracing::warn!("Sample upload requires the S3 model store, samples are kept locally");
+        }
+
+        SampleStore {
+            config,
+            s3,
+            sequence: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    pub fn config(&self) -> &SampleConfig {
+        &self.config
+    }
+
+    /// Whether the prediction is below the confidence threshold.
+    pub fn low_confidence(&self, prediction: &Prediction) -> bool {
+        self.config
+            .min_confidence
+            .is_some_and(|threshold| prediction.confidence() < threshold)
+    }
+
+    /// Save the sample in the background, then prune and optionally upload it.
+    pub fn capture(self: &Arc<Self>, sample: Sample) {
+        let store = self.clone();
+        tracker().spawn(async move {
+            let saved = tokio::task::spawn_blocking({
+                let store = store.clone();
+                move || {
+                    let files = store.save(&sample)?;
+                    store.prune()?;
+                    Ok::<_, crate::error::Error>(files)
+                }
+            })
+            .await;
+
+            let files = match saved {
+                Ok(Ok(files)) => files,
+                Ok(Err(err)) => return tracing::warn!("Failed to save sample: {}", err),
+                Err(err) => return tracing::warn!("Failed to save sample: {}", err),
+            };
+
+            if let (true, Some(s3)) = (store.config.upload, &store.s3) {
+                for file in files {
+                    let key = file
+                        .strip_prefix(&store.config.dir)
+                        .unwrap_or(&file)
+                        .to_string_lossy();
+                    if let Err(err) = s3.upload_file(&format!("samples/{key}"), &file).await {
+                        tracing::warn!("Failed to upload sample {}: {}", file.display(), err);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Save the sample and return the written files.
+    pub fn save(&self, sample: &Sample) -> Result<Vec<PathBuf>> {
+        let (game_variant, instructions) = &sample.game_variant_instructions;
+        let dir = match sample.label {
+            Some(_) => self.config.dir.join(sanitize(game_variant)),
+            None => self
+                .config
+                .dir
+                .join(sanitize(game_variant))
+                .join(UNLABELLED),
+        };
+        std::fs::create_dir_all(&dir)?;
+
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        let name = match sample.label {
+            Some(label) => format!("{millis}-{sequence}_marked_{label}"),
+            None => format!("{millis}-{sequence}"),
+        };
+
+        // Re-encode the image if it isn't a JPEG
+        let bytes = decode_base64(&sample.image)?;
+        let image_file = dir.join(format!("{name}.jpg"));
+        match image::guess_format(&bytes)? {
+            ImageFormat::Jpeg => std::fs::write(&image_file, bytes)?,
+            _ => image::load_from_memory(&bytes)?
+                .into_rgb8()
+                .save_with_format(&image_file, ImageFormat::Jpeg)?,
+        }
+
+        let mut files = vec![image_file];
+        if let Some(label) = sample.label {
+            let label_file = dir.join(format!("{name}.txt"));
+            std::fs::write(&label_file, label.to_string())?;
+            files.push(label_file);
+        }
+
+        let meta_file = dir.join(format!("{name}.json"));
+        std::fs::write(
+            &meta_file,
+            serde_json::to_vec_pretty(&json!({
+                "game_variant_instructions": [game_variant, instructions],
+                "reason": sample.reason,
+                "label": sample.label,
+                "prediction": sample.prediction,
+                "fallback": sample.fallback,
+            }))?,
+        )?;
+
+        files.push(meta_file);
+        Ok(files)
+    }
+
+    /// Remove samples older than the maximum age, then the oldest samples until
+    /// the total size is within the maximum. A sample's image, label and
+    /// metadata files are removed together.
+    pub fn prune(&self) -> Result<()> {
+        if self.config.max_age.is_none() && self.config.max_bytes.is_none() {
+            return Ok(());
+        }
+
+        let mut samples = BTreeMap::<PathBuf, Entry>::new();
+        for dir in std::fs::read_dir(&self.config.dir)? {
+            let dir = dir?;
+            if !dir.file_type()?.is_dir() {
+                continue;
+            }
+            let dirs = [dir.path(), dir.path().join(UNLABELLED)];
+            for dir in dirs.iter().filter(|dir| dir.is_dir()) {
+                for file in std::fs::read_dir(dir)? {
+                    let file = file?;
+                    let metadata = file.metadata()?;
+                    if !metadata.is_file() {
+                        continue;
+                    }
+                    let path = file.path();
+                    let entry = samples.entry(path.with_extension("")).or_insert(Entry {
+                        modified: metadata.modified()?,
+                        len: 0,
+                        files: vec![],
+                    });
+                    entry.modified = entry.modified.min(metadata.modified()?);
+                    entry.len += metadata.len();
+                    entry.files.push(path);
+                }
+            }
+        }
+        let mut samples = samples.into_values().collect::<Vec<_>>();
+        samples.sort_by_key(|entry| entry.modified);
+
+        let now = SystemTime::now();
+        let mut total: u64 = samples.iter().map(|entry| entry.len).sum();
+        for Entry {
+            modified,
+            len,
+            files,
+        } in samples
+        {
+            let expired = self.config.max_age.is_some_and(|max_age| {
+                now.duration_since(modified).unwrap_or_default() > Duration::from_secs(max_age)
+            });
+            let oversized = self
+                .config
+                .max_bytes
+                .is_some_and(|max_bytes| total > max_bytes);
+            if !expired && !oversized {
+                break;
+            }
+            for path in files {
+                remove(&path)?;
+            }
+            total -= len;
+        }
+
+        Ok(())
+    }
+}
+
+/// The files of a saved sample, their oldest modification time and total size.
+struct Entry {
+    modified: SystemTime,
+    len: u64,
+    files: Vec<PathBuf>,
+}
+
+/// Remove a sample file, ignoring files already removed.
+fn remove(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Keep the game variant usable as a directory name.
+fn sanitize(game_variant: &str) -> String {
+    game_variant
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' => c,
+            _ => '_',
+        })
+        .collect()
+}
@@ -1,55 +1,75 @@
+use super::drain::tracker;
 use axum_server::Handle;
-use std::time::Duration;
+use std::{future::Future, time::Duration};
 #[cfg(target_family = "unix")]
 use tokio::signal::unix::{signal, SignalKind};
-use tokio::time::sleep;
 use tracing::info;
 
-pub async fn graceful_shutdown(handle: Handle) {
+/// Wait for a shutdown signal, then drain the server. SIGHUP reloads the
+/// configuration instead, in the background so that a shutdown signal isn't
+/// delayed by a reload.
+#[cfg_attr(target_family = "windows", allow(unused_variables))]
+pub async fn graceful_shutdown<R, F>(
+    handle: Handle,
+    grace: Duration,
+    drain_timeout: Duration,
+    reload: R,
+) where
+    R: Fn() -> F,
+    F: Future<Output = ()> + Send + 'static,
+{
     #[cfg(target_family = "windows")]
     {
         tokio::signal::ctrl_c()
             .await
             .expect("Ctrl+C signal hanlde error");
-        sending_graceful_shutdown_signal(handle, "SIGINT").await;
+        sending_graceful_shutdown_signal(handle, grace, drain_timeout, "SIGINT").await;
     }
 
     #[cfg(target_family = "unix")]
     {
         let mut sigterm = signal(SignalKind::terminate()).expect("SIGTERM signal hanlde error");
         let mut sigquit = signal(SignalKind::quit()).expect("SIGQUIT signal hanlde error");
-        let mut sigchld = signal(SignalKind::child()).expect("SIGCHLD signal hanlde error");
         let mut sighup = signal(SignalKind::hangup()).expect("SIGHUP signal hanlde error");
-        tokio::select! {
-            _ = sigterm.recv() => {
-                sending_graceful_shutdown_signal(handle, "SIGTERM").await;
-            },
-            _ = sigquit.recv() => {
-                sending_graceful_shutdown_signal(handle, "SIGQUIT").await;
-            },
-            _ = sigchld.recv() => {
-                sending_graceful_shutdown_signal(handle, "SIGCHLD").await;
-            },
-            _ = sighup.recv() => {
-                sending_graceful_shutdown_signal(handle, "SIGHUP").await;
-            },
-            _ = tokio::signal::ctrl_c() => {
-                sending_graceful_shutdown_signal(handle, "SIGINT").await;
+        let signal = loop {
+            tokio::select! {
+                _ = sigterm.recv() => break "SIGTERM",
+                _ = sigquit.recv() => break "SIGQUIT",
+                _ = tokio::signal::ctrl_c() => break "SIGINT",
+                _ = sighup.recv() => {
+                    info!("SIGHUP received: reloading");
+                    super::systemd::notify_or_log("RELOADING=1");
+                    let reload = reload();
+                    tokio::spawn(async move {
+                        reload.await;
+                        // A shutdown during the reload already reported stopping
+                        if !tracker().draining() {
+                            super::systemd::notify_or_log("READY=1");
+                        }
+                    });
+                }
             }
-
         };
+        sending_graceful_shutdown_signal(handle, grace, drain_timeout, signal).await;
     }
 }
 
-async fn sending_graceful_shutdown_signal(handle: Handle, signal: &'static str) {
-    info!("{signal} received: starting graceful shutdown");
+/// Report the server not ready, keep accepting connections for the grace period
+/// so that load balancers stop routing to it, then stop accepting connections
+/// and close the open ones once their requests are answered, at most after the
+/// drain timeout.
+async fn sending_graceful_shutdown_signal(
+    handle: Handle,
+    grace: Duration,
+    drain_timeout: Duration,
+    signal: &str,
+) {
+    info!("{signal} received: draining for up to {drain_timeout:?} after a {grace:?} grace period");
+    tracker().drain();
+    #[cfg(target_family = "unix")]
+    super::systemd::notify_or_log("STOPPING=1");
+    tokio::time::sleep(grace).await;
 
     // Signal the server to shut down using Handle.
-    handle.graceful_shutdown(Some(Duration::from_secs(3)));
-
-    // Print alive connection count every second.
-    loop {
-        sleep(Duration::from_secs(1)).await;
-        info!("Alive connections: {}", handle.connection_count());
-    }
+    handle.graceful_shutdown(Some(drain_timeout));
 }
@@ -1,14 +1,26 @@
-use super::{Task, TaskResult};
+use super::{
+    breaker::{BreakerStatus, Breakers},
+    cache::ResultCache,
+    drain::tracker,
+    experiment::{self, Experiment, ExperimentMode},
+    fallback::{self, FallbackOrder, FallbackSolver},
+    feedback::{AccuracyStatus, Feedback, FeedbackRequest, TaskRecord},
+    memory::{self, TileMemory},
+    metrics::metrics,
+    sample::{Sample, SampleReason, SampleStore},
+    spend::{Spend, SpendStatus},
+    status::PredictorStatus,
+    Task, TaskResult,
+};
 use crate::{
     error::Error,
-    onnx::{self, ONNXConfig, Predictor, Variant},
+    onnx::{self, ONNXConfig, Prediction, Predictor, Registry, RouteTable},
     Result,
 };
 use axum::Json;
-use serde::{Deserialize, Serialize};
-use serde_json::json;
-use std::{ops::Deref, str::FromStr, sync::Arc};
+use std::{collections::HashMap, sync::Arc};
 use tokio::sync::OnceCell;
+use tracing::Instrument;
 use typed_builder::TypedBuilder;
 
 /// The `SolverProcess` trait defines a common interface for processing tasks.
@@ -31,40 +43,40 @@ pub trait Solver {
     async fn process(&self, task: &Task) -> Result<Json<TaskResult>>;
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub enum TypedFallback {
-    Yescaptcha,
-    Capsolver,
-}
-
-impl FromStr for TypedFallback {
-    type Err = Error;
-
-    fn from_str(s: &str) -> Result<Self> {
-        match s {
-            "yescaptcha" => Ok(Self::Yescaptcha),
-            "capsolver" => Ok(Self::Capsolver),
-            _ => Err(Error::InvalidSolverType(s.to_string())),
-        }
-    }
-}
-
 /// `SolverHelper` is a struct that encapsulates the logic for handling tasks using both an ONNX model and a fallback solver.
 ///
 /// It contains an `ONNXSolver` instance, which handles tasks using an ONNX model,
-/// an optional `FallbackSolver` instance, which handles tasks using a fallback solver if the ONNX model fails,
+/// a chain of `FallbackSolver` instances, which handle tasks using fallback providers if the ONNX model fails,
 /// and a limit for the number of tasks that can be processed.
 ///
 /// # Fields
 /// * `limit`: The maximum number of tasks that can be processed.
 /// * `onnx_solver`: The ONNX solver used to process tasks.
-/// * `fallback_solver`: The fallback solver used to process tasks if the ONNX solver fails.
+/// * `fallback_solvers`: The fallback providers tried if the ONNX solver fails.
+/// * `fallback_order`: The order the fallback providers are tried in.
+/// * `samples`: The optional store of hard or disputed samples.
+/// * `feedback`: The recent tasks and the accuracy reported through the feedback endpoint.
+/// * `breakers`: The circuit breakers of the local models and the fallback providers.
+/// * `spend`: The spend, balances and daily budget of the fallback providers.
+/// * `fallback_retry_invalid`: Whether an invalid fallback response moves on to the next provider.
 #[derive(TypedBuilder)]
 pub struct SolverHelper {
     limit: usize,
     onnx_solver: DefaultSolver,
-    fallback_solver: Option<FallbackSolver>,
+    #[builder(default, setter(transform = |solvers: Vec<FallbackSolver>| solvers.into_iter().map(Arc::new).collect()))]
+    fallback_solvers: Vec<Arc<FallbackSolver>>,
+    #[builder(default)]
+    fallback_order: FallbackOrder,
+    #[builder(default)]
+    samples: Option<Arc<SampleStore>>,
+    #[builder(default)]
+    feedback: Arc<Feedback>,
+    #[builder(default)]
+    breakers: Arc<Breakers>,
+    #[builder(default)]
+    spend: Arc<Spend>,
+    #[builder(default = true)]
+    fallback_retry_invalid: bool,
 }
 
 impl SolverHelper {
@@ -89,261 +101,816 @@ impl SolverHelper {
     }
 }
 
+impl SolverHelper {
+    /// Returns the status of the loaded predictors.
+    pub fn status(&self) -> Vec<PredictorStatus> {
+        self.onnx_solver.status()
+    }
+
+    /// Capture the low-confidence predictions of the solution.
+    ///
+    /// If fallback disagreement is enabled, the low-confidence images are
+    /// submitted to the fallback solver in the background and only the images it
+    /// answers differently are captured, labelled with its answers.
+    fn capture(&self, task: &Task, solution: &Solution) {
+        let Some(samples) = self.samples.as_ref() else {
+            return;
+        };
+
+        let low_confidence = solution
+            .predictions
+            .iter()
+            .enumerate()
+            .filter_map(|(index, prediction)| {
+                let prediction = prediction.as_ref()?;
+                samples.low_confidence(prediction).then_some(index)
+            })
+            .collect::<Vec<_>>();
+        if low_confidence.is_empty() {
+            return;
+        }
+
+        let sample = |index: usize, reason, label, fallback| Sample {
+            game_variant_instructions: task.game_variant_instructions.clone(),
+            image: task.images[index].clone(),
+            reason,
+            label,
+            prediction: solution.predictions[index].clone(),
+            fallback,
+        };
+
+        let (game_variant, _) = &task.game_variant_instructions;
+        let fallback_solver = self.fallback_chain(game_variant).next();
+        let verify = samples.config().fallback_disagreement && self.spend.check().is_ok();
+        match (fallback_solver, verify) {
+            (Some(fallback_solver), true) => {
+                let fallback_solver = fallback_solver.clone();
+                let samples = samples.clone();
+                let candidates = self.onnx_solver.candidates(task);
+                let task = Task {
+                    api_key: None,
+                    images: low_confidence
+                        .iter()
+                        .map(|&index| task.images[index].clone())
+                        .collect(),
+                    game_variant_instructions: task.game_variant_instructions.clone(),
+                };
+                let candidates = low_confidence
+                    .iter()
+                    .map(|&index| candidates[index])
+                    .collect::<Vec<_>>();
+                let samples_of = low_confidence
+                    .iter()
+                    .map(|&index| {
+                        let answer = solution.answers[index];
+                        (
+                            answer,
+                            sample(index, SampleReason::FallbackDisagreement, None, None),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                let verification = async move {
+                    // The provider records the spend of its submissions, valid answers or not
+                    let answers = fallback_solver.solve(&task).await;
+                    let answers = answers.and_then(|answers| {
+                        fallback::validate(fallback_solver.name(), &answers, &candidates)
+                            .map(|_| answers)
+                    });
+                    let answers = match answers {
+                        Ok(answers) => answers,
+                        Err(err) => return tracing::warn!("Fallback verification failed: {}", err),
+                    };
+                    for ((answer, mut sample), fallback) in samples_of.into_iter().zip(answers) {
+                        if answer != fallback {
+                            sample.label = Some(fallback);
+                            sample.fallback = Some(fallback);
+                            samples.capture(sample);
+                        }
+                    }
+                };
+                tracker().spawn(verification.instrument(tracing::Span::current()));
+            }
+            _ => {
+                for index in low_confidence {
+                    samples.capture(sample(index, SampleReason::LowConfidence, None, None));
+                }
+            }
+        }
+    }
+}
+
 impl Solver for SolverHelper {
     /// Process the task
     async fn process(&self, task: &Task) -> Result<Json<TaskResult>> {
         // Validate the task
-        self.validate_task(&task)?;
-
-        // Match the fallback solver
-        match self.fallback_solver.as_ref() {
-            // If there is no fallback solver, use the solver task
-            None => self.onnx_solver.process(task).await,
-            // If there is a fallback solver, use the fallback solver task
-            Some(fallback_solver) => {
-                // Try to use the solver task
-                match self.onnx_solver.process(task).await {
-                    // If the solver task is successful, return the result
-                    Ok(result) => Ok(result),
-                    // If the solver task fails, use the fallback solver task
-                    Err(_) => fallback_solver.process(task).await,
+        self.validate_task(task)?;
+
+        // Skip the local model if the game variant is unknown, the model is
+        // disabled by the feedback or its circuit is open. Circuits are only
+        // kept for registered game variants.
+        let game_variant = &task.game_variant_instructions.0;
+        let solution = if !self.onnx_solver.registered(game_variant) {
+            Err(Error::UnknownVariantType(game_variant.clone()))
+        } else if self.feedback.disabled(game_variant) {
+            Err(Error::PredictorNotActive(game_variant.clone()))
+        } else {
+            let breaker = self.breakers.variant(game_variant);
+            if breaker.allow() {
+                let solution = self.onnx_solver.solve(task).await;
+                match solution {
+                    Ok(_) => breaker.success(),
+                    // A model that fails to load or to predict an image opens the circuit,
+                    // client errors such as undecodable images say nothing about its health
+                    Err(ref err) if err.status().is_server_error() => breaker.failure(),
+                    Err(_) => (),
+                }
+                solution
+            } else {
+                Err(Error::CircuitOpen(game_variant.clone()))
+            }
+        };
+
+        // Try to use the solver task
+        let (answers, predictions, local) = match solution {
+            // If the solver task is successful, capture the hard samples
+            Ok(solution) => {
+                self.capture(task, &solution);
+                (solution.answers, solution.predictions, true)
+            }
+            // If the solver task fails, use the fallback solver task if there is one
+            Err(err) => match self.fallback_chain(game_variant).next() {
+                Some(_) => (self.fallback(task).await?, vec![], false),
+                None => return Err(err),
+            },
+        };
+
+        // Keep the images only if they may be saved or remembered with their true labels
+        let keep_images = self.onnx_solver.memory().is_some()
+            || self
+                .samples
+                .as_ref()
+                .is_some_and(|samples| samples.config().feedback);
+        let task_id = self.feedback.record(TaskRecord {
+            game_variant_instructions: task.game_variant_instructions.clone(),
+            images: match keep_images {
+                true => task.images.clone(),
+                false => vec![],
+            },
+            answers: answers.clone(),
+            predictions,
+            local,
+        });
+
+        Ok(Json(
+            TaskResult::builder()
+                .task_id(task_id)
+                .solved(true)
+                .objects(answers)
+                .build(),
+        ))
+    }
+}
+
+impl SolverHelper {
+    /// Returns the fallback providers allowed for the game variant, in the order
+    /// they are tried.
+    fn fallback_chain<'a>(
+        &'a self,
+        game_variant: &'a str,
+    ) -> impl Iterator<Item = &'a Arc<FallbackSolver>> + 'a {
+        fallback::chain(&self.fallback_solvers, game_variant, self.fallback_order).into_iter()
+    }
+
+    /// Solve the task with the fallback providers in order until one succeeds.
+    /// Providers whose circuit is open are skipped, the last error is returned
+    /// if all of them fail. Once the daily budget is exhausted the task fails
+    /// without trying them. Answers that don't fit the task's images count as a
+    /// failure of the provider, they are billed all the same.
+    async fn fallback(&self, task: &Task) -> Result<Vec<i32>> {
+        self.spend.check()?;
+
+        let (game_variant, _) = &task.game_variant_instructions;
+        let candidates = self.onnx_solver.candidates(task);
+        let mut last_err = None;
+
+        for fallback_solver in self.fallback_chain(game_variant) {
+            let breaker = self.breakers.fallback(fallback_solver.name());
+            if !breaker.allow() {
+                last_err = Some(Error::CircuitOpen(fallback_solver.name().to_owned()));
+                continue;
+            }
+
+            // The provider records the spend of its submissions, valid answers or not
+            let answers = fallback_solver.solve(task).await;
+            let answers = answers.and_then(|answers| {
+                fallback::validate(fallback_solver.name(), &answers, &candidates).map(|_| answers)
+            });
+            match answers {
+                Ok(answers) => {
+                    breaker.success();
+                    return Ok(answers);
+                }
+                Err(err) => {
+                    tracing::warn!("Fallback {} failed: {}", fallback_solver.name(), err);
+                    breaker.failure();
+                    if let Error::InvalidFallbackResponse(..) = err {
+                        metrics().inc(
+                            "fs_fallback_invalid_total",
+                            &[("provider", fallback_solver.name())],
+                        );
+                        if !self.fallback_retry_invalid {
+                            return Err(err);
+                        }
+                    }
+                    last_err = Some(err);
                 }
             }
         }
+
+        Err(last_err.unwrap_or_else(|| Error::PredictorNotActive(game_variant.clone())))
+    }
+
+    /// Load the predictors of every game variant, returns the number loaded or
+    /// the first failure.
+    pub async fn preload(&self) -> Result<usize> {
+        self.onnx_solver.preload().await
+    }
+
+    /// Returns the spend of the fallback providers.
+    pub fn spend(&self) -> SpendStatus {
+        self.spend.status()
+    }
+
+    /// Returns the multiple image submission limit.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Returns the interval the balances of the fallback providers are queried
+    /// at, in seconds, 0 if disabled.
+    pub fn balance_interval(&self) -> u64 {
+        self.spend.config().balance_interval
+    }
+
+    /// Query the balances of the fallback providers.
+    pub async fn refresh_balances(&self) {
+        for fallback_solver in &self.fallback_solvers {
+            match fallback_solver.balance().await {
+                Ok(balance) => self.spend.set_balance(fallback_solver.name(), balance),
+                Err(err) => tracing::debug!(
+                    "Failed to query the balance of {}: {}",
+                    fallback_solver.name(),
+                    err
+                ),
+            }
+        }
+    }
+
+    /// Whether the game variant is in the model registry.
+    pub fn registered(&self, game_variant: &str) -> bool {
+        self.onnx_solver.registered(game_variant)
+    }
+
+    /// Returns the status of the circuit breakers.
+    pub fn breakers(&self) -> Vec<BreakerStatus> {
+        self.breakers.status()
+    }
+
+    /// Apply the feedback of a task. Images answered wrongly are saved with
+    /// their true labels if the sample store accepts feedback.
+    pub fn feedback(&self, id: &str, request: &FeedbackRequest) -> Result<()> {
+        let (record, labels) = self.feedback.report(id, request)?;
+
+        // Remember or forget the tile sets in the background
+        let game_variant = &record.game_variant_instructions.0;
+        if let (Some(memory), Some(spec)) = (
+            self.onnx_solver.memory(),
+            self.onnx_solver.spec(game_variant),
+        ) {
+            let memory = memory.clone();
+            let record = record.clone();
+            let labels = labels.clone();
+            let correct = request.correct;
+            tracker().spawn_blocking(move || {
+                memory.confirm(
+                    &spec,
+                    &record.game_variant_instructions,
+                    &record.images,
+                    &labels,
+                    correct,
+                )
+            });
+        }
+
+        let Some(samples) = self.samples.as_ref() else {
+            return Ok(());
+        };
+        if !samples.config().feedback {
+            return Ok(());
+        }
+
+        for (index, (image, (&answer, &label))) in record
+            .images
+            .iter()
+            .zip(record.answers.iter().zip(&labels))
+            .enumerate()
+        {
+            if answer != label {
+                samples.capture(Sample {
+                    game_variant_instructions: record.game_variant_instructions.clone(),
+                    image: image.clone(),
+                    reason: SampleReason::Feedback,
+                    label: Some(label),
+                    prediction: record.predictions.get(index).cloned().flatten(),
+                    fallback: (!record.local).then_some(answer),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the rolling accuracy of the game variants.
+    pub fn accuracy(&self) -> Vec<AccuracyStatus> {
+        self.feedback.status()
     }
 }
 
 /// `OnnxSolver` is a struct that encapsulates the logic for handling tasks using an ONNX model.
 ///
 /// It contains an `ONNXConfig` instance, which holds the configuration for the ONNX model,
-/// and an array of `OnceCell` instances, each of which can hold a `Box<dyn Predictor>`.
+/// a model registry, which maps each game variant to its model spec, and a map of `OnceCell`
+/// instances, each of which can hold a `Box<dyn Predictor>`.
 /// The `OnceCell` instances are used to lazily initialize and store the predictors for each variant.
+/// Tasks matching an instruction route use the route's own predictor instead.
 ///
 /// # Fields
 /// * `config`: The ONNX model configuration.
-/// * `predictors`: An array of `OnceCell` instances, each of which can hold a `Box<dyn Predictor>`.
+/// * `registry`: The model registry.
+/// * `routes`: The instruction-aware routing table.
+/// * `predictors`: The `OnceCell` instances of the predictors, keyed by game variant.
+/// * `route_predictors`: The `OnceCell` instances of the routed predictors, indexed by route.
+/// * `experiments`: The candidate model experiments, keyed by game variant.
+/// * `candidates`: The `OnceCell` instances of the candidate predictors, keyed by game variant.
+/// * `cache`: The optional cache of the predictions of recently seen images.
+/// * `memory`: The optional memory of the answers of recurring tile sets.
 #[derive(TypedBuilder)]
 pub struct DefaultSolver {
     config: ONNXConfig,
-    predictors: [OnceCell<Arc<dyn Predictor>>; Variant::const_count()],
+    #[builder(default)]
+    registry: Registry,
+    #[builder(default)]
+    routes: RouteTable,
+    #[builder(default = registry.keys().map(|key| (key.clone(), OnceCell::new())).collect())]
+    predictors: HashMap<String, OnceCell<Arc<dyn Predictor>>>,
+    #[builder(default = std::iter::repeat_with(OnceCell::new).take(routes.len()).collect())]
+    route_predictors: Vec<OnceCell<Arc<dyn Predictor>>>,
+    #[builder(default)]
+    experiments: HashMap<String, Experiment>,
+    #[builder(default = experiments.keys().map(|key| (key.clone(), OnceCell::new())).collect())]
+    candidates: HashMap<String, OnceCell<Arc<dyn Predictor>>>,
+    #[builder(default)]
+    cache: Option<Arc<ResultCache>>,
+    #[builder(default)]
+    memory: Option<Arc<TileMemory>>,
+}
+
+impl DefaultSolver {
+    /// Returns the status of the loaded predictors, sorted by game variant.
+    /// Predictors are loaded lazily, so variants without a task yet are not listed.
+    pub fn status(&self) -> Vec<PredictorStatus> {
+        let status = |game_variant: &str, route, predictor: &Arc<dyn Predictor>| PredictorStatus {
+            game_variant: game_variant.to_owned(),
+            route,
+            candidate: false,
+            active: predictor.active(),
+            models: predictor.models(),
+        };
+
+        let mut predictors: Vec<_> = self
+            .predictors
+            .iter()
+            .filter_map(|(game_variant, cell)| Some(status(game_variant, None, cell.get()?)))
+            .collect();
+        predictors.sort_by(|a, b| a.game_variant.cmp(&b.game_variant));
+
+        predictors.extend(
+            self.routes
+                .iter()
+                .zip(&self.route_predictors)
+                .enumerate()
+                .filter_map(|(index, (route, cell))| {
+                    Some(status(&route.game_variant, Some(index), cell.get()?))
+                }),
+        );
+
+        let mut candidates: Vec<_> = self
+            .candidates
+            .iter()
+            .filter_map(|(game_variant, cell)| {
+                let mut status = status(game_variant, None, cell.get()?);
+                status.candidate = true;
+                Some(status)
+            })
+            .collect();
+        candidates.sort_by(|a, b| a.game_variant.cmp(&b.game_variant));
+        predictors.extend(candidates);
+        predictors
+    }
+}
+
+impl DefaultSolver {
+    /// Drop the game variant's cached results computed by models no longer
+    /// loaded, called when a predictor is loaded.
+    fn invalidate_cache(&self, game_variant: &str, loaded: &Arc<dyn Predictor>) {
+        let Some(cache) = self.cache.as_ref() else {
+            return;
+        };
+
+        let mut fingerprints = vec![loaded.fingerprint()];
+        let cells = self
+            .predictors
+            .get(game_variant)
+            .into_iter()
+            .chain(self.candidates.get(game_variant))
+            .chain(
+                self.routes
+                    .iter()
+                    .zip(&self.route_predictors)
+                    .filter(|(route, _)| route.game_variant == game_variant)
+                    .map(|(_, cell)| cell),
+            );
+        fingerprints.extend(cells.filter_map(|cell| Some(cell.get()?.fingerprint())));
+        cache.retain(game_variant, &fingerprints);
+    }
+
+    /// Returns the predictor of the game variant, or of the route if any,
+    /// loading it on first use.
+    async fn predictor(
+        &self,
+        game_variant: &str,
+        spec: &onnx::ModelSpec,
+        route: Option<(usize, &onnx::Route)>,
+    ) -> Result<&Arc<dyn Predictor>> {
+        match route {
+            Some((index, route)) => {
+                self.route_predictors[index]
+                    .get_or_try_init(|| async {
+                        let spec = spec.with_options(&route.options);
+                        let predictor = onnx::build_predictor(&spec, &self.config).await?;
+                        self.invalidate_cache(game_variant, &predictor);
+                        Ok::<_, Error>(predictor)
+                    })
+                    .await
+            }
+            None => {
+                self.predictors[game_variant]
+                    .get_or_try_init(|| async {
+                        let predictor = onnx::build_predictor(spec, &self.config).await?;
+                        self.invalidate_cache(game_variant, &predictor);
+                        Ok::<_, Error>(predictor)
+                    })
+                    .await
+            }
+        }
+    }
+
+    /// Load the predictors of every game variant and route, instead of on the
+    /// first task. Returns the number loaded, or the first failure once all of
+    /// them were tried. Failures are logged and retried on the variant's next
+    /// task.
+    pub async fn preload(&self) -> Result<usize> {
+        let mut variants = self.predictors.keys().collect::<Vec<_>>();
+        variants.sort();
+        let routes = self.routes.iter().enumerate().map(|(index, route)| {
+            let game_variant = &route.game_variant;
+            (game_variant, Some((index, route)))
+        });
+
+        let mut loaded = 0;
+        let mut failure = None;
+        for (game_variant, route) in variants.into_iter().map(|v| (v, None)).chain(routes) {
+            let Ok(spec) = self.registry.get(game_variant) else {
+                continue;
+            };
+            match self.predictor(game_variant, spec, route).await {
+                Ok(_) => loaded += 1,
+                Err(err) => {
+                    tracing::warn!("Failed to preload {}: {}", game_variant, err);
+                    failure.get_or_insert(Error::PreloadError(game_variant.clone(), Box::new(err)));
+                }
+            }
+        }
+        match failure {
+            Some(err) => Err(err),
+            None => Ok(loaded),
+        }
+    }
+
+    /// Load the candidate predictor of the experiment.
+    /// Returns `None` and logs the error if it fails to load or isn't active.
+    async fn candidate(
+        &self,
+        game_variant: &str,
+        spec: &onnx::ModelSpec,
+        experiment: &Experiment,
+    ) -> Option<Arc<dyn Predictor>> {
+        let candidate = self.candidates[game_variant]
+            .get_or_try_init(|| async {
+                let spec = spec.with_options(&experiment.candidate);
+                let predictor = onnx::build_predictor(&spec, &self.config).await?;
+                self.invalidate_cache(game_variant, &predictor);
+                Ok::<_, Error>(predictor)
+            })
+            .await;
+
+        match candidate {
+            Ok(candidate) if candidate.active() => Some(candidate.clone()),
+            Ok(_) => None,
+            Err(err) => {
+                tracing::warn!("Failed to load candidate of {}: {}", game_variant, err);
+                None
+            }
+        }
+    }
+}
+
+/// The answers of the local models and the predictions they were mapped from,
+/// `None` for images answered by the tile memory.
+pub struct Solution {
+    pub answers: Vec<i32>,
+    pub predictions: Vec<Option<Prediction>>,
+}
+
+/// The answer of an image of a task.
+enum Answer {
+    /// Answered by the tile memory, already mapped through the route
+    Remembered(i32),
+    /// The prediction and the tile hashes if the tile memory is enabled
+    Predicted(Prediction, Option<Vec<u64>>),
+}
+
+/// What answering the images of a task needs.
+struct Answering {
+    game_variant_instructions: (String, String),
+    fingerprint: String,
+    spec: onnx::ModelSpec,
+    predictor: Arc<dyn Predictor>,
+    cache: Option<Arc<ResultCache>>,
+    memory: Option<Arc<TileMemory>>,
+}
+
+impl Answering {
+    /// Answer the image from the tile memory, the result cache or the predictor,
+    /// in that order.
+    fn answer(&self, image: &str) -> Result<Answer> {
+        let (game_variant, instructions) = &self.game_variant_instructions;
+
+        // Answer from the tile memory if the tiles were seen before
+        let hashes = self
+            .memory
+            .as_ref()
+            .and_then(|_| memory::tile_hashes(&self.spec, image));
+        if let (Some(memory), Some(hashes)) = (&self.memory, &hashes) {
+            if let Some(answer) = memory.lookup(game_variant, instructions, hashes) {
+                return Ok(Answer::Remembered(answer));
+            }
+        }
+
+        // Answer from the cache if the image was seen recently
+        let key = self
+            .cache
+            .as_ref()
+            .and_then(|_| ResultCache::key(game_variant, &self.fingerprint, image));
+        if let (Some(cache), Some(key)) = (&self.cache, &key) {
+            if let Some(prediction) = cache.get(game_variant, key) {
+                return Ok(Answer::Predicted(prediction, hashes));
+            }
+        }
+
+        let prediction = self.predictor.prediction_base64(image)?;
+        if let (Some(cache), Some(key)) = (&self.cache, key) {
+            cache.insert(game_variant, &self.fingerprint, key, prediction.clone());
+        }
+        Ok(Answer::Predicted(prediction, hashes))
+    }
 }
 
 impl Solver for DefaultSolver {
     async fn process(&self, task: &Task) -> Result<Json<TaskResult>> {
-        // Try to convert the task to a variant
-        let variant = Variant::try_from(&*task)?;
+        let solution = self.solve(task).await?;
+        Ok(Json(
+            TaskResult::builder()
+                .solved(true)
+                .objects(solution.answers)
+                .build(),
+        ))
+    }
+}
+
+impl DefaultSolver {
+    /// Returns the tile memory, if enabled.
+    pub fn memory(&self) -> Option<&Arc<TileMemory>> {
+        self.memory.as_ref()
+    }
+
+    /// Whether the game variant is in the model registry.
+    pub fn registered(&self, game_variant: &str) -> bool {
+        self.registry.get(game_variant).is_ok()
+    }
+
+    /// Returns the model spec of the game variant, if known.
+    pub fn spec(&self, game_variant: &str) -> Option<onnx::ModelSpec> {
+        self.registry.get(game_variant).ok().cloned()
+    }
+
+    /// Returns the candidate count of each image of the task, `None` where the
+    /// game variant is unknown or the image can't be read.
+    pub fn candidates(&self, task: &Task) -> Vec<Option<u32>> {
+        let spec = self.registry.get(&task.game_variant_instructions.0).ok();
+        task.images
+            .iter()
+            .map(|image| spec?.candidates(dimensions(image).ok()?).ok())
+            .collect()
+    }
+
+    /// Solve the task with the local models.
+    pub async fn solve(&self, task: &Task) -> Result<Solution> {
+        // Get the model spec of the game variant
+        let (game_variant, instructions) = &task.game_variant_instructions;
+        let spec = self.registry.get(game_variant)?;
+
+        // Resolve the instruction route
+        let route = self.routes.resolve(game_variant, instructions)?;
+
+        // Reject the images that don't fit the variant's layout before loading the model
+        for image in &task.images {
+            spec.candidates(dimensions(image)?)?;
+        }
 
         // Process the task using the model
-        let predictor = self.predictors[variant as usize]
-            .get_or_try_init(|| onnx::new_predictor(variant, &self.config))
-            .await?;
+        let predictor = self.predictor(game_variant, spec, route).await?;
 
         // Check if the predictor is active
         if !predictor.active() {
-            return Err(Error::PredictorNotActive(variant));
+            return Err(Error::PredictorNotActive(game_variant.clone()));
         }
 
+        // Load the candidate model of the experiment, if any. The candidate replaces the
+        // variant's model, routed tasks are answered by the route's model only.
+        let experiment = match route {
+            Some(_) => None,
+            None => self.experiments.get(game_variant.as_str()),
+        };
+        let candidate = match experiment {
+            Some(experiment) => self.candidate(game_variant, spec, experiment).await,
+            None => None,
+        };
+
+        // In split mode the candidate answers a fraction of the tasks
+        let (predictor, arm) = match (experiment, &candidate) {
+            (Some(experiment), Some(candidate)) if experiment.split() => (candidate, "candidate"),
+            _ => (predictor, "current"),
+        };
+        let version = predictor.version();
+
         // Process the task
-        let answers = {
+        let answered = {
             // Assume the number of images is known and not too large for buffer size
             let (tx, mut rx) = tokio::sync::mpsc::channel(task.images.len());
-
-            // Create tasks for processing images
+            let answering = Arc::new(Answering {
+                game_variant_instructions: task.game_variant_instructions.clone(),
+                fingerprint: predictor.fingerprint(),
+                spec: spec.clone(),
+                predictor: predictor.clone(),
+                cache: self.cache.clone(),
+                memory: self.memory.clone(),
+            });
+
+            // Create tasks for processing images, in the request's span
             for (index, image) in task.images.iter().enumerate() {
                 let tx = tx.clone();
-                let predictor = predictor.clone();
+                let answering = answering.clone();
                 let image = image.clone();
-                let _ = tokio::task::spawn_blocking(move || {
-                    let answer = predictor.predict_base64(&image).unwrap_or(0);
-                    if let Some(err) = tx.blocking_send((index, answer)).err() {
-                        tracing::warn!("Error sending result: {}", err);
-                    }
-                })
-                .await;
+                let span = tracing::info_span!(
+                    "inference",
+                    variant = %game_variant,
+                    index,
+                    outcome = tracing::field::Empty,
+                );
+                let _ = tracker()
+                    .spawn_blocking(move || {
+                        let _enter = span.enter();
+                        let answer = answering.answer(&image);
+                        span.record(
+                            "outcome",
+                            match answer {
+                                Ok(Answer::Remembered(_)) => "remembered",
+                                Ok(Answer::Predicted(..)) => "ok",
+                                Err(_) => "error",
+                            },
+                        );
+                        if let Some(err) = tx.blocking_send((index, answer)).err() {
+                            tracing::warn!("Error sending result: {}", err);
+                        }
+                    })
+                    .await;
             }
 
             drop(tx);
 
             // Collect and sort the results
-            let mut objects = vec![];
+            let mut answered = vec![];
             while let Some(result) = rx.recv().await {
-                objects.push(result);
+                answered.push(result);
             }
 
-            // Sort the results by index and extract the answers
-            objects.sort_by_key(|&(index, _)| index);
-            objects
+            // Sort the results by index
+            answered.sort_by_key(|(index, _)| *index);
+            answered
                 .into_iter()
                 .map(|(_, answer)| answer)
-                .collect::<Vec<i32>>()
+                .collect::<Vec<_>>()
         };
 
-        // If the task is successfully processed, return the answers
-        Ok(Json(
-            TaskResult::builder().solved(true).objects(answers).build(),
-        ))
-    }
-}
-
-/// `FallbackSolver` is a struct that encapsulates the logic for handling tasks using a fallback solver.
-///
-/// It contains a `TypedFallback` instance, which holds the configuration for the fallback solver,
-/// a `reqwest::Client` instance for making HTTP requests, a client key for authentication,
-/// an optional endpoint URL, and a limit for the number of tasks that can be processed.
-///
-/// # Fields
-/// * `typed`: The fallback solver configuration.
-/// * `client`: The HTTP client used to make requests to the fallback solver.
-/// * `client_key`: The client key used for authentication with the fallback solver.
-/// * `endpoint`: The endpoint URL of the fallback solver. If not provided, a default endpoint is used based on the `SolverType`.
-/// * `limit`: The maximum number of tasks that can be processed by the fallback solver.
-#[derive(TypedBuilder)]
-pub struct FallbackSolver {
-    typed: TypedFallback,
-    client: reqwest::Client,
-    client_key: String,
-    endpoint: Option<String>,
-    limit: usize,
-}
-
-impl FallbackSolver {
-    /// This method is responsible for submitting a task to the solver.
-    ///
-    /// It takes a `SubmitTask` object as an argument, which contains the
-    /// details of the task to be submitted. Depending on the `SolverType`,
-    /// it prepares the endpoint URL and the request body. For `SolverType::Yescaptcha`, it uses the endpoint "https://api.yescaptcha.com/createTask" by default.
-    /// For `SolverType::Capsolver`, it uses the endpoint "https://api.capsolver.com/createTask" by default.
-    /// The request body is a JSON object that includes the client key, task
-    /// details (type, image(s), and question), and either a softID or an appId.
-    /// After preparing the endpoint and the request body, it sends a request to
-    /// the solver.
-    ///
-    /// This method is asynchronous and returns a `Result<Vec<i32>>`.
-    /// If the task is successfully submitted and solved, it returns a `Result`
-    /// wrapping a vector of integers. If there is an error during the
-    /// process, it returns a `Result` wrapping an error.
-    async fn submit_task(&self, submit_task: SubmitTask<'_>) -> Result<Vec<i32>> {
-        let (endpoint, body) = match self.typed {
-            TypedFallback::Yescaptcha => (
-                self.endpoint
-                    .as_deref()
-                    .unwrap_or("https://api.yescaptcha.com/createTask"),
-                json!({
-                    "clientKey": self.client_key,
-                    "task": {
-                        "type": "FunCaptchaClassification",
-                        "image": submit_task.image,
-                        "question": &submit_task.game_variant_instructions.1,
-                    },
-                    "softID": "26299"
-                }),
-            ),
-            TypedFallback::Capsolver => (
-                self.endpoint
-                    .as_deref()
-                    .unwrap_or("https://api.capsolver.com/createTask"),
-                json!({
-                    "clientKey": self.client_key,
-                    "task": {
-                        "type": "FunCaptchaClassification",
-                        "images": submit_task.images,
-                        "question": submit_task.game_variant_instructions.0
-                    },
-                    "appId": "60632CB0-8BE8-41D3-808F-60CC2442F16E"
-                }),
-            ),
-        };
-
-        // Send request
-        let resp = self
-            .client
-            .post(endpoint)
-            .json(&body)
-            .send()
-            .await?
-            .error_for_status()?;
-
-        // Task response
-        let task = resp.json::<TaskResp0>().await?;
-        // If error
-        if let Some(error_description) = task.error_description {
-            return Err(Error::FallbackSolverError(error_description));
+        // Record the per-version metrics
+        let labels = [
+            ("variant", game_variant.as_str()),
+            ("version", version.as_str()),
+            ("arm", arm),
+        ];
+        for answer in &answered {
+            match answer {
+                Ok(Answer::Predicted(..)) => metrics().inc("fs_predictions_total", &labels),
+                Err(_) => metrics().inc("fs_prediction_errors_total", &labels[..2]),
+                Ok(Answer::Remembered(_)) => (),
+            }
         }
 
-        Ok(task.solution.objects)
-    }
-}
-
-impl Solver for FallbackSolver {
-    async fn process(&self, task: &Task) -> Result<Json<TaskResult>> {
-        // Get game variant and instructions
-        let (game_variant, instructions) = &task.game_variant_instructions;
-
-        // Answers
-        let mut answers = Vec::with_capacity(task.images.len());
-
-        match self.typed {
-            TypedFallback::Yescaptcha => {
-                // Single image
-                for image in task.images.iter() {
-                    // Submit task
-                    let task = SubmitTask::builder()
-                        .image(image)
-                        .game_variant_instructions((&game_variant, &instructions))
-                        .build();
-                    let answer = self.submit_task(task).await?;
-                    answers.extend(answer);
+        // An image that fails to predict fails the task, e.g. one that doesn't fit the
+        // model's layout, so that it falls back or is rejected instead of answered 0
+        let answered = answered.into_iter().collect::<Result<Vec<_>>>()?;
+
+        // Extract the answers, remembered answers are already mapped
+        let answers = answered
+            .iter()
+            .map(|answer| match answer {
+                Answer::Remembered(answer) => *answer,
+                Answer::Predicted(prediction, _) => {
+                    let answer = prediction.answer;
+                    match route {
+                        Some((_, route)) => route.map_answer(answer),
+                        None => answer,
+                    }
                 }
-            }
-            TypedFallback::Capsolver => {
-                // Split chunk images
-                let images_chunk = task.images.chunks(self.limit.max(1));
-
-                // Submit multiple images task
-                for chunk in images_chunk {
-                    // Submit task
-                    let task = SubmitTask::builder()
-                        .images(chunk.iter().map(|s| s.deref()).collect::<Vec<_>>())
-                        .game_variant_instructions((&game_variant, &instructions))
-                        .build();
-                    let answer = self.submit_task(task).await?;
-                    answers.extend(answer);
+            })
+            .collect::<Vec<i32>>();
+
+        // Remember the confident answers
+        if let Some(memory) = self.memory.as_ref() {
+            for (answer, answered) in answers.iter().zip(&answered) {
+                if let Answer::Predicted(prediction, Some(hashes)) = answered {
+                    if memory.confident(prediction) {
+                        memory.remember(game_variant, instructions, hashes.clone(), *answer);
+                    }
                 }
             }
         }
 
-        Ok(Json(
-            TaskResult::builder().solved(true).objects(answers).build(),
-        ))
-    }
-}
-
-#[derive(Deserialize, Default)]
-#[serde(default)]
-struct TaskResp0 {
-    #[serde(rename = "errorId")]
-    error_id: i32,
-    #[serde(rename = "errorCode")]
-    error_code: String,
-    #[serde(rename = "errorDescription")]
-    error_description: Option<String>,
-    status: String,
-    solution: SolutionResp,
-    #[serde(rename = "taskId")]
-    task_id: String,
-}
+        let predictions = answered
+            .into_iter()
+            .map(|answer| match answer {
+                Answer::Predicted(prediction, _) => Some(prediction),
+                Answer::Remembered(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        // In shadow mode the candidate runs on the same images in the background
+        if let (Some(experiment), Some(candidate)) = (experiment, candidate) {
+            if experiment.mode == ExperimentMode::Shadow {
+                let game_variant = game_variant.clone();
+                let save_dir = experiment.save_dir.clone();
+                let images = task.images.clone();
+                let predictions = predictions.clone();
+                tracker().spawn_blocking(move || {
+                    experiment::shadow(game_variant, save_dir, candidate, images, predictions)
+                });
+            }
+        }
 
-#[derive(Deserialize, Default)]
-#[serde(default)]
-struct SolutionResp {
-    objects: Vec<i32>,
+        // If the task is successfully processed, return the answers
+        Ok(Solution {
+            answers,
+            predictions,
+        })
+    }
 }
 
-#[derive(typed_builder::TypedBuilder)]
-pub struct SubmitTask<'a> {
-    #[builder(default, setter(strip_option))]
-    pub image: Option<&'a String>,
-    #[builder(default, setter(strip_option))]
-    pub images: Option<Vec<&'a String>>,
-    pub game_variant_instructions: (&'a str, &'a str),
+/// Returns the dimensions of the base64 image, read from its header.
+fn dimensions(image: &str) -> Result<(u32, u32)> {
+    let bytes = onnx::decode_base64(image)?;
+    Ok(image::ImageReader::new(std::io::Cursor::new(bytes))
+        .with_guessed_format()?
+        .into_dimensions()?)
 }
@@ -0,0 +1,193 @@
+use super::metrics::metrics;
+use crate::{error::Error, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Fallback spend configuration.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SpendConfig {
+    /// Maximum spend per UTC day across the fallback providers, in the unit of
+    /// their `cost`. Once reached the fallback is disabled until the next day.
+    pub daily_budget: Option<f64>,
+    /// Seconds between provider balance queries, 0 disables them
+    pub balance_interval: u64,
+}
+
+impl Default for SpendConfig {
+    fn default() -> Self {
+        Self {
+            daily_budget: None,
+            balance_interval: 300,
+        }
+    }
+}
+
+/// Spend of a fallback provider
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ProviderSpend {
+    /// Provider name
+    pub name: String,
+    /// Images submitted in billable tasks
+    pub images: u64,
+    /// Billable tasks submitted
+    pub tasks: u64,
+    /// Total spend since the server started
+    pub spent: f64,
+    /// Spend of the current UTC day
+    pub spent_today: f64,
+    /// Last known balance, if the provider reports one
+    pub balance: Option<f64>,
+}
+
+/// Fallback spend status
+#[derive(Serialize)]
+pub struct SpendStatus {
+    /// Daily budget, if any
+    pub daily_budget: Option<f64>,
+    /// Spend of the current UTC day across the providers
+    pub spent_today: f64,
+    /// Whether the daily budget is exhausted
+    pub exhausted: bool,
+    /// Per-provider spend, sorted by name
+    pub providers: Vec<ProviderSpend>,
+}
+
+#[derive(Default)]
+struct Inner {
+    day: u64,
+    spent_today: f64,
+    providers: BTreeMap<String, ProviderSpend>,
+}
+
+impl Inner {
+    /// Start a new day if the UTC day changed.
+    fn roll(&mut self) {
+        let day = today();
+        if self.day != day {
+            self.day = day;
+            self.spent_today = 0.0;
+            for provider in self.providers.values_mut() {
+                provider.spent_today = 0.0;
+            }
+            metrics().set("fs_fallback_spend_today", &[], 0.0);
+        }
+    }
+}
+
+/// Submitted images, tasks and spend of the fallback providers, and their balances.
+#[derive(Default)]
+pub struct Spend {
+    config: RwLock<SpendConfig>,
+    inner: Mutex<Inner>,
+}
+
+impl Spend {
+    pub fn new(config: SpendConfig) -> Spend {
+        Spend {
+            config: RwLock::new(config),
+            ..Default::default()
+        }
+    }
+
+    pub fn config(&self) -> SpendConfig {
+        self.config
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// Apply a reloaded configuration, the spend so far is kept.
+    pub fn configure(&self, config: SpendConfig) {
+        *self.config.write().unwrap_or_else(|err| err.into_inner()) = config;
+    }
+
+    /// Returns an error if the daily budget is exhausted.
+    pub fn check(&self) -> Result<()> {
+        let Some(budget) = self.config().daily_budget else {
+            return Ok(());
+        };
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        inner.roll();
+        match inner.spent_today >= budget {
+            true => Err(Error::BudgetExceeded(budget)),
+            false => Ok(()),
+        }
+    }
+
+    /// Record the images a provider billed for through the submitted tasks.
+    pub fn record(&self, provider: &str, images: usize, tasks: usize, cost: f64) {
+        let spent = images as f64 * cost;
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        inner.roll();
+        inner.spent_today += spent;
+        let spent_today = inner.spent_today;
+
+        let entry = inner
+            .providers
+            .entry(provider.to_owned())
+            .or_insert_with(|| ProviderSpend {
+                name: provider.to_owned(),
+                ..Default::default()
+            });
+        entry.images += images as u64;
+        entry.tasks += tasks as u64;
+        entry.spent += spent;
+        entry.spent_today += spent;
+
+        let labels = [("provider", provider)];
+        metrics().add("fs_fallback_images_total", &labels, images as f64);
+        metrics().add("fs_fallback_tasks_total", &labels, tasks as f64);
+        metrics().add("fs_fallback_spend_total", &labels, spent);
+        metrics().set("fs_fallback_spend_today", &[], spent_today);
+
+        if let Some(budget) = self.config().daily_budget {
+            if spent_today >= budget && spent_today - spent < budget {
+                tracing::warn!(
+                    "Fallback daily budget of {} exhausted, the fallback is disabled until tomorrow",
+                    budget
+                );
+            }
+        }
+    }
+
+    /// Record the balance reported by a provider.
+    pub fn set_balance(&self, provider: &str, balance: f64) {
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        inner
+            .providers
+            .entry(provider.to_owned())
+            .or_insert_with(|| ProviderSpend {
+                name: provider.to_owned(),
+                ..Default::default()
+            })
+            .balance = Some(balance);
+        metrics().set("fs_fallback_balance", &[("provider", provider)], balance);
+    }
+
+    /// Returns the spend of the providers.
+    pub fn status(&self) -> SpendStatus {
+        let daily_budget = self.config().daily_budget;
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        inner.roll();
+        SpendStatus {
+            daily_budget,
+            spent_today: inner.spent_today,
+            exhausted: daily_budget.is_some_and(|budget| inner.spent_today >= budget),
+            providers: inner.providers.values().cloned().collect(),
+        }
+    }
+}
+
+/// Returns the current UTC day since the epoch.
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 86400
+}
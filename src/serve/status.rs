@@ -0,0 +1,42 @@
+use super::{breaker::BreakerStatus, feedback::AccuracyStatus, spend::SpendStatus};
+use crate::onnx::ModelInfo;
+use serde::{Deserialize, Serialize};
+
+/// Status query parameters
+#[derive(Deserialize)]
+pub struct StatusQuery {
+    /// API key
+    pub api_key: Option<String>,
+}
+
+/// Server status
+#[derive(Serialize)]
+pub struct Status {
+    /// Server version
+    pub version: &'static str,
+    /// Loaded predictors
+    pub predictors: Vec<PredictorStatus>,
+    /// Rolling accuracy reported through the feedback endpoint
+    pub accuracy: Vec<AccuracyStatus>,
+    /// Circuit breakers of the local models and fallback providers
+    pub breakers: Vec<BreakerStatus>,
+    /// Spend and balances of the fallback providers
+    pub spend: SpendStatus,
+}
+
+/// Status of a loaded predictor
+#[derive(Serialize)]
+pub struct PredictorStatus {
+    /// Game variant, e.g. "3d_rollball_objects"
+    pub game_variant: String,
+    /// Index of the instruction route, if the predictor is routed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route: Option<usize>,
+    /// Whether the predictor is the candidate of an experiment
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub candidate: bool,
+    /// Whether the predictor is active
+    pub active: bool,
+    /// Models backing the predictor
+    pub models: Vec<ModelInfo>,
+}
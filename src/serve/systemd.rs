@@ -0,0 +1,106 @@
+//! systemd integration: readiness and watchdog notifications through
+//! `NOTIFY_SOCKET` and socket activation through `LISTEN_FDS`.
+
+use std::{
+    ffi::OsStr,
+    net::TcpListener,
+    os::unix::{
+        ffi::OsStrExt,
+        io::{FromRawFd, RawFd},
+        net::UnixDatagram,
+    },
+    time::Duration,
+};
+
+/// The first file descriptor passed by socket activation.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Send a state notification, e.g. `READY=1`, to the service manager.
+/// Returns false if the process isn't run by systemd with `Type=notify`.
+pub fn notify(state: &str) -> std::io::Result<bool> {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+
+    let socket = UnixDatagram::unbound()?;
+    match path.as_bytes() {
+        #[cfg(target_os = "linux")]
+        [b'@', name @ ..] => {
+            use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+            let addr = SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &addr)?;
+        }
+        path => {
+            socket.send_to(state.as_bytes(), OsStr::from_bytes(path))?;
+        }
+    }
+    Ok(true)
+}
+
+/// Send a state notification, logging failures.
+pub fn notify_or_log(state: &str) {
+    if let Err(err) = notify(state) {
+        tracing::warn!("Failed to notify systemd of {}: {}", state, err);
+    }
+}
+
+/// Returns the number of sockets passed by socket activation, if they were
+/// passed to this process.
+pub fn listen_fds() -> Option<usize> {
+    let pid = std::env::var("LISTEN_PID").ok()?.parse::<u32>().ok()?;
+    if pid != std::process::id() {
+        return None;
+    }
+    std::env::var("LISTEN_FDS")
+        .ok()?
+        .parse()
+        .ok()
+        .filter(|&fds| fds > 0)
+}
+
+/// Take the first socket passed by socket activation, the server then
+/// listens on it instead of binding the address.
+pub fn listener() -> Option<TcpListener> {
+    let fds = listen_fds()?;
+    if fds > 1 {
+        tracing::warn!("{} sockets passed, listening on the first only", fds);
+    }
+
+    // The sockets aren't passed on to child processes
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+
+    // SAFETY: the service manager passed the socket at this descriptor to this
+    // process, nothing else owns it
+    let listener = unsafe { TcpListener::from_raw_fd(LISTEN_FDS_START) };
+    if let Err(err) = listener.set_nonblocking(true) {
+        tracing::warn!("Failed to make the activated socket non-blocking: {}", err);
+    }
+    Some(listener)
+}
+
+/// Returns the interval the watchdog must be notified at, half its timeout,
+/// if the watchdog is enabled for this process.
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+    let usec = std::env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
+
+/// Notify the watchdog until the process exits.
+pub async fn watchdog() {
+    let Some(interval) = watchdog_interval() else {
+        return;
+    };
+
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        notify_or_log("WATCHDOG=1");
+    }
+}
@@ -5,7 +5,7 @@ use serde::{
     Deserialize, Deserializer, Serialize,
 };
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct Task {
     /// API key
     pub api_key: Option<String>,
@@ -49,6 +49,14 @@ where
 }
 #[derive(Serialize, typed_builder::TypedBuilder)]
 pub struct TaskResult {
+    /// request id, from the `X-Request-Id` header or generated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default = super::request_id::current(), setter(strip_option))]
+    request_id: Option<String>,
+    /// task id, used to report feedback on the answers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    task_id: Option<String>,
     /// error message, if any
     #[serde(skip_serializing_if = "Option::is_none")]
     #[builder(default, setter(strip_option))]
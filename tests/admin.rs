@@ -0,0 +1,119 @@
+#![cfg(target_family = "unix")]
+
+mod common;
+
+use common::dir;
+use fs::{
+    daemon::format_uptime,
+    error::Error,
+    serve::{
+        admin::{self, AdminStatus, FallbackUsage, ModelStatus, VariantRequests},
+        metrics::metrics,
+    },
+    DaemonPaths,
+};
+fn status() -> AdminStatus {
+    AdminStatus {
+        pid: std::process::id(),
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        uptime: 3723,
+        bind: Some("127.0.0.1:8000".to_owned()),
+        in_flight: 2,
+        models: vec![ModelStatus {
+            game_variant: "3d_rollball_objects".to_owned(),
+            model: "3d_rollball_objects.onnx".to_owned(),
+            version: Some("2".to_owned()),
+            active: true,
+        }],
+        requests: vec![VariantRequests {
+            game_variant: "3d_rollball_objects".to_owned(),
+            ok: 10,
+            error: 1,
+        }],
+        fallbacks: vec![FallbackUsage {
+            provider: "capsolver".to_owned(),
+            tasks: 1,
+            images: 3,
+            spend: 2.4,
+        }],
+    }
+}
+
+#[tokio::test]
+async fn test_admin_socket() {
+    let dir = dir("admin");
+    let path = dir.join("fs.sock");
+
+    // A socket file left by a server that is gone is replaced
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    let listener = admin::bind(&path).unwrap();
+    tokio::spawn(admin::serve(listener, |command| async move {
+        match command.as_str() {
+            "status" => Ok(serde_json::to_string(&status())?),
+            _ => Err(Error::AdminError(format!(
+                "Unknown admin command {command:?}"
+            ))),
+        }
+    }));
+
+    // Another server can't take over the socket of a running one
+    assert!(matches!(admin::bind(&path), Err(Error::AdminError(_))));
+
+    let answer = tokio::task::spawn_blocking({
+        let path = path.clone();
+        move || {
+            (
+                admin::status(&path).unwrap(),
+                admin::query(&path, "reboot").unwrap_err(),
+            )
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(answer.0, status());
+    assert_eq!(answer.1.to_string(), "Unknown admin command \"reboot\"");
+
+    // Only the current user can connect
+    use std::os::unix::fs::PermissionsExt;
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_admin_unreachable() {
+    let dir = dir("admin-unreachable");
+    let paths = DaemonPaths {
+        pid_file: Some(dir.join("fs-8001.pid")),
+        ..Default::default()
+    };
+    assert_eq!(paths.admin_socket(), dir.join("fs-8001.sock"));
+
+    // `fs ps` falls back to the process view on I/O errors
+    assert!(matches!(
+        admin::status(&paths.admin_socket()),
+        Err(Error::IoError(_))
+    ));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_admin_format() {
+    assert_eq!(format_uptime(0), "0s");
+    assert_eq!(format_uptime(59), "59s");
+    assert_eq!(format_uptime(3723), "1h 2m 3s");
+    assert_eq!(format_uptime(86400 + 5), "1d 0h 0m 5s");
+
+    metrics().add("fs_admin_test_total", &[("provider", "a")], 2.0);
+    metrics().add("fs_admin_test_total", &[("provider", "b")], 1.0);
+    assert_eq!(
+        metrics().family("fs_admin_test_total"),
+        [
+            (vec![("provider".to_owned(), "a".to_owned())], 2.0),
+            (vec![("provider".to_owned(), "b".to_owned())], 1.0),
+        ]
+    );
+    assert!(metrics().family("fs_admin_missing").is_empty());
+}
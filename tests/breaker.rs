@@ -0,0 +1,124 @@
+mod common;
+
+use base64::{engine::general_purpose, Engine as _};
+use common::{boot_args, dir};
+use fs::{
+    error::Error,
+    serve::{AppState, BreakerConfig, BreakerState, Breakers, CircuitBreaker, Task},
+};
+use image::{DynamicImage, ImageFormat, RgbImage};
+use std::{io::Cursor, sync::Arc};
+
+#[test]
+fn test_breaker_transitions() {
+    let breaker = CircuitBreaker::new(
+        "fallback",
+        "capsolver",
+        BreakerConfig {
+            failures: 2,
+            cooldown: 0,
+        },
+    );
+    assert!(breaker.allow());
+
+    breaker.failure();
+    assert_eq!(breaker.status().state, BreakerState::Closed);
+    breaker.failure();
+    assert_eq!(breaker.status().state, BreakerState::Open);
+
+    // The cooldown elapsed, a failed trial reopens the circuit
+    assert!(breaker.allow());
+    assert_eq!(breaker.status().state, BreakerState::HalfOpen);
+    breaker.failure();
+    assert_eq!(breaker.status().state, BreakerState::Open);
+
+    // A successful trial closes it
+    assert!(breaker.allow());
+    breaker.success();
+    assert_eq!(breaker.status().state, BreakerState::Closed);
+    assert_eq!(breaker.status().failures, 0);
+}
+
+#[test]
+fn test_breaker_open() {
+    let breakers = Breakers::new(BreakerConfig {
+        failures: 1,
+        cooldown: 60,
+    });
+    breakers.variant("counting").failure();
+    assert!(!breakers.variant("counting").allow());
+    assert!(breakers.fallback("counting").allow());
+
+    let status = breakers.status();
+    assert_eq!(status.len(), 2);
+    assert_eq!(
+        (status[0].kind, status[0].state),
+        ("fallback", BreakerState::Closed)
+    );
+    assert_eq!(
+        (status[1].kind, status[1].state),
+        ("variant", BreakerState::Open)
+    );
+    // A reloaded configuration applies to the existing circuits, which keep
+    // their state
+    breakers.configure(BreakerConfig {
+        failures: 2,
+        cooldown: 60,
+    });
+    assert!(!breakers.variant("counting").allow());
+    breakers.fallback("counting").failure();
+    assert!(breakers.fallback("counting").allow());
+    breakers.fallback("counting").failure();
+    assert!(!breakers.fallback("counting").allow());
+}
+
+#[tokio::test]
+async fn test_breaker_local_failures() {
+    // The model can't be fetched, every task it is asked to answer fails
+    let dir = dir("breaker");
+    let config = dir.join("config.json");
+    std::fs::write(
+        &config,
+        r#"{ "breaker": { "failures": 2, "cooldown": 60 } }"#,
+    )
+    .unwrap();
+    let state = AppState::new(boot_args(&config)).await.unwrap();
+    let task = |width, height| {
+        let mut bytes = Cursor::new(vec![]);
+        DynamicImage::ImageRgb8(RgbImage::new(width, height))
+            .write_to(&mut bytes, ImageFormat::Png)
+            .unwrap();
+        Task {
+            api_key: None,
+            images: vec![Arc::new(
+                general_purpose::STANDARD.encode(bytes.into_inner()),
+            )],
+            game_variant_instructions: ("counting".to_owned(), "Pick the image".to_owned()),
+        }
+    };
+    let error = |result: fs::Result<_>| match result {
+        Ok(_) => panic!("expected the task to fail"),
+        Err(err) => err,
+    };
+
+    // Client errors don't count
+    for _ in 0..3 {
+        let err = error(state.settings().process(&task(400, 200)).await);
+        assert!(matches!(err, Error::InvalidImageSize(_)));
+    }
+
+    // Local failures open the circuit
+    for _ in 0..2 {
+        let err = error(state.settings().process(&task(300, 200)).await);
+        assert!(err.status().is_server_error());
+    }
+    let err = error(state.settings().process(&task(300, 200)).await);
+    assert!(matches!(err, Error::CircuitOpen(_)));
+
+    // Model and tensor errors are server errors
+    let shape = ndarray::ShapeError::from_kind(ndarray::ErrorKind::IncompatibleShape);
+    assert!(Error::ShapeError(shape).status().is_server_error());
+    assert!(Error::InvalidModelOutput(0).status().is_server_error());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use common::dir;
+use fs::{
+    onnx::{ModelInfo, Prediction, Predictor},
+    serve::{metrics::metrics, CacheConfig, MemoryConfig, ResultCache, TileMemory},
+};
+
+fn prediction(answer: i32) -> Prediction {
+    Prediction {
+        answer,
+        scores: vec![vec![0.1, 0.9]],
+    }
+}
+
+#[test]
+fn test_cache_lru() {
+    let cache = ResultCache::new(CacheConfig {
+        capacity: 2,
+        ..Default::default()
+    });
+
+    // The key is the decoded image, a data URL prefix doesn't change it
+    let key = |image| ResultCache::key("lru", "v1", image).unwrap();
+    assert_eq!(key("aGVsbG8="), key("data:image/png;base64,aGVsbG8="));
+    assert_ne!(key("aGVsbG8="), key("d29ybGQ="));
+    assert_ne!(
+        key("aGVsbG8="),
+        ResultCache::key("lru", "v2", "aGVsbG8=").unwrap()
+    );
+    assert!(ResultCache::key("lru", "v1", "not base64!").is_none());
+
+    cache.insert("lru", "v1", key("YQ=="), prediction(1));
+    cache.insert("lru", "v1", key("Yg=="), prediction(2));
+    assert_eq!(cache.get("lru", &key("YQ==")), Some(prediction(1)));
+
+    // The least recently used entry is evicted
+    cache.insert("lru", "v1", key("Yw=="), prediction(3));
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get("lru", &key("Yg==")), None);
+    assert_eq!(cache.get("lru", &key("YQ==")), Some(prediction(1)));
+    assert_eq!(cache.get("lru", &key("Yw==")), Some(prediction(3)));
+    assert_eq!(
+        metrics().get("fs_cache_hits_total", &[("variant", "lru")]),
+        3.0
+    );
+    assert_eq!(
+        metrics().get("fs_cache_misses_total", &[("variant", "lru")]),
+        1.0
+    );
+
+    // Loading a new model drops the results of the old one
+    cache.insert("lru", "v2", key("Yg=="), prediction(2));
+    cache.retain("lru", &["v2".to_owned()]);
+    assert_eq!(cache.len(), 1);
+    assert_eq!(cache.get("lru", &key("Yg==")), Some(prediction(2)));
+
+    // Expired entries are not used
+    let expired = ResultCache::new(CacheConfig {
+        ttl: 0,
+        ..Default::default()
+    });
+    expired.insert("lru", "v1", key("YQ=="), prediction(1));
+    assert_eq!(expired.get("lru", &key("YQ==")), None);
+    assert!(expired.is_empty());
+}
+
+/// A predictor backed by the given models.
+struct Models(Vec<ModelInfo>);
+
+impl Predictor for Models {
+    fn prediction(&self, _: image::DynamicImage) -> fs::Result<Prediction> {
+        Ok(prediction(0))
+    }
+
+    fn active(&self) -> bool {
+        true
+    }
+
+    fn models(&self) -> Vec<ModelInfo> {
+        self.0.clone()
+    }
+}
+
+#[test]
+fn test_cache_fingerprint() {
+    let model = |model: &str, sha256: Option<&str>| ModelInfo {
+        model: model.to_owned(),
+        version: Some("v1".to_owned()),
+        sha256: sha256.map(str::to_owned),
+        ..Default::default()
+    };
+
+    // A model file replaced in place keeps its name and version, not its hash
+    let before = Models(vec![model("a.onnx", Some("aa")), model("b.onnx", None)]);
+    let after = Models(vec![model("a.onnx", Some("cc")), model("b.onnx", None)]);
+    assert_eq!(before.version(), after.version());
+    assert_eq!(before.fingerprint(), "aa+b.onnx");
+    assert_eq!(after.fingerprint(), "cc+b.onnx");
+
+    // The results of the replaced model are dropped once the new one is loaded
+    let cache = ResultCache::new(CacheConfig::default());
+    let key = |predictor: &Models| {
+        ResultCache::key("fingerprint", &predictor.fingerprint(), "YQ==").unwrap()
+    };
+    assert_ne!(key(&before), key(&after));
+    cache.insert(
+        "fingerprint",
+        &before.fingerprint(),
+        key(&before),
+        prediction(1),
+    );
+    cache.retain("fingerprint", &[after.fingerprint()]);
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn test_cache_persist() {
+    let dir = dir("cache");
+    let config = CacheConfig {
+        path: Some(dir.join("cache.json")),
+        ..Default::default()
+    };
+
+    let key = ResultCache::key("persist", "v1", "YQ==").unwrap();
+    let cache = ResultCache::new(config.clone());
+    assert!(cache.is_empty());
+    cache.insert("persist", "v1", key.clone(), prediction(4));
+    cache.save().unwrap();
+
+    let loaded = ResultCache::new(config);
+    assert_eq!(loaded.get("persist", &key), Some(prediction(4)));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_cache_persist_with_memory() {
+    let dir = dir("cache-memory");
+
+    // Files differing only in their extension are written through their own
+    // temporary files
+    let key = ResultCache::key("persist", "v1", "YQ==").unwrap();
+    let cache = ResultCache::new(CacheConfig {
+        path: Some(dir.join("fs.cache")),
+        ..Default::default()
+    });
+    cache.insert("persist", "v1", key.clone(), prediction(4));
+    let memory_config = MemoryConfig {
+        path: Some(dir.join("fs.memory")),
+        ..Default::default()
+    };
+    let memory = TileMemory::new(memory_config.clone());
+    memory.remember("counting", "Pick the image", vec![1, 2, 3], 2);
+    cache.save().unwrap();
+    memory.save().unwrap();
+
+    let mut files: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    files.sort();
+    assert_eq!(files, ["fs.cache", "fs.memory"]);
+
+    let loaded = ResultCache::new(CacheConfig {
+        path: Some(dir.join("fs.cache")),
+        ..Default::default()
+    });
+    assert_eq!(loaded.get("persist", &key), Some(prediction(4)));
+    let loaded = TileMemory::new(memory_config);
+    assert_eq!(
+        loaded.lookup("counting", "Pick the image", &[1, 2, 3]),
+        Some(2)
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
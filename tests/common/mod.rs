@@ -0,0 +1,27 @@
+// Each test crate uses some of the helpers only
+#![allow(dead_code)]
+
+use clap::Parser;
+use fs::{BootArgs, Commands, Opt};
+use std::path::{Path, PathBuf};
+
+/// Create an empty directory for the test.
+pub fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("fs-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Boot arguments of a server reading the configuration file.
+pub fn boot_args(config: &Path) -> BootArgs {
+    let argv = ["fs", "run", "--limit", "1", "--config"]
+        .into_iter()
+        .map(Into::into)
+        .chain([config.as_os_str().to_owned()])
+        .chain(["github", "--url", "https://example.com/models"].map(Into::into));
+    match Opt::try_parse_from(argv).unwrap().commands {
+        Commands::Run(args) => args,
+        _ => unreachable!(),
+    }
+}
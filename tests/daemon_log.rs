@@ -0,0 +1,195 @@
+#![cfg(target_family = "unix")]
+
+mod common;
+
+use common::dir;
+use fs::daemon::{
+    log::{level, tail, Follower, LevelFilter},
+    rotate::{rotate, rotated, LogFile, RotatePolicy},
+};
+use std::{io::Write, os::unix::io::AsRawFd, time::Duration};
+use tracing::Level;
+
+fn read(path: &std::path::Path) -> String {
+    std::fs::read_to_string(path).unwrap_or_default()
+}
+
+#[test]
+fn test_rotate_retention() {
+    let dir = dir("rotate");
+    let path = dir.join("fs.out");
+
+    for run in 0..4 {
+        std::fs::write(&path, format!("run {run}")).unwrap();
+        rotate(&path, 2).unwrap();
+    }
+    assert!(!path.exists());
+    assert_eq!(read(&rotated(&path, 1)), "run 3");
+    assert_eq!(read(&rotated(&path, 2)), "run 2");
+    assert!(!rotated(&path, 3).exists());
+
+    // A smaller retention drops the older files
+    std::fs::write(&path, "run 4").unwrap();
+    rotate(&path, 1).unwrap();
+    assert_eq!(read(&rotated(&path, 1)), "run 4");
+    assert!(!rotated(&path, 2).exists());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_rotate_unprivileged() {
+    use nix::{
+        sys::wait::{waitpid, WaitStatus},
+        unistd::{fork, setgid, setuid, ForkResult, User},
+    };
+    use std::os::unix::fs::MetadataExt;
+
+    // Only root can hand the logs to another user
+    if !nix::unistd::Uid::effective().is_root() {
+        return;
+    }
+    let user = User::from_name("nobody").unwrap().unwrap();
+    let dir = dir("rotate-owner");
+    let path = dir.join("fs.out");
+    std::fs::write(rotated(&path, 1), "previous run").unwrap();
+    writeln!(fs::daemon::rotate::open(&path).unwrap(), "current run").unwrap();
+
+    // Rotate as the user in a child process, as the daemon does once it drops its privileges
+    let rotate_as_user = || match unsafe { fork() }.unwrap() {
+        ForkResult::Child => {
+            let rotated = setgid(user.gid).is_ok()
+                && setuid(user.uid).is_ok()
+                && rotate(&path, 3).is_ok()
+                && fs::daemon::rotate::open(&path).is_ok();
+            std::process::exit(if rotated { 0 } else { 1 })
+        }
+        ForkResult::Parent { child } => {
+            waitpid(child, None).unwrap() == WaitStatus::Exited(child, 0)
+        }
+    };
+
+    // The user can't rotate the logs root created
+    assert!(!rotate_as_user());
+    assert_eq!(read(&path), "current run\n");
+
+    // It can once they are handed over
+    fs::daemon::rotate::chown([path.as_path(), dir.as_path()], &user).unwrap();
+    assert!(rotate_as_user());
+    assert_eq!(read(&rotated(&path, 1)), "current run\n");
+    assert_eq!(read(&rotated(&path, 2)), "previous run");
+    assert_eq!(std::fs::metadata(&path).unwrap().uid(), user.uid.as_raw());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_rotate_redirects_descriptor() {
+    let dir = dir("redirect");
+    let path = dir.join("fs.err");
+    let mut file = fs::daemon::rotate::open(&path).unwrap();
+    let mut log = LogFile::new(path.clone(), file.as_raw_fd());
+
+    let by_size = RotatePolicy {
+        max_size: 8,
+        interval: None,
+        retention: 3,
+    };
+    assert!(!log.due(&by_size));
+    writeln!(file, "first line").unwrap();
+    assert!(log.due(&by_size));
+
+    log.rotate(&by_size).unwrap();
+    assert!(!log.due(&by_size));
+    writeln!(file, "second").unwrap();
+    assert_eq!(read(&rotated(&path, 1)), "first line\n");
+    assert_eq!(read(&path), "second\n");
+
+    let by_age = RotatePolicy {
+        max_size: 0,
+        interval: Some(Duration::ZERO),
+        retention: 3,
+    };
+    assert!(log.due(&by_age));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_log_level() {
+    assert_eq!(
+        level("2024-05-01T00:00:00.000000Z  WARN fs::serve: Fallback failed"),
+        Some(Level::WARN)
+    );
+    assert_eq!(
+        level("\u{1b}[2m2024-05-01T00:00:00Z\u{1b}[0m \u{1b}[32m INFO\u{1b}[0m fs: Listening"),
+        Some(Level::INFO)
+    );
+    assert_eq!(
+        level(r#"{"timestamp":"2024-05-01T00:00:00Z","level":"ERROR","fields":{}}"#),
+        Some(Level::ERROR)
+    );
+    assert_eq!(level("   0: std::backtrace"), None);
+
+    // Continuation lines follow their entry
+    let mut filter = LevelFilter::new(Some(Level::WARN));
+    let kept = [
+        "2024-05-01T00:00:00Z  INFO fs: Listening",
+        "2024-05-01T00:00:01Z ERROR fs: Failed",
+        "  caused by: timeout",
+        "2024-05-01T00:00:02Z DEBUG fs: Details",
+        "  more details",
+    ]
+    .into_iter()
+    .filter(|line| filter.keep(line))
+    .collect::<Vec<_>>();
+    assert_eq!(
+        kept,
+        [
+            "2024-05-01T00:00:01Z ERROR fs: Failed",
+            "  caused by: timeout"
+        ]
+    );
+}
+
+#[test]
+fn test_log_tail() {
+    let lines = (0..5).map(|i| i.to_string()).collect::<Vec<_>>();
+    assert_eq!(tail(lines.clone(), Some(2)), ["3", "4"]);
+    assert_eq!(tail(lines.clone(), Some(10)).len(), 5);
+    assert!(tail(lines.clone(), Some(0)).is_empty());
+    assert_eq!(tail(lines, None).len(), 5);
+}
+
+#[test]
+fn test_log_follow() {
+    let dir = dir("follow");
+    let path = dir.join("fs.out");
+    let mut follower = Follower::new(path.clone());
+    assert!(follower.poll().unwrap().is_empty());
+
+    std::fs::write(&path, "a\nb\npart").unwrap();
+    assert_eq!(follower.poll().unwrap(), ["a", "b"]);
+
+    // The partial line is returned once complete
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap();
+    writeln!(file, "ial").unwrap();
+    assert_eq!(follower.poll().unwrap(), ["partial"]);
+
+    // Lines written before the rotation come first, then the new file
+    writeln!(file, "c").unwrap();
+    rotate(&path, 1).unwrap();
+    std::fs::write(&path, "d\n").unwrap();
+    assert_eq!(follower.poll().unwrap(), ["c", "d"]);
+
+    // Truncated files are read again from the start
+    std::fs::write(&path, "").unwrap();
+    assert!(follower.poll().unwrap().is_empty());
+    std::fs::write(&path, "e\n").unwrap();
+    assert_eq!(follower.poll().unwrap(), ["e"]);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
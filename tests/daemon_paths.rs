@@ -0,0 +1,74 @@
+#![cfg(target_family = "unix")]
+
+mod common;
+
+use common::dir;
+use fs::{
+    daemon::paths::{read_pid, running_pid, runtime_dir},
+    error::Error,
+    DaemonPaths,
+};
+use std::path::PathBuf;
+
+#[test]
+fn test_daemon_paths() {
+    let paths = DaemonPaths::default();
+    assert_eq!(paths.pid_file(), runtime_dir().join("fs.pid"));
+    assert_eq!(paths.stdout_log(), runtime_dir().join("fs.out"));
+    assert_eq!(paths.stderr_log(), runtime_dir().join("fs.err"));
+    if nix::unistd::Uid::effective().is_root() {
+        assert_eq!(runtime_dir(), PathBuf::from("/var/run/fs"));
+    }
+
+    let dir = dir("paths");
+    let paths = DaemonPaths {
+        pid_file: Some(dir.join("run/fs-8001.pid")),
+        stdout_log: Some(dir.join("log/fs-8001.out")),
+        stderr_log: None,
+    };
+    assert_eq!(paths.pid_file(), dir.join("run/fs-8001.pid"));
+    paths.create_dirs().unwrap();
+    assert!(dir.join("run").is_dir());
+    assert!(dir.join("log").is_dir());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_daemon_pid_file() {
+    let dir = dir("pid");
+    let path = dir.join("fs.pid");
+    assert_eq!(read_pid(&path).unwrap(), None);
+    assert_eq!(running_pid(&path).unwrap(), None);
+
+    // A corrupt PID file is an error, not a panic
+    for corrupt in ["", "abc", "-1", "\u{fffd}"] {
+        std::fs::write(&path, corrupt).unwrap();
+        assert!(matches!(running_pid(&path), Err(Error::InvalidPidFile(..))));
+    }
+
+    // The running fs, this test executable stands in for it
+    std::fs::write(&path, format!("{}\n", std::process::id())).unwrap();
+    assert_eq!(running_pid(&path).unwrap(), Some(std::process::id() as i32));
+    assert!(path.exists());
+
+    // The process is gone
+    let mut exited = std::process::Command::new("true").spawn().unwrap();
+    exited.wait().unwrap();
+    std::fs::write(&path, exited.id().to_string()).unwrap();
+    assert_eq!(running_pid(&path).unwrap(), None);
+    assert!(!path.exists());
+
+    // The PID was reused by another program
+    let mut other = std::process::Command::new("sleep")
+        .arg("5")
+        .spawn()
+        .unwrap();
+    std::fs::write(&path, other.id().to_string()).unwrap();
+    assert_eq!(running_pid(&path).unwrap(), None);
+    assert!(!path.exists());
+    other.kill().unwrap();
+    other.wait().unwrap();
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
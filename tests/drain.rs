@@ -0,0 +1,74 @@
+#![cfg(target_family = "unix")]
+
+use axum_server::Handle;
+use fs::serve::{drain::tracker, signal::graceful_shutdown};
+use nix::sys::signal::{raise, Signal};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+// The tracker and the signals are process-wide, so this is the only test.
+#[tokio::test]
+async fn test_drain() {
+    let tracker = tracker();
+    assert_eq!(tracker.in_flight(), 0);
+    assert!(tracker.wait(Duration::ZERO).await);
+
+    // Blocking calls and tasks are in flight until they complete
+    let (tx, rx) = std::sync::mpsc::channel::<()>();
+    let blocking = tracker.spawn_blocking(move || rx.recv().unwrap());
+    let task = tracker.spawn(tokio::time::sleep(Duration::from_millis(50)));
+    let guard = tracker.guard();
+    assert_eq!(tracker.in_flight(), 3);
+    assert!(!tracker.wait(Duration::from_millis(100)).await);
+    task.await.unwrap();
+    drop(guard);
+    assert_eq!(tracker.in_flight(), 1);
+    tx.send(()).unwrap();
+    assert!(tracker.wait(Duration::from_secs(5)).await);
+    blocking.await.unwrap();
+
+    // SIGHUP reloads, the server keeps running. The reload never completes.
+    let handle = Handle::new();
+    let reloads = Arc::new(AtomicUsize::new(0));
+    let shutdown = tokio::spawn(graceful_shutdown(
+        handle.clone(),
+        Duration::from_millis(500),
+        Duration::from_secs(7),
+        {
+            let reloads = reloads.clone();
+            move || {
+                reloads.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_secs(3600))
+            }
+        },
+    ));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    raise(Signal::SIGHUP).unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(reloads.load(Ordering::SeqCst), 1);
+    assert!(!shutdown.is_finished());
+    assert!(!tracker.draining());
+
+    // SIGCHLD no longer shuts the server down
+    std::process::Command::new("true").status().unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!shutdown.is_finished());
+
+    // SIGTERM drains without waiting for the reload, the server reports not
+    // ready but keeps listening for the grace period
+    raise(Signal::SIGTERM).unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(tracker.draining());
+    assert!(!shutdown.is_finished());
+    tokio::time::timeout(Duration::from_secs(5), shutdown)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(tracker.draining());
+    assert!(tracker.drain_started().is_some());
+}
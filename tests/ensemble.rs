@@ -0,0 +1,37 @@
+use fs::onnx::{Augment, Combine, Transform};
+use image::{DynamicImage, Rgb, RgbImage};
+
+#[test]
+fn test_ensemble_combine() {
+    let scores = vec![
+        vec![0.1, 0.9, 0.2],
+        vec![0.2, 0.3, 0.4],
+        vec![0.1, 0.2, 0.8],
+    ];
+    // Averages: 0.13, 0.47, 0.47, the first highest wins
+    assert_eq!(Combine::Average.answer(&scores), Some(1));
+    // Votes: one for 1, two for 2
+    assert_eq!(Combine::Vote.answer(&scores), Some(2));
+    assert_eq!(Combine::Average.answer(&[]), None);
+}
+
+#[test]
+fn test_ensemble_augment() {
+    assert_eq!(Augment::default().transforms(), vec![Transform::Identity]);
+
+    let augment = Augment {
+        shift: 2,
+        flip_horizontal: true,
+        brightness: vec![-10, 0, 10],
+        ..Default::default()
+    };
+    let transforms = augment.transforms();
+    assert_eq!(transforms.len(), 8);
+    assert_eq!(transforms[1], Transform::Shift(2, 0));
+
+    let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(10, 8, Rgb([100, 100, 100])));
+    let shifted = Transform::Shift(0, -2).apply(image.clone());
+    assert_eq!((shifted.width(), shifted.height()), (10, 6));
+    let brightened = Transform::Brighten(10).apply(image).into_rgb8();
+    assert_eq!(brightened.get_pixel(0, 0), &Rgb([110, 110, 110]));
+}
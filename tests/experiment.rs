@@ -0,0 +1,131 @@
+mod common;
+
+use base64::{engine::general_purpose, Engine as _};
+use common::{boot_args, dir};
+use fs::{
+    config::Config,
+    serve::{
+        metrics::{metrics, Metrics},
+        AppState, ExperimentMode, Task,
+    },
+};
+use std::sync::Arc;
+
+const CONFIG: &str = r#"{
+    "experiments": {
+        "3d_rollball_objects": {
+            "model": "3d_rollball_objects_v2.onnx",
+            "save_dir": "/tmp/shadow"
+        },
+        "hopscotch_highsec": {
+            "mode": "split",
+            "weight": 1.0,
+            "model": "hopscotch_highsec_v2.onnx"
+        }
+    }
+}"#;
+
+#[test]
+fn test_experiment_config() {
+    let config: Config = serde_json::from_str(CONFIG).unwrap();
+
+    let shadow = &config.experiments["3d_rollball_objects"];
+    assert_eq!(shadow.mode, ExperimentMode::Shadow);
+    assert_eq!(
+        shadow.candidate.model.as_deref(),
+        Some("3d_rollball_objects_v2.onnx")
+    );
+    assert!(!shadow.split());
+
+    let mut split = config.experiments["hopscotch_highsec"].clone();
+    assert_eq!(split.mode, ExperimentMode::Split);
+    assert!(split.split());
+    split.weight = 0.0;
+    assert!(!split.split());
+}
+
+#[test]
+fn test_experiment_metrics() {
+    let metrics = Metrics::default();
+    let labels = [("variant", "counting"), ("version", "counting.onnx")];
+    metrics.inc("fs_predictions_total", &labels);
+    metrics.add("fs_predictions_total", &labels, 2.0);
+    metrics.set("fs_in_flight", &[], 4.0);
+
+    assert_eq!(metrics.get("fs_predictions_total", &labels), 3.0);
+    assert_eq!(metrics.get("fs_predictions_total", &labels[..1]), 0.0);
+    assert_eq!(
+        metrics.render(),
+        "# TYPE fs_in_flight gauge\nfs_in_flight 4\n\
+         # TYPE fs_predictions_total counter\n\
+         fs_predictions_total{variant=\"counting\",version=\"counting.onnx\"} 3\n"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_experiment_routed() {
+    // The candidate answers every counting task, except the routed ones
+    let dir = dir("experiment");
+    let config = dir.join("config.json");
+    std::fs::write(
+        &config,
+        r#"{
+            "routes": [
+                {
+                    "game_variant": "counting",
+                    "instructions": "Pick the routed image",
+                    "answers": [5, 4, 3, 2, 1, 0]
+                }
+            ],
+            "experiments": {
+                "counting": { "mode": "split", "weight": 1.0, "model": "counting.onnx" }
+            }
+        }"#,
+    )
+    .unwrap();
+    let mut args = boot_args(&config);
+    args.store = fs::onnx::Config::Github {
+        url: "https://github.com/0x676e67/fs/releases/download/model".to_owned(),
+    };
+    let state = AppState::new(args).await.unwrap();
+
+    let image = "tests/data/counting/0a1d5e94-8187-4124-a999-3ab7af6cb5e3";
+    let label: i32 = std::fs::read_to_string(format!("{image}.txt"))
+        .unwrap()
+        .trim()
+        .parse()
+        .unwrap();
+    let task = |instructions: &str| Task {
+        api_key: None,
+        images: vec![Arc::new(
+            general_purpose::STANDARD.encode(std::fs::read(format!("{image}.jpg")).unwrap()),
+        )],
+        game_variant_instructions: ("counting".to_owned(), instructions.to_owned()),
+    };
+    let objects = |result: fs::Result<axum::Json<_>>| {
+        serde_json::to_value(result.unwrap().0).unwrap()["objects"].clone()
+    };
+    let candidate = || {
+        metrics()
+            .render()
+            .lines()
+            .filter(|line| line.starts_with("fs_predictions_total"))
+            .any(|line| {
+                line.contains(r#"variant="counting""#) && line.contains(r#"arm="candidate""#)
+            })
+    };
+
+    // The routed task is answered by the route, mapped through its answers
+    let routed = state
+        .settings()
+        .process(&task("Pick the routed image"))
+        .await;
+    assert_eq!(objects(routed), serde_json::json!([5 - label]));
+    assert!(!candidate());
+
+    let unrouted = state.settings().process(&task("Pick the image")).await;
+    assert_eq!(objects(unrouted), serde_json::json!([label]));
+    assert!(candidate());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
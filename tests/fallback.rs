@@ -0,0 +1,356 @@
+use axum::{http::StatusCode, routing::post, Json, Router};
+use fs::{
+    error::Error,
+    serve::{
+        backoff, chain, metrics::metrics, validate, FallbackConfig, FallbackOrder, FallbackSolver,
+        Spend, Task,
+    },
+};
+use serde_json::{json, Value};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Start a stand-in provider, `/ok` answers 2 for every image, `/fail` errors,
+/// `/flaky` fails with 503 every other request, `/echo` answers the image
+/// parsed as a number after as many tens of milliseconds as the image is
+/// smaller than 5, `/picky` answers the images that are numbers and rejects
+/// the others, `/slow` never answers in time, `/short` answers one image per
+/// task and `/getBalance` reports a balance of 7.5.
+async fn provider() -> String {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let app = Router::new()
+        .route(
+            "/ok",
+            post(|Json(body): Json<Value>| async move {
+                let images = body["task"]["images"].as_array().map_or(1, Vec::len);
+                Json(json!({ "errorId": 0, "status": "ready", "solution": { "objects": vec![2; images] } }))
+            }),
+        )
+        .route(
+            "/fail",
+            post(|| async { Json(json!({ "errorId": 1, "errorDescription": "ERROR_KEY_DENIED" })) }),
+        )
+        .route(
+            "/flaky",
+            post(move || async move {
+                match requests.fetch_add(1, Ordering::Relaxed) % 2 {
+                    0 => Err(StatusCode::SERVICE_UNAVAILABLE),
+                    _ => Ok(Json(json!({ "errorId": 0, "solution": { "objects": [3] } }))),
+                }
+            }),
+        )
+        .route(
+            "/echo",
+            post(|Json(body): Json<Value>| async move {
+                let image: u64 = body["task"]["image"].as_str().unwrap().parse().unwrap();
+                tokio::time::sleep(Duration::from_millis(10 * 5u64.saturating_sub(image))).await;
+                Json(json!({ "errorId": 0, "solution": { "objects": [image] } }))
+            }),
+        )
+        .route(
+            "/picky",
+            post(|Json(body): Json<Value>| async move {
+                match body["task"]["image"].as_str().unwrap().parse::<u64>() {
+                    Ok(image) => Json(json!({ "errorId": 0, "solution": { "objects": [image] } })),
+                    Err(_) => Json(json!({ "errorId": 1, "errorDescription": "ERROR_INVALID_IMAGE" })),
+                }
+            }),
+        )
+        .route(
+            "/short",
+            post(|| async { Json(json!({ "errorId": 0, "solution": { "objects": [0] } })) }),
+        )
+        .route(
+            "/getBalance",
+            post(|| async { Json(json!({ "errorId": 0, "balance": 7.5 })) }),
+        )
+        .route(
+            "/slow",
+            post(|| async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Json(json!({ "errorId": 0 }))
+            }),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+fn solver(config: Value) -> FallbackSolver {
+    solver_with_spend(config, Arc::default())
+}
+
+fn solver_with_spend(config: Value, spend: Arc<Spend>) -> FallbackSolver {
+    let config: FallbackConfig = serde_json::from_value(config).unwrap();
+    FallbackSolver::from_config(config, spend).unwrap()
+}
+
+fn task(images: usize) -> Task {
+    Task {
+        api_key: None,
+        images: (0..images).map(|_| Arc::new("image".to_owned())).collect(),
+        game_variant_instructions: ("counting".to_owned(), "Pick the image".to_owned()),
+    }
+}
+
+#[test]
+fn test_fallback_chain() {
+    let solvers = vec![
+        Arc::new(solver(
+            json!({ "provider": "capsolver", "key": "a", "cost": 2.0 }),
+        )),
+        Arc::new(solver(
+            json!({ "provider": "yescaptcha", "key": "b", "cost": 1.0, "variants": ["card"] }),
+        )),
+        Arc::new(solver(
+            json!({ "provider": "capsolver", "name": "cheap", "key": "c", "cost": 1.0 }),
+        )),
+    ];
+
+    let names = |order| {
+        chain(&solvers, "counting", order)
+            .into_iter()
+            .map(|solver| solver.name().to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(FallbackOrder::Ordered), ["capsolver", "cheap"]);
+    assert_eq!(names(FallbackOrder::Cheapest), ["cheap", "capsolver"]);
+    assert_eq!(chain(&solvers, "card", FallbackOrder::Cheapest).len(), 3);
+}
+
+#[tokio::test]
+async fn test_fallback_solve() {
+    let endpoint = provider().await;
+
+    let ok = solver(json!({
+        "provider": "capsolver",
+        "key": "key",
+        "endpoint": format!("{endpoint}/ok"),
+        "limit": 2
+    }));
+    assert_eq!(ok.solve(&task(3)).await.unwrap(), vec![2, 2, 2]);
+
+    let fail = solver(json!({
+        "provider": "yescaptcha",
+        "key": "key",
+        "endpoint": format!("{endpoint}/fail")
+    }));
+    assert!(matches!(
+        fail.solve(&task(1)).await,
+        Err(Error::FallbackSolverError(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_fallback_retry() {
+    let endpoint = provider().await;
+
+    let flaky = |retries| {
+        solver(json!({
+            "provider": "yescaptcha",
+            "name": format!("flaky-{retries}"),
+            "key": "key",
+            "endpoint": format!("{endpoint}/flaky"),
+            "retries": retries,
+            "backoff": 1,
+            "concurrency": 1
+        }))
+    };
+
+    // Every failed submission is retried once
+    assert_eq!(flaky(1).solve(&task(2)).await.unwrap(), vec![3, 3]);
+    assert_eq!(
+        metrics().get("fs_fallback_retries_total", &[("provider", "flaky-1")]),
+        2.0
+    );
+
+    // The 503 isn't retried
+    assert!(matches!(
+        flaky(0).solve(&task(1)).await,
+        Err(Error::ReqwestError(_))
+    ));
+
+    for attempt in 0..4 {
+        let delay = backoff(Duration::from_millis(100), attempt);
+        let base = Duration::from_millis(100 << attempt);
+        assert!(delay >= base / 2 && delay <= base * 3 / 2);
+    }
+    assert!(backoff(Duration::from_secs(1), 30) <= Duration::from_secs(45));
+}
+
+#[tokio::test]
+async fn test_fallback_concurrency() {
+    let endpoint = provider().await;
+
+    let echo = solver(json!({
+        "provider": "yescaptcha",
+        "key": "key",
+        "endpoint": format!("{endpoint}/echo"),
+        "concurrency": 5
+    }));
+    let numbered = Task {
+        api_key: None,
+        images: (0..5).map(|i| Arc::new(i.to_string())).collect(),
+        game_variant_instructions: ("counting".to_owned(), "Pick the image".to_owned()),
+    };
+    // The later images are answered first, the answers keep the image order
+    assert_eq!(echo.solve(&numbered).await.unwrap(), vec![0, 1, 2, 3, 4]);
+
+    // Abandoning the task cancels the submissions
+    let slow = solver(json!({
+        "provider": "yescaptcha",
+        "name": "slow",
+        "key": "key",
+        "endpoint": format!("{endpoint}/slow")
+    }));
+    let abandoned = tokio::time::timeout(Duration::from_millis(50), slow.solve(&task(2))).await;
+    assert!(abandoned.is_err());
+    assert_eq!(
+        metrics().get("fs_fallback_cancelled_total", &[("provider", "slow")]),
+        1.0
+    );
+}
+
+#[tokio::test]
+async fn test_fallback_balance() {
+    let endpoint = provider().await;
+
+    // The balance endpoint is derived from the `createTask` endpoint
+    let derived = solver(json!({
+        "provider": "capsolver",
+        "key": "key",
+        "endpoint": format!("{endpoint}/createTask"),
+        "limit": 2
+    }));
+    assert_eq!(derived.balance().await.unwrap(), 7.5);
+    assert_eq!(derived.submissions(5), 3);
+
+    let configured = solver(json!({
+        "provider": "yescaptcha",
+        "key": "key",
+        "balance_endpoint": format!("{endpoint}/fail")
+    }));
+    assert!(matches!(
+        configured.balance().await,
+        Err(Error::FallbackSolverError(_))
+    ));
+    assert_eq!(configured.submissions(5), 5);
+}
+
+#[tokio::test]
+async fn test_fallback_validate() {
+    let endpoint = provider().await;
+
+    // A submission answered with too few answers fails
+    let short = solver(json!({
+        "provider": "capsolver",
+        "key": "key",
+        "endpoint": format!("{endpoint}/short"),
+        "limit": 3
+    }));
+    let err = short.solve(&task(3)).await.unwrap_err();
+    assert!(matches!(err, Error::InvalidFallbackResponse(..)));
+    assert_eq!(err.status(), 502);
+
+    // Answers are checked against the candidate count of each image
+    assert!(validate("ok", &[0, 5], &[Some(6), Some(6)]).is_ok());
+    assert!(validate("ok", &[0, 7], &[Some(6), None]).is_ok());
+    assert!(matches!(
+        validate("ok", &[0, 6], &[Some(6), Some(6)]),
+        Err(Error::InvalidFallbackResponse(..))
+    ));
+    assert!(validate("ok", &[-1], &[None]).is_err());
+    assert!(validate("ok", &[0], &[Some(6), Some(6)]).is_err());
+}
+
+#[tokio::test]
+async fn test_fallback_spend() {
+    let endpoint = provider().await;
+    let spend = Arc::new(Spend::default());
+    let spent = |name: &str| {
+        let status = spend.status();
+        let provider = status.providers.iter().find(|p| p.name == name).unwrap();
+        (provider.images, provider.tasks, provider.spent)
+    };
+
+    // Answers that don't fit the task are billed
+    let short = solver_with_spend(
+        json!({
+            "provider": "capsolver",
+            "name": "spend-short",
+            "key": "key",
+            "endpoint": format!("{endpoint}/short"),
+            "limit": 3,
+            "cost": 0.5
+        }),
+        spend.clone(),
+    );
+    assert!(short.solve(&task(3)).await.is_err());
+    assert_eq!(spent("spend-short"), (3, 1, 1.5));
+
+    // The submissions answered before a later one fails are billed
+    let picky = solver_with_spend(
+        json!({
+            "provider": "yescaptcha",
+            "name": "spend-picky",
+            "key": "key",
+            "endpoint": format!("{endpoint}/picky"),
+            "concurrency": 1,
+            "cost": 1.0
+        }),
+        spend.clone(),
+    );
+    let partial = Task {
+        api_key: None,
+        images: ["0", "1", "image"]
+            .map(|image| Arc::new(image.to_owned()))
+            .to_vec(),
+        game_variant_instructions: ("counting".to_owned(), "Pick the image".to_owned()),
+    };
+    assert!(matches!(
+        picky.solve(&partial).await,
+        Err(Error::FallbackSolverError(_))
+    ));
+    assert_eq!(spent("spend-picky"), (2, 2, 2.0));
+
+    // Every timed out attempt is billed
+    let slow = solver_with_spend(
+        json!({
+            "provider": "yescaptcha",
+            "name": "spend-slow",
+            "key": "key",
+            "endpoint": format!("{endpoint}/slow"),
+            "timeout": 1,
+            "retries": 1,
+            "backoff": 1,
+            "cost": 1.0
+        }),
+        spend.clone(),
+    );
+    assert!(slow.solve(&task(1)).await.is_err());
+    assert_eq!(spent("spend-slow"), (2, 2, 2.0));
+
+    // Rejected submissions aren't
+    let fail = solver_with_spend(
+        json!({
+            "provider": "yescaptcha",
+            "name": "spend-fail",
+            "key": "key",
+            "endpoint": format!("{endpoint}/fail"),
+            "cost": 1.0
+        }),
+        spend.clone(),
+    );
+    assert!(fail.solve(&task(1)).await.is_err());
+    assert!(spend
+        .status()
+        .providers
+        .iter()
+        .all(|p| p.name != "spend-fail"));
+}
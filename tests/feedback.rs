@@ -0,0 +1,68 @@
+use fs::{
+    error::Error,
+    serve::{Feedback, FeedbackConfig, FeedbackRequest, TaskRecord},
+};
+
+fn record() -> TaskRecord {
+    TaskRecord {
+        game_variant_instructions: ("counting".to_owned(), "Pick the image".to_owned()),
+        images: vec![],
+        answers: vec![1, 2],
+        predictions: vec![None, None],
+        local: true,
+    }
+}
+
+fn request(correct: bool, labels: Option<Vec<i32>>) -> FeedbackRequest {
+    FeedbackRequest {
+        api_key: None,
+        correct,
+        labels,
+    }
+}
+
+#[test]
+fn test_feedback_report() {
+    let feedback = Feedback::new(FeedbackConfig::default());
+    let id = feedback.record(record());
+
+    // Invalid labels keep the task
+    assert!(matches!(
+        feedback.report(&id, &request(false, Some(vec![1]))),
+        Err(Error::InvalidFeedback(_))
+    ));
+
+    let (_, labels) = feedback
+        .report(&id, &request(false, Some(vec![1, 3])))
+        .unwrap();
+    assert_eq!(labels, vec![1, 3]);
+
+    // A task accepts feedback once
+    assert!(matches!(
+        feedback.report(&id, &request(true, None)),
+        Err(Error::TaskNotFound(_))
+    ));
+
+    let status = feedback.status();
+    assert_eq!(status[0].game_variant, "counting");
+    assert_eq!(status[0].accuracy, 0.5);
+    assert!(!status[0].disabled);
+}
+
+#[test]
+fn test_feedback_disable() {
+    let feedback = Feedback::new(FeedbackConfig {
+        window: 4,
+        min_accuracy: Some(0.6),
+        ..Default::default()
+    });
+
+    let id = feedback.record(record());
+    feedback.report(&id, &request(true, None)).unwrap();
+    assert!(!feedback.disabled("counting"));
+
+    let id = feedback.record(record());
+    feedback.report(&id, &request(false, None)).unwrap();
+    assert!(feedback.disabled("counting"));
+    assert!(!feedback.disabled("card"));
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use base64::{engine::general_purpose, Engine as _};
+use common::{boot_args, dir};
+use fs::{
+    error::Error,
+    onnx::{GridLayout, Orientation, TileLayout},
+    serve::{AppState, Task},
+};
+use image::{DynamicImage, ImageFormat, RgbImage};
+use std::{io::Cursor, sync::Arc};
+
+/// A blank base64 PNG of the dimensions.
+fn png(width: u32, height: u32) -> Arc<String> {
+    let mut bytes = Cursor::new(vec![]);
+    DynamicImage::ImageRgb8(RgbImage::new(width, height))
+        .write_to(&mut bytes, ImageFormat::Png)
+        .unwrap();
+    Arc::new(general_purpose::STANDARD.encode(bytes.into_inner()))
+}
+
+/// Solve a task of one image with the configured models, without a fallback solver.
+async fn solve(name: &str, models: &str, game_variant: &str, image: Arc<String>) -> Error {
+    let dir = dir(name);
+    let config = dir.join("config.json");
+    std::fs::write(&config, format!(r#"{{ "models": {models} }}"#)).unwrap();
+    let state = AppState::new(boot_args(&config)).await.unwrap();
+    let task = Task {
+        api_key: None,
+        images: vec![image],
+        game_variant_instructions: (game_variant.to_owned(), "Pick the image".to_owned()),
+    };
+    let result = state.settings().process(&task).await;
+    std::fs::remove_dir_all(dir).unwrap();
+    match result {
+        Ok(_) => panic!("expected the task to fail"),
+        Err(err) => err,
+    }
+}
+
+#[test]
+fn test_tile_layout_detect() {
+    // Current FunCaptcha layout
+    let layout = TileLayout::detect((1200, 400)).unwrap();
+    assert_eq!(layout, TileLayout::default());
+    assert_eq!(layout.candidates((1200, 400)).unwrap(), 6);
+    assert_eq!(layout.tile_rect(2), (400, 0, 200, 200));
+
+    // Scaled layout
+    let layout = TileLayout::detect((600, 200)).unwrap();
+    assert_eq!(layout.tile, (100, 100));
+    assert_eq!(layout.reference, (0, 100, 67, 100));
+    assert_eq!(layout.candidates((600, 200)).unwrap(), 6);
+
+    // Ambiguous or mismatched dimensions
+    assert!(TileLayout::detect((1000, 401)).is_none());
+    assert!(TileLayout::detect((1000, 300)).is_none());
+}
+
+#[test]
+fn test_tile_layout_config() {
+    let layout: TileLayout = serde_json::from_str(
+        r#"{ "tile": [150, 150], "reference": [150, 0, 150, 150], "orientation": "vertical" }"#,
+    )
+    .unwrap();
+    assert_eq!(layout.orientation, Orientation::Vertical);
+    assert_eq!(layout.candidates((300, 600)).unwrap(), 4);
+    assert_eq!(layout.tile_rect(3), (0, 450, 150, 150));
+
+    // Reference crop out of bounds
+    assert!(matches!(
+        layout.candidates((150, 600)),
+        Err(Error::InvalidImageSize((150, 600)))
+    ));
+
+    // Missing fields fall back to the default layout
+    let layout: TileLayout = serde_json::from_str(r#"{ "tile": [200, 200] }"#).unwrap();
+    assert_eq!(layout, TileLayout::default());
+}
+
+#[test]
+fn test_grid_layout_detect() {
+    // Current FunCaptcha layout
+    let layout = GridLayout::detect((300, 200)).unwrap();
+    assert_eq!(layout, GridLayout::default());
+    assert_eq!(layout.cells((300, 200)).unwrap(), 6);
+    assert_eq!(layout.cell_rect(4), (100, 100, 100, 100));
+
+    // Scaled layout
+    let layout = GridLayout::detect((450, 300)).unwrap();
+    assert_eq!(layout.cell, (150, 150));
+    assert_eq!(layout.cell_rect(5), (300, 150, 150, 150));
+
+    assert!(GridLayout::detect((400, 200)).is_none());
+}
+
+#[test]
+fn test_grid_layout_config() {
+    let layout: GridLayout =
+        serde_json::from_str(r#"{ "rows": 3, "cols": 3, "cell": [120, 120] }"#).unwrap();
+    assert_eq!(layout.cells((360, 360)).unwrap(), 9);
+    assert_eq!(layout.cell_rect(7), (120, 240, 120, 120));
+
+    assert!(matches!(
+        layout.cells((300, 200)),
+        Err(Error::InvalidImageSize((300, 200)))
+    ));
+}
+
+#[tokio::test]
+async fn test_grid_layout_rejected() {
+    // An image that doesn't fit the configured grid isn't answered
+    let models = r#"{ "counting": { "model": "counting.onnx", "kind": "classifier", "grid": { "rows": 3, "cols": 3, "cell": [120, 120] } } }"#;
+    let err = solve("grid-layout", models, "counting", png(300, 200)).await;
+    assert!(matches!(err, Error::InvalidImageSize((300, 200))));
+    assert_eq!(err.status(), 400);
+}
+
+#[tokio::test]
+async fn test_tile_layout_rejected() {
+    // The reference crop of the configured tiles is out of the image
+    let models = r#"{ "3d_rollball_objects": { "model": "3d_rollball_objects.onnx", "kind": "pair_classifier", "tiles": { "tile": [150, 150], "reference": [150, 0, 150, 150], "orientation": "vertical" } } }"#;
+    let err = solve("tile-layout", models, "3d_rollball_objects", png(150, 600)).await;
+    assert!(matches!(err, Error::InvalidImageSize((150, 600))));
+
+    // No layout is detected from the image size
+    let err = solve("tile-detect", "{}", "3d_rollball_objects", png(1000, 300)).await;
+    assert!(matches!(err, Error::InvalidImageSize((1000, 300))));
+    assert_eq!(err.status(), 400);
+}
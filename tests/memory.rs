@@ -0,0 +1,126 @@
+use base64::{engine::general_purpose, Engine as _};
+use fs::{
+    error::Error,
+    onnx::{dhash, distance, Registry},
+    serve::{tile_hashes, MemoryConfig, MemoryEntry, TileMemory},
+};
+use image::{DynamicImage, ImageFormat, RgbImage};
+use std::io::Cursor;
+
+/// A 300x200 image with a different pattern in each 100x100 cell.
+fn grid(seed: u32) -> DynamicImage {
+    DynamicImage::ImageRgb8(RgbImage::from_fn(300, 200, |x, y| {
+        let cell = (y / 100) * 3 + x / 100 + seed;
+        let v = ((x * (cell + 1) + y * (cell + 3)) % 256) as u8;
+        image::Rgb([v, v.wrapping_mul(3), 255 - v])
+    }))
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat) -> Vec<u8> {
+    let mut bytes = vec![];
+    image
+        .write_to(&mut Cursor::new(&mut bytes), format)
+        .unwrap();
+    bytes
+}
+
+#[test]
+fn test_memory_hashes() {
+    let image = grid(0);
+    let jpeg = image::load_from_memory(&encode(&image, ImageFormat::Jpeg)).unwrap();
+    assert!(distance(dhash(&image), dhash(&jpeg)) <= 4);
+    assert!(distance(dhash(&image), dhash(&grid(7))) > 4);
+
+    let registry = Registry::default();
+    let classifier = registry.get("counting").unwrap();
+    assert_eq!(classifier.tile_hashes(&image).unwrap().len(), 6);
+
+    // The reference image and the candidate tiles of a pair classifier
+    let pair = registry.get("3d_rollball_objects").unwrap();
+    let tiles = DynamicImage::ImageRgb8(RgbImage::new(1200, 400));
+    assert_eq!(pair.tile_hashes(&tiles).unwrap().len(), 7);
+    assert!(pair.tile_hashes(&DynamicImage::new_rgb8(301, 7)).is_err());
+}
+
+#[test]
+fn test_memory_lookup() {
+    let memory = TileMemory::new(MemoryConfig {
+        threshold: 4,
+        min_confidence: Some(0.9),
+        ..Default::default()
+    });
+    let spec = Registry::default().get("counting").unwrap().clone();
+
+    let png = general_purpose::STANDARD.encode(encode(&grid(0), ImageFormat::Png));
+    let jpeg = general_purpose::STANDARD.encode(encode(&grid(0), ImageFormat::Jpeg));
+    let other = general_purpose::STANDARD.encode(encode(&grid(7), ImageFormat::Png));
+    let instructions = ("counting".to_owned(), "Pick the image".to_owned());
+
+    // Confirmed answers are remembered per variant and instructions
+    memory.confirm(&spec, &instructions, &[png.into()], &[4], true);
+    let hashes = tile_hashes(&spec, &jpeg).unwrap();
+    assert_eq!(
+        memory.lookup("counting", "pick the image!", &hashes),
+        Some(4)
+    );
+    assert_eq!(
+        memory.lookup("counting", "Pick another image", &hashes),
+        None
+    );
+    assert_eq!(memory.lookup("card", "Pick the image", &hashes), None);
+    let other_hashes = tile_hashes(&spec, &other).unwrap();
+    assert_eq!(
+        memory.lookup("counting", "Pick the image", &other_hashes),
+        None
+    );
+
+    // A wrong answer without a label is forgotten
+    memory.confirm(&spec, &instructions, &[jpeg.into()], &[], false);
+    assert_eq!(memory.lookup("counting", "Pick the image", &hashes), None);
+}
+
+#[test]
+fn test_memory_export() {
+    let memory = TileMemory::new(MemoryConfig::default());
+    memory.remember("counting", "Pick the image", vec![1, 2, 3], 2);
+    memory.remember("counting", "Pick the image", vec![1, 2, 3], 1);
+    memory.remember("card", "Pick the card", vec![u64::MAX], 0);
+
+    let entries = memory.export();
+    assert_eq!(
+        entries,
+        vec![
+            MemoryEntry {
+                game_variant: "card".to_owned(),
+                instructions: "pick the card".to_owned(),
+                hashes: vec!["ffffffffffffffff".to_owned()],
+                answer: 0,
+            },
+            MemoryEntry {
+                game_variant: "counting".to_owned(),
+                instructions: "pick the image".to_owned(),
+                hashes: vec![
+                    "0000000000000001".to_owned(),
+                    "0000000000000002".to_owned(),
+                    "0000000000000003".to_owned()
+                ],
+                answer: 1,
+            },
+        ]
+    );
+
+    let imported = TileMemory::new(MemoryConfig::default());
+    assert_eq!(imported.import(entries.clone()).unwrap(), 2);
+    assert_eq!(imported.export(), entries);
+    assert_eq!(
+        imported.lookup("card", "Pick the card", &[u64::MAX]),
+        Some(0)
+    );
+
+    let mut invalid = entries;
+    invalid[0].hashes[0] = "not hex".to_owned();
+    assert!(matches!(
+        imported.import(invalid),
+        Err(Error::InvalidMemory(_))
+    ));
+}
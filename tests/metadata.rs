@@ -0,0 +1,53 @@
+use fs::onnx::{ChannelOrder, ModelInfo, Preprocess, TensorInfo, TensorLayout};
+
+fn tensor(name: &str, shape: &[i64]) -> TensorInfo {
+    TensorInfo {
+        name: name.to_owned(),
+        dtype: "Float32".to_owned(),
+        shape: shape.to_vec(),
+    }
+}
+
+fn info() -> ModelInfo {
+    ModelInfo {
+        model: "card.onnx".to_owned(),
+        inputs: vec![tensor("input", &[-1, 3, 52, 52])],
+        outputs: vec![tensor("output", &[-1, 1])],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_metadata_input_shape() {
+    let mut info = info();
+    assert_eq!(info.input_shape(TensorLayout::Nchw), Some((52, 52)));
+
+    info.inputs[0].shape = vec![-1, -1, -1, 3];
+    assert_eq!(info.input_shape(TensorLayout::Nhwc), None);
+}
+
+#[test]
+fn test_metadata_validate() {
+    let info = info();
+    let inputs = vec!["input".to_owned()];
+    let preprocess = Preprocess::default();
+    assert!(info.validate(&inputs, (52, 52), &preprocess).is_ok());
+
+    // Wrong input name, shape and channel count
+    assert!(info
+        .validate(&["left".to_owned()], (52, 52), &preprocess)
+        .is_err());
+    assert!(info.validate(&inputs, (100, 100), &preprocess).is_err());
+    let luma = Preprocess {
+        channel_order: ChannelOrder::Luma,
+        ..Default::default()
+    };
+    assert!(info.validate(&inputs, (52, 52), &luma).is_err());
+
+    // Unknown output name
+    let output = Preprocess {
+        output: Some("score".to_owned()),
+        ..Default::default()
+    };
+    assert!(info.validate(&inputs, (52, 52), &output).is_err());
+}
@@ -0,0 +1,112 @@
+use axum::{body::Bytes, routing::post, Json, Router};
+use base64::{engine::general_purpose, Engine};
+use fs::{
+    onnx::{ModelInfo, Prediction, Predictor},
+    serve::{otlp, FallbackConfig, FallbackSolver, Task},
+};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::layer::SubscriberExt;
+
+/// Start a collector stand-in keeping the bodies posted to `/v1/traces`, and a
+/// fallback provider at `/ok` answering 2 for every image.
+async fn collector(bodies: Arc<Mutex<Vec<u8>>>) -> String {
+    let app = Router::new()
+        .route(
+            "/v1/traces",
+            post(move |body: Bytes| async move {
+                bodies.lock().unwrap().extend_from_slice(&body);
+            }),
+        )
+        .route(
+            "/ok",
+            post(|| async { Json(json!({ "errorId": 0, "solution": { "objects": [2] } })) }),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+/// A predictor answering 1 for every image.
+struct Fixed;
+
+impl Predictor for Fixed {
+    fn prediction(&self, _: image::DynamicImage) -> fs::Result<Prediction> {
+        Ok(Prediction {
+            answer: 1,
+            scores: vec![],
+        })
+    }
+
+    fn active(&self) -> bool {
+        true
+    }
+
+    fn models(&self) -> Vec<ModelInfo> {
+        vec![]
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_otlp_export() {
+    let bodies = Arc::new(Mutex::new(vec![]));
+    let base = collector(bodies.clone()).await;
+
+    let provider = otlp::provider(&base).unwrap();
+    let subscriber = tracing_subscriber::registry().with(otlp::layer(&provider));
+    let guard = tracing::subscriber::set_default(subscriber);
+
+    // Decode spans of a prediction
+    let mut png = vec![];
+    image::DynamicImage::new_rgb8(2, 2)
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    let image = general_purpose::STANDARD.encode(png);
+    assert_eq!(Fixed.predict_base64(&image).unwrap(), 1);
+
+    // Fallback provider call spans
+    let config: FallbackConfig = serde_json::from_value(json!({
+        "provider": "yescaptcha",
+        "key": "a",
+        "endpoint": format!("{base}/ok"),
+    }))
+    .unwrap();
+    let solver = FallbackSolver::from_config(config, Arc::default()).unwrap();
+    let task = Task {
+        api_key: None,
+        images: (0..2).map(|_| Arc::new("image".to_owned())).collect(),
+        game_variant_instructions: ("counting".to_owned(), "Pick the image".to_owned()),
+    };
+    assert_eq!(solver.solve(&task).await.unwrap(), [2, 2]);
+
+    drop(guard);
+    tokio::task::spawn_blocking(move || provider.force_flush())
+        .await
+        .unwrap()
+        .unwrap();
+
+    // The protobuf export carries the span names and attributes as plain strings
+    let bodies = bodies.lock().unwrap();
+    let exported = String::from_utf8_lossy(&bodies);
+    for expected in [
+        "decode_base64",
+        "decode_image",
+        "fallback",
+        "yescaptcha",
+        "counting",
+        "outcome",
+        "ok",
+    ] {
+        assert!(exported.contains(expected), "missing {expected}");
+    }
+}
+
+#[test]
+fn test_otlp_outcome() {
+    assert_eq!(otlp::outcome(&Ok(())), "ok");
+    assert_eq!(
+        otlp::outcome::<()>(&Err(fs::error::Error::InvalidImages)),
+        "error"
+    );
+}
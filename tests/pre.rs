@@ -27,8 +27,8 @@ pub fn test_predictor(dir: &str, predictor: Arc<dyn Predictor>) -> Result<(), Bo
                 } else {
                     // parse the label from the filename 2024-06-28-09-42-1321773_marked_3.jpg
                     let label = filepath
-                        .split('_')
-                        .last()
+                        .rsplit('_')
+                        .next()
                         .unwrap()
                         .split('.')
                         .next()
@@ -0,0 +1,54 @@
+use fs::onnx::{Activation, ChannelOrder, Preprocess, TensorLayout};
+use image::{DynamicImage, Rgb, RgbImage};
+
+fn image() -> DynamicImage {
+    DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 2, Rgb([255, 0, 51])))
+}
+
+#[test]
+fn test_preprocess_default() {
+    let tensor = Preprocess::default().tensor(image(), (4, 2)).unwrap();
+    assert_eq!(tensor.shape(), &[1, 3, 2, 4]);
+    assert_eq!(tensor[[0, 0, 1, 3]], 1.0);
+    assert_eq!(tensor[[0, 1, 0, 0]], 0.0);
+    assert_eq!(tensor[[0, 2, 0, 0]], 0.2);
+}
+
+#[test]
+fn test_preprocess_config() {
+    let preprocess: Preprocess = serde_json::from_str(
+        r#"{
+            "filter": "nearest",
+            "mean": [0.5, 0.5, 0.5],
+            "std": [0.5, 0.5, 0.5],
+            "channel_order": "bgr",
+            "layout": "nhwc",
+            "inputs": ["image"],
+            "activation": "softmax",
+            "score_index": 1
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(preprocess.inputs, Some(vec!["image".to_owned()]));
+
+    let tensor = preprocess.tensor(image(), (2, 2)).unwrap();
+    assert_eq!(tensor.shape(), &[1, 2, 2, 3]);
+    assert_eq!(tensor[[0, 0, 0, 0]], (0.2 - 0.5) / 0.5);
+    assert_eq!(tensor[[0, 0, 0, 2]], 1.0);
+
+    let scores = preprocess.activate(vec![0.0, 0.0]);
+    assert_eq!(scores, vec![0.5, 0.5]);
+}
+
+#[test]
+fn test_preprocess_luma() {
+    let preprocess = Preprocess {
+        channel_order: ChannelOrder::Luma,
+        layout: TensorLayout::Nchw,
+        activation: Activation::Sigmoid,
+        ..Default::default()
+    };
+    let tensor = preprocess.tensor(image(), (4, 2)).unwrap();
+    assert_eq!(tensor.shape(), &[1, 1, 2, 4]);
+    assert_eq!(preprocess.activate(vec![0.0]), vec![0.5]);
+}
@@ -0,0 +1,70 @@
+use fs::{
+    config::Config,
+    error::Error,
+    onnx::{ModelSpec, PredictorKind, Registry, Variant},
+};
+
+#[test]
+fn test_registry_default() {
+    let registry = Registry::default();
+    assert_eq!(registry.keys().count(), Variant::const_count());
+
+    for variant in Variant::ALL {
+        let spec = registry.get(variant.as_str()).unwrap();
+        assert_eq!(spec.model, ModelSpec::from(variant).model);
+    }
+
+    let spec = registry.get("numericalmatch").unwrap();
+    assert_eq!(spec.kind, PredictorKind::PairClassifier);
+    assert_eq!(spec.input_shape, Some((100, 100)));
+
+    assert!(matches!(
+        registry.get("unknown"),
+        Err(Error::UnknownVariantType(_))
+    ));
+}
+
+#[test]
+fn test_registry_config() {
+    let config: Config = serde_json::from_str(
+        r#"{
+            "models": {
+                "new_game": { "model": "new_game.onnx", "kind": "classifier", "grayscale": true },
+                "card": { "model": "card_v2.onnx", "kind": "classifier", "input_shape": [64, 64] }
+            }
+        }"#,
+    )
+    .unwrap();
+
+    let registry = config.models;
+    assert_eq!(registry.keys().count(), Variant::const_count() + 1);
+
+    let spec = registry.get("new_game").unwrap();
+    assert_eq!(spec.kind, PredictorKind::Classifier);
+    assert!(spec.grayscale);
+
+    let spec = registry.get("card").unwrap();
+    assert_eq!(spec.model, "card_v2.onnx");
+    assert_eq!(spec.input_shape, Some((64, 64)));
+
+    // Compiled-in entries are kept
+    assert!(registry.get("3d_rollball_objects").is_ok());
+}
+
+#[test]
+fn test_registry_candidates() {
+    let registry = Registry::default();
+
+    // Pair classifiers have one candidate per tile
+    let spec = registry.get("numericalmatch").unwrap();
+    assert_eq!(spec.candidates((1200, 400)).unwrap(), 6);
+
+    // Classifiers have one candidate per grid cell
+    let spec = registry.get("counting").unwrap();
+    assert_eq!(spec.kind, PredictorKind::Classifier);
+    assert_eq!(spec.candidates((300, 200)).unwrap(), 6);
+    assert!(matches!(
+        spec.candidates((300, 300)),
+        Err(Error::InvalidImageSize(_))
+    ));
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use common::{boot_args, dir};
+use fs::{config::Config, error::Error, serve::AppState};
+
+fn invalid(config: &str) -> String {
+    let config: Config = serde_json::from_str(config).unwrap();
+    match config.validate() {
+        Err(Error::InvalidConfig(err)) => err,
+        other => panic!("expected an invalid configuration, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_reload_config() {
+    let config: Config = serde_json::from_str(
+        r#"{
+            "api_key": "reloaded-key",
+            "limit": 3,
+            "routes": [{ "game_variant": "3d_rollball_objects", "instructions": "pick the ball" }],
+            "fallbacks": [{ "provider": "capsolver", "key": "fallback-key" }]
+        }"#,
+    )
+    .unwrap();
+    config.validate().unwrap();
+    assert_eq!(config.api_key.as_deref(), Some("reloaded-key"));
+    assert_eq!(config.limit, Some(3));
+
+    // The command line settings apply if unset
+    let config = Config::default();
+    config.validate().unwrap();
+    assert_eq!(config.api_key, None);
+    assert_eq!(config.limit, None);
+}
+
+#[test]
+fn test_reload_invalid_config() {
+    assert_eq!(invalid(r#"{ "limit": 0 }"#), "limit must be at least 1");
+    assert_eq!(invalid(r#"{ "api_key": "" }"#), "api_key must not be empty");
+    assert_eq!(
+        invalid(r#"{ "routes": [{ "game_variant": "no_such_game" }] }"#),
+        "unknown game variant no_such_game"
+    );
+    assert_eq!(
+        invalid(r#"{ "experiments": { "no_such_game": { "model": "new.onnx" } } }"#),
+        "unknown game variant no_such_game"
+    );
+    assert_eq!(
+        invalid(r#"{ "fallbacks": [{ "provider": "yescaptcha", "key": "" }] }"#),
+        "fallback yescaptcha has no key"
+    );
+    assert_eq!(
+        invalid(
+            r#"{ "fallbacks": [{ "provider": "capsolver", "name": "backup", "key": "k", "limit": 0 }] }"#
+        ),
+        "fallback backup limit must be at least 1"
+    );
+    assert_eq!(
+        Error::InvalidConfig("limit must be at least 1".to_owned()).to_string(),
+        "Invalid configuration: limit must be at least 1"
+    );
+}
+
+#[tokio::test]
+async fn test_reload_state() {
+    let dir = dir("reload");
+    let config = dir.join("config.json");
+    std::fs::write(&config, r#"{ "api_key": "one", "limit": 2 }"#).unwrap();
+
+    let state = AppState::new(boot_args(&config)).await.unwrap();
+    let before = state.settings();
+    assert_eq!(before.api_key(), Some("one"));
+    assert_eq!(before.limit(), 2);
+
+    // A valid configuration is applied, requests in flight keep their settings
+    std::fs::write(&config, r#"{ "api_key": "two" }"#).unwrap();
+    state.reload().await.unwrap();
+    assert_eq!(state.settings().api_key(), Some("two"));
+    assert_eq!(state.settings().limit(), 1);
+    assert_eq!(before.api_key(), Some("one"));
+
+    // An invalid one is rejected as a whole, the running settings are kept
+    std::fs::write(&config, r#"{ "api_key": "three", "limit": 0 }"#).unwrap();
+    assert!(matches!(state.reload().await, Err(Error::InvalidConfig(_))));
+    std::fs::write(&config, r#"{ "api_key": "three", "#).unwrap();
+    assert!(matches!(
+        state.reload().await,
+        Err(Error::SerdeJsonError(_))
+    ));
+    assert_eq!(state.settings().api_key(), Some("two"));
+    assert_eq!(state.settings().limit(), 1);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
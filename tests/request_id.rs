@@ -0,0 +1,70 @@
+use axum::{routing::post, Json, Router};
+use fs::serve::{
+    request_id::{current, traced, X_REQUEST_ID},
+    TaskResult,
+};
+use serde_json::Value;
+
+/// Start a server answering `/task` with an error result and `/current` with
+/// the request id in scope.
+async fn server() -> String {
+    let app = Router::new()
+        .route(
+            "/task",
+            post(|| async {
+                Json(
+                    TaskResult::builder()
+                        .error("failed".to_owned())
+                        .solved(false)
+                        .build(),
+                )
+            }),
+        )
+        .route("/current", post(|| async { current().unwrap_or_default() }));
+    let app = traced(app);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+#[tokio::test]
+async fn test_request_id_echoed() {
+    let base = server().await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!("{base}/task"))
+        .header(X_REQUEST_ID, "abc-123")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()[X_REQUEST_ID], "abc-123");
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["request_id"], "abc-123");
+    assert_eq!(body["error"], "failed");
+
+    let resp = client
+        .post(format!("{base}/current"))
+        .header(X_REQUEST_ID, "def-456")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.text().await.unwrap(), "def-456");
+}
+
+#[tokio::test]
+async fn test_request_id_generated() {
+    let base = server().await;
+    let client = reqwest::Client::new();
+
+    let resp = client.post(format!("{base}/task")).send().await.unwrap();
+    let header = resp.headers()[X_REQUEST_ID].to_str().unwrap().to_owned();
+    assert!(!header.is_empty());
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["request_id"], header.as_str());
+
+    let other = client.post(format!("{base}/task")).send().await.unwrap();
+    assert_ne!(other.headers()[X_REQUEST_ID].to_str().unwrap(), header);
+}
@@ -0,0 +1,61 @@
+use fs::{config::Config, error::Error};
+
+const CONFIG: &str = r#"{
+    "routes": [
+        {
+            "game_variant": "counting",
+            "instructions": "Pick the image where the total equals 5!",
+            "model": "counting_5.onnx"
+        },
+        {
+            "game_variant": "counting",
+            "pattern": "^pick the image where the total equals \\d+$",
+            "input_shape": [64, 64],
+            "answers": [5, 4, 3, 2, 1, 0]
+        },
+        {
+            "game_variant": "3d_rollball_objects",
+            "pattern": "direction of the hand"
+        }
+    ]
+}"#;
+
+#[test]
+fn test_route_resolve() {
+    let config: Config = serde_json::from_str(CONFIG).unwrap();
+    let routes = config.routes;
+    assert_eq!(routes.len(), 3);
+
+    // Exact normalised instructions
+    let (index, route) = routes
+        .resolve("counting", "Pick the image where the  total equals 5")
+        .unwrap()
+        .unwrap();
+    assert_eq!(index, 0);
+    assert_eq!(route.options.model.as_deref(), Some("counting_5.onnx"));
+
+    // Regex pattern with answer remapping
+    let (index, route) = routes
+        .resolve("counting", "Pick the image where the total equals 7.")
+        .unwrap()
+        .unwrap();
+    assert_eq!(index, 1);
+    assert_eq!(route.options.input_shape, Some((64, 64)));
+    assert_eq!(route.map_answer(1), 4);
+    assert_eq!(route.map_answer(9), 9);
+
+    // Game variant without routes uses the compiled-in predictor
+    assert!(routes.resolve("card", "anything").unwrap().is_none());
+
+    // Routed game variant with unknown instructions
+    assert!(matches!(
+        routes.resolve("3d_rollball_objects", "Rotate the animal upright"),
+        Err(Error::UnknownInstructionVariant(..))
+    ));
+}
+
+#[test]
+fn test_route_invalid_pattern() {
+    let config = r#"{"routes": [{"game_variant": "counting", "pattern": "("}]}"#;
+    assert!(serde_json::from_str::<Config>(config).is_err());
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use base64::{engine::general_purpose, Engine as _};
+use common::dir;
+use fs::{
+    onnx::Prediction,
+    serve::{Sample, SampleConfig, SampleReason, SampleStore},
+};
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+use std::{
+    io::Cursor,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+fn image() -> Arc<String> {
+    let mut bytes = Cursor::new(vec![]);
+    DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, Rgb([10, 20, 30])))
+        .write_to(&mut bytes, ImageFormat::Png)
+        .unwrap();
+    Arc::new(format!(
+        "data:image/png;base64,{}",
+        general_purpose::STANDARD.encode(bytes.into_inner())
+    ))
+}
+
+fn config(dir: &std::path::Path) -> SampleConfig {
+    serde_json::from_value(serde_json::json!({ "dir": dir, "min_confidence": 0.5 })).unwrap()
+}
+
+#[test]
+fn test_sample_store() {
+    let dir = dir("samples");
+    let mut config = config(&dir);
+    assert!(config.feedback);
+
+    let prediction = Prediction {
+        answer: 1,
+        scores: vec![vec![0.1, 0.4, 0.2]],
+    };
+    let store = SampleStore::new(config.clone(), None);
+    assert!(store.low_confidence(&prediction));
+
+    let sample = Sample {
+        game_variant_instructions: ("counting".to_owned(), "Pick the image".to_owned()),
+        image: image(),
+        reason: SampleReason::FallbackDisagreement,
+        label: Some(3),
+        prediction: Some(prediction),
+        fallback: Some(3),
+    };
+    let files = store.save(&sample).unwrap();
+    let name = files[0].file_name().unwrap().to_string_lossy().into_owned();
+    assert!(name.ends_with("_marked_3.jpg"));
+    assert_eq!(files[0].parent().unwrap(), dir.join("counting"));
+    assert_eq!(std::fs::read_to_string(&files[1]).unwrap(), "3");
+    let saved = image::open(&files[0]).unwrap();
+    assert_eq!((saved.width(), saved.height()), (8, 8));
+
+    // Nothing is removed within the bounds
+    store.prune().unwrap();
+    assert!(files.iter().all(|file| file.exists()));
+
+    // Everything is removed over the size bound
+    config.max_bytes = Some(0);
+    SampleStore::new(config, None).prune().unwrap();
+    assert!(files.iter().all(|file| !file.exists()));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_sample_unlabelled() {
+    let dir = dir("samples-unlabelled");
+    let mut config = config(&dir);
+    let store = SampleStore::new(config.clone(), None);
+
+    // The local model's answer isn't saved as a label
+    let sample = |label| Sample {
+        game_variant_instructions: ("counting".to_owned(), "Pick the image".to_owned()),
+        image: image(),
+        reason: SampleReason::LowConfidence,
+        label,
+        prediction: Some(Prediction {
+            answer: 1,
+            scores: vec![vec![0.1, 0.4, 0.2]],
+        }),
+        fallback: None,
+    };
+    let unlabelled = store.save(&sample(None)).unwrap();
+    assert_eq!(unlabelled.len(), 2);
+    assert_eq!(
+        unlabelled[0].parent().unwrap(),
+        dir.join("counting").join("unlabelled")
+    );
+    assert!(!unlabelled[0].to_string_lossy().contains("_marked_"));
+    let meta: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&unlabelled[1]).unwrap()).unwrap();
+    assert!(meta["label"].is_null());
+    assert_eq!(meta["prediction"]["answer"], 1);
+
+    // The oldest sample is removed whole, the newer ones are kept whole
+    let labelled = store.save(&sample(Some(2))).unwrap();
+    let older = SystemTime::now() - Duration::from_secs(60);
+    for file in &unlabelled {
+        std::fs::File::options()
+            .write(true)
+            .open(file)
+            .unwrap()
+            .set_modified(older)
+            .unwrap();
+    }
+    let size = |files: &[std::path::PathBuf]| {
+        files
+            .iter()
+            .map(|file| file.metadata().unwrap().len())
+            .sum::<u64>()
+    };
+    config.max_bytes = Some(size(&labelled) + size(&unlabelled) - 1);
+    SampleStore::new(config, None).prune().unwrap();
+    assert!(unlabelled.iter().all(|file| !file.exists()));
+    assert!(labelled.iter().all(|file| file.exists()));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
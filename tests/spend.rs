@@ -0,0 +1,118 @@
+mod common;
+
+use axum::{routing::post, Json, Router};
+use base64::{engine::general_purpose, Engine as _};
+use common::{boot_args, dir};
+use fs::{
+    error::Error,
+    serve::{metrics::metrics, AppState, Spend, SpendConfig, Task},
+};
+use image::{DynamicImage, ImageFormat, RgbImage};
+use serde_json::json;
+use std::{io::Cursor, sync::Arc};
+
+#[test]
+fn test_spend_budget() {
+    let spend = Spend::new(SpendConfig {
+        daily_budget: Some(5.0),
+        balance_interval: 0,
+    });
+    assert!(spend.check().is_ok());
+
+    spend.record("budget-capsolver", 3, 1, 1.0);
+    spend.record("budget-yescaptcha", 1, 1, 1.5);
+    assert!(spend.check().is_ok());
+
+    spend.record("budget-capsolver", 1, 1, 1.0);
+    assert!(matches!(spend.check(), Err(Error::BudgetExceeded(_))));
+    assert_eq!(spend.check().unwrap_err().status(), 503);
+
+    let status = spend.status();
+    assert!(status.exhausted);
+    assert_eq!(status.spent_today, 5.5);
+    assert_eq!(status.providers[0].name, "budget-capsolver");
+    assert_eq!(status.providers[0].images, 4);
+    assert_eq!(status.providers[0].tasks, 2);
+    assert_eq!(status.providers[0].spent, 4.0);
+    assert_eq!(
+        metrics().get(
+            "fs_fallback_images_total",
+            &[("provider", "budget-capsolver")]
+        ),
+        4.0
+    );
+
+    // A reloaded budget applies to the spend so far
+    spend.configure(SpendConfig {
+        daily_budget: Some(10.0),
+        balance_interval: 0,
+    });
+    assert!(spend.check().is_ok());
+    assert_eq!(spend.status().spent_today, 5.5);
+}
+
+#[test]
+fn test_spend_balance() {
+    let spend = Spend::new(SpendConfig::default());
+    spend.set_balance("balance", 12.5);
+    spend.record("balance", 2, 2, 0.0);
+
+    // Without a budget the fallback is never disabled
+    assert!(spend.check().is_ok());
+    let status = spend.status();
+    assert!(!status.exhausted);
+    assert_eq!(status.providers[0].balance, Some(12.5));
+    assert_eq!(
+        metrics().get("fs_fallback_balance", &[("provider", "balance")]),
+        12.5
+    );
+}
+
+#[tokio::test]
+async fn test_spend_invalid_answers() {
+    // A stand-in provider answering out of range
+    let app = Router::new().route(
+        "/createTask",
+        post(|| async { Json(json!({ "errorId": 0, "solution": { "objects": [99] } })) }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}/createTask", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    // The model can't be fetched, the task goes to the provider
+    let dir = dir("spend");
+    let config = dir.join("config.json");
+    let fallbacks = json!({
+        "fallbacks": [{
+            "provider": "yescaptcha",
+            "name": "spend-invalid",
+            "key": "key",
+            "endpoint": endpoint,
+            "cost": 2.0
+        }]
+    });
+    std::fs::write(&config, fallbacks.to_string()).unwrap();
+    let state = AppState::new(boot_args(&config)).await.unwrap();
+
+    let mut png = Cursor::new(vec![]);
+    DynamicImage::ImageRgb8(RgbImage::new(300, 200))
+        .write_to(&mut png, ImageFormat::Png)
+        .unwrap();
+    let task = Task {
+        api_key: None,
+        images: vec![Arc::new(general_purpose::STANDARD.encode(png.into_inner()))],
+        game_variant_instructions: ("counting".to_owned(), "Pick the image".to_owned()),
+    };
+    let err = match state.settings().process(&task).await {
+        Ok(_) => panic!("expected the answers to be rejected"),
+        Err(err) => err,
+    };
+    assert!(matches!(err, Error::InvalidFallbackResponse(..)));
+
+    // The rejected answers are billed
+    let labels = [("provider", "spend-invalid")];
+    assert_eq!(metrics().get("fs_fallback_images_total", &labels), 1.0);
+    assert_eq!(metrics().get("fs_fallback_spend_total", &labels), 2.0);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,148 @@
+#![cfg(target_family = "unix")]
+
+mod common;
+
+use clap::Parser;
+use common::dir;
+use fs::{
+    daemon::service::{env_file_content, exec_args, quote, service_unit, socket_unit},
+    serve::systemd::{listen_fds, notify, watchdog_interval},
+    Commands, Opt, ServiceCommand,
+};
+use std::{os::unix::net::UnixDatagram, path::Path, time::Duration};
+
+fn install_args(args: &[&str]) -> fs::ServiceArgs {
+    let argv = ["fs", "service", "install"].iter().chain(args);
+    match Opt::try_parse_from(argv).unwrap().commands {
+        Commands::Service(ServiceCommand::Install(args)) => args,
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_systemd_env() {
+    // All environment changes are made in this test, tests run in parallel
+    let dir = dir("notify");
+    let path = dir.join("notify.sock");
+    let socket = UnixDatagram::bind(&path).unwrap();
+
+    std::env::remove_var("NOTIFY_SOCKET");
+    assert!(!notify("READY=1").unwrap());
+    std::env::set_var("NOTIFY_SOCKET", &path);
+    assert!(notify("READY=1\nSTATUS=Listening").unwrap());
+    let mut buf = [0; 64];
+    let len = socket.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"READY=1\nSTATUS=Listening");
+    std::env::remove_var("NOTIFY_SOCKET");
+
+    // The sockets are passed to another process
+    let pid = std::process::id().to_string();
+    std::env::set_var("LISTEN_FDS", "1");
+    std::env::set_var("LISTEN_PID", "1");
+    assert_eq!(listen_fds(), None);
+    std::env::set_var("LISTEN_PID", &pid);
+    assert_eq!(listen_fds(), Some(1));
+    std::env::set_var("LISTEN_FDS", "0");
+    assert_eq!(listen_fds(), None);
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_PID");
+
+    assert_eq!(watchdog_interval(), None);
+    std::env::set_var("WATCHDOG_USEC", "60000000");
+    assert_eq!(watchdog_interval(), Some(Duration::from_secs(30)));
+    std::env::set_var("WATCHDOG_PID", "1");
+    assert_eq!(watchdog_interval(), None);
+    std::env::set_var("WATCHDOG_PID", &pid);
+    assert_eq!(watchdog_interval(), Some(Duration::from_secs(30)));
+    std::env::remove_var("WATCHDOG_USEC");
+    std::env::remove_var("WATCHDOG_PID");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_systemd_quote() {
+    assert_eq!(quote("0.0.0.0:8000"), "0.0.0.0:8000");
+    assert_eq!(quote("/opt/fs models"), "\"/opt/fs models\"");
+    assert_eq!(quote("a\"b"), "\"a\\\"b\"");
+    assert_eq!(quote("100%"), "100%%");
+    assert_eq!(quote("$HOME"), "$$HOME");
+    assert_eq!(quote(""), "\"\"");
+}
+
+#[test]
+fn test_systemd_exec_args() {
+    let args = install_args(&[
+        "--socket",
+        "--watchdog",
+        "30",
+        "--bind",
+        "127.0.0.1:8001",
+        "--api-key",
+        "secret-key",
+        "--model-dir",
+        "models",
+        "--limit",
+        "5",
+        "--allocator",
+        "arena",
+        "--fallback-solver",
+        "capsolver",
+        "--fallback-key",
+        "fallback-key",
+        "--fallback-timeout",
+        "20",
+        "--fallback-connect-timeout",
+        "5",
+        "--log-format",
+        "json",
+        "s3",
+        "--bucket-name",
+        "bucket",
+        "--url",
+        "https://s3.example.com",
+        "--client-id",
+        "id",
+        "--secret",
+        "store-secret",
+    ]);
+
+    // The arguments parse back to the same server, without the keys
+    let argv = exec_args(&args.boot).unwrap();
+    assert!(!argv.iter().any(|arg| arg.contains("secret")));
+    // The store secret is read from the environment file
+    let argv = std::iter::once("fs".to_owned())
+        .chain(argv)
+        .chain(["--secret".to_owned(), "store-secret".to_owned()]);
+    let Commands::Run(boot) = Opt::try_parse_from(argv).unwrap().commands else {
+        unreachable!()
+    };
+    let mut expected = args.boot.clone();
+    expected.api_key = None;
+    expected.fallback_key = None;
+    expected.model_dir = Some(std::path::absolute("models").unwrap());
+    assert_eq!(format!("{boot:?}"), format!("{expected:?}"));
+
+    let unit = service_unit(
+        &args,
+        Path::new("/usr/bin/fs"),
+        Path::new("/etc/default/fs"),
+    )
+    .unwrap();
+    assert!(unit.contains("Type=notify\n"));
+    assert!(unit.contains("ExecStart=/usr/bin/fs run "));
+    assert!(unit.contains(" --preload "));
+    assert!(unit.contains("EnvironmentFile=-/etc/default/fs\n"));
+    assert!(unit.contains("WatchdogSec=30\n"));
+    assert!(unit.contains("ExecReload=/bin/kill -HUP $MAINPID\n"));
+    assert!(unit.contains("TimeoutStopSec=50\n"));
+    assert!(unit.contains("Requires=fs.socket\n"));
+    assert!(!unit.contains("secret"));
+
+    assert!(socket_unit(&args).contains("ListenStream=127.0.0.1:8001\n"));
+
+    let env = env_file_content(&args);
+    assert!(env.contains("FS_API_KEY=\"secret-key\"\n"));
+    assert!(env.contains("FS_FALLBACK_KEY=\"fallback-key\"\n"));
+    assert!(env.contains("FS_STORE_SECRET=\"store-secret\"\n"));
+}